@@ -0,0 +1,101 @@
+//! Measures the throughput of `TokenBucket::acquire` and
+//! `TokenBucket::allow1`, and of contended acquisitions from a
+//! `SharedTokenBucket` and an `AtomicTokenBucket`.
+//!
+//! Run with `cargo bench --bench acquire`. This is a plain timing loop
+//! rather than a harness-based benchmark so it builds without any
+//! extra dependencies.
+
+use std::hint::black_box;
+use std::thread;
+use std::time::Instant;
+use tokenbucket::{AtomicTokenBucket, SharedTokenBucket, TokenBucket};
+
+const ITERATIONS: u32 = 10_000_000;
+const THREADS: u32 = 8;
+
+// Runs `f` ITERATIONS times and prints the mean time per call.
+fn bench(name: &str, mut f: impl FnMut()) {
+    // Warm up caches and the branch predictor before timing.
+    for _ in 0..ITERATIONS / 10 {
+        f();
+    }
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{:<24} {:>8.1} ns/iter ({:.1}M/s)",
+        name,
+        elapsed.as_nanos() as f64 / f64::from(ITERATIONS),
+        f64::from(ITERATIONS) / elapsed.as_secs_f64() / 1_000_000.0,
+    );
+}
+
+// Runs `f` ITERATIONS times in total, spread across THREADS threads
+// running at once, and prints the mean time per call across all
+// threads.
+fn bench_contended(name: &str, f: impl Fn() + Sync) {
+    let start = Instant::now();
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                for _ in 0..ITERATIONS / THREADS {
+                    f();
+                }
+            });
+        }
+    });
+    let elapsed = start.elapsed();
+
+    println!(
+        "{:<24} {:>8.1} ns/iter ({:.1}M/s)",
+        name,
+        elapsed.as_nanos() as f64 / f64::from(ITERATIONS),
+        f64::from(ITERATIONS) / elapsed.as_secs_f64() / 1_000_000.0,
+    );
+}
+
+fn main() {
+    // A bucket that never runs out measures the allow path.
+    let mut allowed = TokenBucket::new(1e12, 1e12);
+    bench("acquire (allowed)", || {
+        black_box(allowed.acquire(black_box(1.0))).ok();
+    });
+
+    // A bucket with an effectively unlimited burst value is never
+    // clamped, so nothing it replenishes overflows.
+    let mut plentiful = TokenBucket::new(1.0, 1e15);
+    bench("acquire (plentiful)", || {
+        black_box(plentiful.acquire(black_box(1.0))).ok();
+    });
+
+    // A bucket that never refills measures the deny path.
+    let mut denied = TokenBucket::new(0.0, 0.0);
+    bench("acquire (denied)", || {
+        black_box(denied.acquire(black_box(1.0))).ok();
+    });
+
+    let mut allowed = TokenBucket::new(1e12, 1e12);
+    bench("allow1 (allowed)", || {
+        black_box(allowed.allow1());
+    });
+
+    let mut denied = TokenBucket::new(0.0, 0.0);
+    bench("allow1 (denied)", || {
+        black_box(denied.allow1());
+    });
+
+    let shared = SharedTokenBucket::new(TokenBucket::new(1e12, 1e12));
+    bench_contended("shared (contended)", || {
+        black_box(shared.acquire(black_box(1.0))).ok();
+    });
+
+    let atomic = AtomicTokenBucket::new(1e12, 1e12);
+    bench_contended("atomic (contended)", || {
+        black_box(atomic.acquire(black_box(1.0))).ok();
+    });
+}
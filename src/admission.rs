@@ -0,0 +1,130 @@
+/// Represents the verdict of an
+/// [AdmissionPolicy](trait.AdmissionPolicy.html) on an acquisition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    /// Leave the decision to the token check.
+    Defer,
+    /// Deny the acquisition even if the bucket holds enough tokens,
+    /// with `Err(TokenAcquisitionError::Vetoed)`. No tokens are taken.
+    Veto,
+    /// Allow the acquisition even if the bucket doesn't hold enough
+    /// tokens. An acquisition the tokens cover takes them as usual, one
+    /// they don't cover takes none.
+    Override,
+}
+
+/// Represents admission logic beyond token availability that a
+/// [TokenBucket](struct.TokenBucket.html) consults on every
+/// acquisition, e.g. a circuit breaker or a maintenance switch.
+///
+/// Set it with
+/// [TokenBucketBuilder.admission_policy()](struct.TokenBucketBuilder.html#method.admission_policy).
+/// The policy is asked after the bucket has checked its tokens, with
+/// whether they cover the acquisition, and may veto or override that
+/// decision. Acquisitions rejected as invalid, e.g. for exceeding
+/// `max_cost`, never reach it. A
+/// [MultiRateLimiter](struct.MultiRateLimiter.html) asks it twice per
+/// acquisition, once to check and once to acquire.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::{Admission, AdmissionPolicy, TokenAcquisitionError, TokenBucket};
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use std::sync::Arc;
+///
+/// // Denies every acquisition while the service is under maintenance.
+/// struct Maintenance(AtomicBool);
+///
+/// impl AdmissionPolicy for Maintenance {
+///     fn admit(&self, _count: f64, _covered: bool) -> Admission {
+///         if self.0.load(Ordering::Relaxed) {
+///             Admission::Veto
+///         } else {
+///             Admission::Defer
+///         }
+///     }
+/// }
+///
+/// let maintenance = Arc::new(Maintenance(AtomicBool::new(false)));
+/// let mut tb = TokenBucket::builder(5.0, 100.0).admission_policy(maintenance.clone()).build();
+/// assert!(tb.acquire(1.0).is_ok());
+///
+/// maintenance.0.store(true, Ordering::Relaxed);
+/// assert_eq!(tb.acquire(1.0), Err(TokenAcquisitionError::Vetoed));
+/// ```
+pub trait AdmissionPolicy: Send + Sync {
+    /// Returns the verdict on acquiring `count` tokens, where `covered`
+    /// is whether the bucket holds enough tokens for it.
+    fn admit(&self, count: f64, covered: bool) -> Admission;
+}
+
+/// Represents the default admission policy, which admits every
+/// acquisition the bucket's tokens cover and nothing else.
+///
+/// A bucket without a policy behaves the same, so this is only needed
+/// as a stand-in, e.g. for a policy that is switched off.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AlwaysAdmit;
+
+impl AdmissionPolicy for AlwaysAdmit {
+    fn admit(&self, _count: f64, _covered: bool) -> Admission {
+        Admission::Defer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{TokenAcquisitionError, TokenBucket};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    // This module contains unit tests for the AdmissionPolicy implementation.
+
+    // Vetoes everything while under maintenance, and lets acquisitions
+    // of up to `priority` tokens through regardless of the bucket.
+    struct Gate {
+        maintenance: AtomicBool,
+        priority:    f64,
+    }
+
+    impl AdmissionPolicy for Gate {
+        fn admit(&self, count: f64, _covered: bool) -> Admission {
+            if self.maintenance.load(Ordering::Relaxed) {
+                Admission::Veto
+            } else if count <= self.priority {
+                Admission::Override
+            } else {
+                Admission::Defer
+            }
+        }
+    }
+
+    /// 1. **Vetoes and Overrides**:
+    ///    - Test acquisitions through a policy that overrides small acquisitions and vetoes everything during maintenance, and through the default policy.
+    ///    - Overridden acquisitions should succeed without tokens, vetoed ones should fail untouched, and the default policy should leave the bucket's decision alone.
+    #[test]
+    fn test_admission_policy() {
+        let gate = Arc::new(Gate {
+            maintenance: AtomicBool::new(false),
+            priority:    1.0,
+        });
+        let mut bucket = TokenBucket::builder(0.0, 5.0).admission_policy(gate.clone()).build();
+
+        assert!(bucket.acquire(4.0).is_ok());
+        assert!(matches!(bucket.acquire(2.0), Err(TokenAcquisitionError::RateLimited { .. })));
+        assert!(bucket.acquire(1.0).is_ok());
+        assert!(bucket.acquire(1.0).is_ok());
+        assert_eq!(bucket.remaining_after(0.0), Some(0.0));
+
+        gate.maintenance.store(true, Ordering::Relaxed);
+        let mut vetoed = TokenBucket::builder(0.0, 5.0).admission_policy(gate).build();
+        assert_eq!(vetoed.acquire(1.0), Err(TokenAcquisitionError::Vetoed));
+        assert_eq!(vetoed.remaining_after(0.0), Some(5.0));
+
+        let mut default = TokenBucket::builder(0.0, 5.0).admission_policy(Arc::new(AlwaysAdmit)).build();
+        assert!(default.acquire(5.0).is_ok());
+        assert!(default.acquire(1.0).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{Clock, TokenAcquisitionError, TokenAcquisitionResult};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// Represents a token bucket that can be acquired from by many threads
+/// at once without a lock.
+///
+/// A [SharedTokenBucket](struct.SharedTokenBucket.html) serializes
+/// every acquisition on a mutex, which dominates on many-core hot
+/// paths. An AtomicTokenBucket instead packs its whole state into a
+/// single `AtomicU64` and acquires with a compare-and-swap loop, so
+/// callers never block each other.
+///
+/// The state is the time, in nanoseconds since the UNIX epoch, at which
+/// the bucket would have been empty if nothing had been acquired since:
+/// the tokens at any time follow from the time elapsed since then at
+/// rate `r`, clamped to `b`, and an acquisition of `count` tokens moves
+/// it forward by `count / r` seconds. This is why the rate must be
+/// positive. In exchange for being lock-free the bucket only supports
+/// plain acquisitions: none of the optional settings of
+/// [TokenBucket](struct.TokenBucket.html) are available.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::AtomicTokenBucket;
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let bucket = Arc::new(AtomicTokenBucket::new(0.001, 4.0));
+/// let handles: Vec<_> = (0..4).map(|_| {
+///     let bucket = Arc::clone(&bucket);
+///     thread::spawn(move || bucket.acquire(1.0).is_ok())
+/// }).collect();
+/// for handle in handles {
+///     assert!(handle.join().unwrap());
+/// }
+/// assert!(bucket.acquire(1.0).is_err());
+/// ```
+pub struct AtomicTokenBucket {
+    r:     f64,
+    b:     f64,
+    // Represents the time it takes to refill the bucket from empty, in
+    // nanoseconds. The zero time is never further in the past than
+    // this, which is how the tokens are clamped to `b`.
+    fill:  u64,
+    // Represents the time at which the bucket would have been empty,
+    // in nanoseconds since the UNIX epoch.
+    zero:  AtomicU64,
+    clock: Option<Arc<dyn Clock>>,
+}
+
+impl AtomicTokenBucket {
+    /// Returns a new, full AtomicTokenBucket. See
+    /// [TokenBucket::new()](struct.TokenBucket.html#method.new) for the
+    /// meaning of `r` and `b`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `r` isn't positive and finite, or if `b` is negative.
+    pub fn new(r: f64, b: f64) -> AtomicTokenBucket {
+        AtomicTokenBucket::build(r, b, None)
+    }
+
+    /// Returns a new, full AtomicTokenBucket that reads the current
+    /// time from `clock`, e.g. a
+    /// [ManualClock](struct.ManualClock.html) in tests.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `r` isn't positive and finite, or if `b` is negative.
+    pub fn with_clock(r: f64, b: f64, clock: impl Clock + 'static) -> AtomicTokenBucket {
+        AtomicTokenBucket::build(r, b, Some(Arc::new(clock)))
+    }
+
+    fn build(r: f64, b: f64, clock: Option<Arc<dyn Clock>>) -> AtomicTokenBucket {
+        assert!(r > 0.0 && r.is_finite(), "an AtomicTokenBucket needs a positive rate");
+        assert!(b >= 0.0, "an AtomicTokenBucket needs a burst value that isn't negative");
+
+        let mut bucket = AtomicTokenBucket {
+            r,
+            b,
+            fill: (b / r * 1e9) as u64,
+            zero: AtomicU64::new(0),
+            clock,
+        };
+        let now = bucket.now();
+        *bucket.zero.get_mut() = now.saturating_sub(bucket.fill);
+        bucket
+    }
+
+    /// Attempts to acquire `count` tokens from the bucket.
+    ///
+    /// Unlike
+    /// [TokenBucket.acquire()](struct.TokenBucket.html#method.acquire)
+    /// the bucket keeps no history of its acquisitions, so the rate
+    /// reported in `Ok(rate)` and `Err(TokenAcquisitionError::RateLimited
+    /// { rate, .. })` is the refill rate `r`, and `scarce_for` is always
+    /// zero.
+    ///
+    /// A `count` that isn't finite is denied. As with a TokenBucket, a
+    /// negative `count` is granted and returns its tokens to the
+    /// bucket, up to the burst value.
+    pub fn acquire(&self, count: f64) -> TokenAcquisitionResult {
+        let denied = Err(TokenAcquisitionError::RateLimited {
+            rate:       self.r,
+            scarce_for: Duration::ZERO,
+        });
+        if !count.is_finite() {
+            return denied;
+        }
+        let now = self.now();
+        let ns = count / self.r * 1e9;
+
+        let mut zero = self.zero.load(Ordering::Acquire);
+        loop {
+            let start = zero.max(now.saturating_sub(self.fill));
+            if self.tokens_between(start, now) < count {
+                return denied;
+            }
+
+            // A refund moves the zero time back, which is clamped to
+            // `fill` before `now` when it is read.
+            let next = if ns < 0.0 { start.saturating_sub((-ns) as u64) } else { start + ns.ceil() as u64 };
+            match self.zero.compare_exchange_weak(zero, next, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => return Ok(self.r),
+                Err(actual) => zero = actual,
+            }
+        }
+    }
+
+    /// Returns the number of tokens currently in the bucket.
+    pub fn tokens(&self) -> f64 {
+        let now = self.now();
+        let start = self.zero.load(Ordering::Acquire).max(now.saturating_sub(self.fill));
+        self.tokens_between(start, now)
+    }
+
+    // Returns the tokens accrued from `start` to `now`, in nanoseconds
+    // since the UNIX epoch, clamped to the burst value.
+    fn tokens_between(&self, start: u64, now: u64) -> f64 {
+        self.b.min(now.saturating_sub(start) as f64 * self.r / 1e9)
+    }
+
+    // Returns the current time in nanoseconds since the UNIX epoch.
+    fn now(&self) -> u64 {
+        let now = match &self.clock {
+            Some(clock) => clock.now(),
+            None => SystemTime::now(),
+        };
+        now.duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ManualClock;
+    use std::thread;
+
+    // This module contains unit tests for the AtomicTokenBucket implementation.
+
+    /// 1. **Refill and Clamp**:
+    ///    - Test draining the bucket, refilling it partially and past the burst value, and acquiring more than it holds.
+    ///    - The tokens should follow the rate, stop at the burst value, and acquisitions should only succeed while they are covered.
+    #[test]
+    fn test_refill_and_clamp() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000));
+        let bucket = AtomicTokenBucket::with_clock(4.0, 10.0, clock.clone());
+        assert_eq!(bucket.tokens(), 10.0);
+        assert!(bucket.acquire(10.0).is_ok());
+        assert!(bucket.acquire(1.0).is_err());
+
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(bucket.tokens(), 2.0);
+        assert!(bucket.acquire(3.0).is_err());
+        assert!(bucket.acquire(2.0).is_ok());
+
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(bucket.tokens(), 10.0);
+        assert!(bucket.acquire(11.0).is_err());
+        assert!(bucket.acquire(10.0).is_ok());
+    }
+
+    /// 2. **Invalid and Negative Counts**:
+    ///    - Test acquiring a NaN and infinite counts from a full bucket, then negative counts from a partially drained one.
+    ///    - Counts that aren't finite should be denied without taking tokens, and negative counts should be granted and return their tokens up to the burst value.
+    #[test]
+    fn test_invalid_and_negative_counts() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000));
+        let bucket = AtomicTokenBucket::with_clock(4.0, 10.0, clock);
+        for &count in &[f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert!(matches!(bucket.acquire(count), Err(TokenAcquisitionError::RateLimited { .. })));
+            assert_eq!(bucket.tokens(), 10.0);
+        }
+
+        assert!(bucket.acquire(6.0).is_ok());
+        assert!(bucket.acquire(-2.0).is_ok());
+        assert_eq!(bucket.tokens(), 6.0);
+        assert!(bucket.acquire(-20.0).is_ok());
+        assert_eq!(bucket.tokens(), 10.0);
+    }
+
+    /// 3. **Concurrent Acquisition**:
+    ///    - Test many threads acquiring single tokens from a bucket that barely refills, all at once.
+    ///    - Exactly as many acquisitions as the bucket holds should succeed.
+    #[test]
+    fn test_concurrent_acquisition() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000));
+        let bucket = Arc::new(AtomicTokenBucket::with_clock(1.0, 1_000.0, clock));
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let bucket = Arc::clone(&bucket);
+                thread::spawn(move || (0..500).filter(|_| bucket.acquire(1.0).is_ok()).count())
+            })
+            .collect();
+        let admitted: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+
+        assert_eq!(admitted, 1_000);
+        assert_eq!(bucket.tokens(), 0.0);
+    }
+}
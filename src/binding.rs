@@ -0,0 +1,124 @@
+use crate::{MultiRateLimiter, TokenBucket};
+use std::time::Duration;
+
+/// Represents the bucket that currently limits an acquisition the
+/// most among several limits that all apply, as returned by
+/// [BindingConstraint::min_of()](#method.min_of).
+///
+/// Knowing which limit binds turns a plain denial into a useful reason
+/// for clients, e.g. "throttled by the per-minute limit, retry in 12s".
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::{BindingConstraint, TokenBucket};
+/// let names = ["per-second", "per-minute"];
+/// let mut limiter = TokenBucket::new(10.0, 10.0) & TokenBucket::new(1.0, 60.0);
+/// for _ in 0..10 {
+///     limiter.acquire(1.0).unwrap();
+/// }
+///
+/// let binding = BindingConstraint::min_of(limiter.buckets(), 1.0).unwrap();
+/// assert_eq!(names[binding.index], "per-second");
+/// assert!(binding.wait > std::time::Duration::ZERO);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BindingConstraint {
+    /// The index of the binding bucket in the slice it was chosen from.
+    pub index:    usize,
+    /// The number of tokens the binding bucket would have left after
+    /// the acquisition, not counting its reserve. This is negative if
+    /// the bucket can't afford it yet.
+    pub headroom: f64,
+    /// The time until the binding bucket can afford the acquisition,
+    /// or `Duration::MAX` if it never can because the acquisition
+    /// exceeds its burst value.
+    pub wait:     Duration,
+}
+
+impl BindingConstraint {
+    /// Returns the bucket in `buckets` that limits an acquisition of
+    /// `count` tokens the most, or `None` if `buckets` is empty.
+    ///
+    /// The binding bucket is the one with the longest wait until it
+    /// can afford `count`. While every bucket can afford it right away,
+    /// it is the one with the least headroom left afterwards; ties go
+    /// to the bucket that comes first. Nothing is acquired.
+    pub fn min_of(buckets: &[TokenBucket], count: f64) -> Option<BindingConstraint> {
+        buckets
+            .iter()
+            .enumerate()
+            .map(|(index, bucket)| BindingConstraint::of(index, bucket, count))
+            .reduce(|binding, other| {
+                if (other.wait, -other.headroom) > (binding.wait, -binding.headroom) {
+                    other
+                } else {
+                    binding
+                }
+            })
+    }
+
+    fn of(index: usize, bucket: &TokenBucket, count: f64) -> BindingConstraint {
+        let (tokens, _) = bucket.replenished(bucket.now());
+        let wait = if count + bucket.reserve > bucket.b {
+            Duration::MAX
+        } else {
+            bucket.time_until_available(count)
+        };
+
+        BindingConstraint {
+            index,
+            headroom: tokens - bucket.reserve - count,
+            wait,
+        }
+    }
+}
+
+impl MultiRateLimiter {
+    /// Returns the bucket that limits an acquisition of `count` tokens
+    /// the most, or `None` if there are no buckets. See
+    /// [BindingConstraint::min_of()](struct.BindingConstraint.html#method.min_of).
+    pub fn binding_constraint(&self, count: f64) -> Option<BindingConstraint> {
+        BindingConstraint::min_of(self.buckets(), count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ManualClock;
+    use std::time::SystemTime;
+
+    // This module contains unit tests for the BindingConstraint implementation.
+
+    /// 1. **Binding Window Changes**:
+    ///    - Test a per-second and a per-minute bucket, exhausting first the per-second one and then, over several seconds, the per-minute one.
+    ///    - The per-second bucket should bind first, the per-minute bucket should bind once it runs low, and the reported wait should match its refill rate.
+    #[test]
+    fn test_binding_constraint_changes() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let per_second = TokenBucket::builder(10.0, 10.0).clock(clock.clone()).build();
+        let per_minute = TokenBucket::builder(1.0, 30.0).clock(clock.clone()).build();
+        let mut limiter = per_second & per_minute;
+
+        let binding = limiter.binding_constraint(1.0).unwrap();
+        assert_eq!((binding.index, binding.wait), (0, Duration::ZERO));
+
+        assert!(limiter.acquire(10.0).is_ok());
+        let binding = limiter.binding_constraint(1.0).unwrap();
+        assert_eq!((binding.index, binding.headroom), (0, -1.0));
+        assert_eq!(binding.wait, Duration::from_millis(100));
+
+        for _ in 0..2 {
+            clock.advance(Duration::from_secs(1));
+            assert!(limiter.acquire(10.0).is_ok());
+        }
+        clock.advance(Duration::from_secs(1));
+        let binding = limiter.binding_constraint(10.0).unwrap();
+        assert_eq!((binding.index, binding.headroom), (1, -7.0));
+        assert_eq!(binding.wait, Duration::from_secs(7));
+
+        assert_eq!(limiter.binding_constraint(40.0).unwrap().wait, Duration::MAX);
+        assert_eq!(BindingConstraint::min_of(&[], 1.0), None);
+    }
+}
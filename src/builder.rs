@@ -0,0 +1,524 @@
+use crate::ewma::Ewma;
+#[cfg(feature = "metrics")]
+use crate::MetricNames;
+#[cfg(feature = "opentelemetry")]
+use crate::Meter;
+use crate::{AdmissionPolicy, Clock, RoundingMode, SharedTokenBucket, TokenBucket, EPSILON};
+#[cfg(feature = "rand")]
+use std::collections::hash_map::RandomState;
+#[cfg(feature = "rand")]
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// Builds a [TokenBucket](struct.TokenBucket.html) with optional
+/// settings that aren't covered by
+/// [TokenBucket::new()](struct.TokenBucket.html#method.new).
+///
+/// Obtain one from
+/// [TokenBucket::builder()](struct.TokenBucket.html#method.builder).
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::TokenBucket;
+/// let mut tb = TokenBucket::builder(5.0, 100.0)
+///     .max_cost(10.0)
+///     .build();
+/// assert!(tb.acquire(20.0).is_err());
+/// ```
+#[derive(Clone)]
+pub struct TokenBucketBuilder {
+    r:            f64,
+    b:            f64,
+    max_cost:     Option<f64>,
+    reserve:      f64,
+    clock:        Option<Arc<dyn Clock>>,
+    // Represents the seed used to pick a random initial token count.
+    // `None` means the bucket starts full.
+    #[cfg(feature = "rand")]
+    jitter:       Option<u64>,
+    integer_only: bool,
+    // Represents the bounds `b` is adapted within. `None` means `b` is
+    // fixed.
+    adaptive:     Option<(f64, f64)>,
+    penalty:      Option<(f64, f64)>,
+    // Represents the averaging window of the observed rate. `None`
+    // means the rate isn't observed.
+    observe:      Option<Duration>,
+    idle_cap:     Option<Duration>,
+    shadow:       bool,
+    epsilon:      f64,
+    rounding:     RoundingMode,
+    grace:        u64,
+    admission:    Option<Arc<dyn AdmissionPolicy>>,
+    #[cfg(feature = "opentelemetry")]
+    meter:        Option<Arc<dyn Meter>>,
+    #[cfg(feature = "metrics")]
+    metric_names: MetricNames,
+}
+
+impl TokenBucketBuilder {
+    pub(crate) fn new(r: f64, b: f64) -> TokenBucketBuilder {
+        TokenBucketBuilder {
+            r,
+            b,
+            max_cost: None,
+            reserve: 0.0,
+            clock: None,
+            #[cfg(feature = "rand")]
+            jitter: None,
+            integer_only: false,
+            adaptive: None,
+            penalty: None,
+            observe: None,
+            idle_cap: None,
+            shadow: false,
+            epsilon: EPSILON,
+            rounding: RoundingMode::Fractional,
+            grace: 0,
+            admission: None,
+            #[cfg(feature = "opentelemetry")]
+            meter: None,
+            #[cfg(feature = "metrics")]
+            metric_names: MetricNames::DEFAULT,
+        }
+    }
+
+    /// Sets the largest number of tokens a single acquisition may
+    /// request.
+    ///
+    /// Any call to `acquire(count)` with `count > max_cost` will
+    /// return `Err(TokenAcquisitionError::CostExceedsMax { .. })`
+    /// without touching the bucket. This is useful for catching
+    /// miscomputed costs early, e.g. passing a byte count where a
+    /// request count was expected.
+    pub fn max_cost(mut self, max_cost: f64) -> TokenBucketBuilder {
+        self.max_cost = Some(max_cost);
+        self
+    }
+
+    /// Sets the number of tokens held back from ordinary acquisitions.
+    ///
+    /// `acquire(count)` only succeeds if `tokens - reserve >= count`,
+    /// while
+    /// [TokenBucket.acquire_reserve()](struct.TokenBucket.html#method.acquire_reserve)
+    /// may use every token in the bucket. This keeps headroom
+    /// available for emergency-only acquisitions. Defaults to `0.0`.
+    pub fn reserve(mut self, reserve: f64) -> TokenBucketBuilder {
+        self.reserve = reserve;
+        self
+    }
+
+    /// Sets the clock the bucket reads the current time from.
+    ///
+    /// Defaults to the system clock. A
+    /// [ManualClock](struct.ManualClock.html) lets tests and
+    /// simulations control time precisely. Note that the blocking
+    /// acquisition methods still sleep the current thread, so they
+    /// only return once the configured clock has moved far enough.
+    pub fn clock(mut self, clock: impl Clock + 'static) -> TokenBucketBuilder {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Sets whether the bucket only hands out whole tokens.
+    ///
+    /// In integer-only mode any acquisition of a fractional `count`,
+    /// e.g. `acquire(0.5)`, returns
+    /// `Err(TokenAcquisitionError::FractionalCount { .. })`, and
+    /// replenishment only makes whole tokens available. Fractions of a
+    /// token keep accruing internally, so no capacity is lost to
+    /// rounding. Defaults to `false`.
+    pub fn integer_only(mut self, integer_only: bool) -> TokenBucketBuilder {
+        self.integer_only = integer_only;
+        self
+    }
+
+    /// Sets whether the bucket runs in shadow mode, e.g. while rolling
+    /// out a new limit.
+    ///
+    /// In shadow mode every acquisition the limit would deny for lack
+    /// of tokens returns `Ok`, but the bucket otherwise behaves as if
+    /// it enforced its limit: such an acquisition takes no tokens, is
+    /// still reported to
+    /// [on_denied()](struct.TokenBucket.html#method.on_denied) and to
+    /// metrics, and is counted in
+    /// [TokenBucket.shadow_denials()](struct.TokenBucket.html#method.shadow_denials).
+    /// Shadow denials don't cost a
+    /// [denial_penalty()](#method.denial_penalty) or shrink an
+    /// [adaptive_burst()](#method.adaptive_burst). Acquisitions that are
+    /// invalid, e.g. for exceeding `max_cost`, or vetoed by the
+    /// admission policy still fail. This shows what a limit would
+    /// throttle before it throttles anything. Defaults to `false`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut tb = TokenBucket::builder(0.0, 1.0).shadow(true).build();
+    /// assert!(tb.acquire(1.0).is_ok());
+    /// assert!(tb.acquire(1.0).is_ok());
+    /// assert_eq!(tb.shadow_denials(), 1);
+    /// ```
+    pub fn shadow(mut self, shadow: bool) -> TokenBucketBuilder {
+        self.shadow = shadow;
+        self
+    }
+
+    /// Sets the tolerance for float rounding when the bucket compares
+    /// token counts, in tokens.
+    ///
+    /// An acquisition succeeds if the tokens available fall short of
+    /// `count` by no more than `epsilon`, in which case the bucket is
+    /// left empty rather than a rounding error below it, and
+    /// [TokenBucket.is_full()](struct.TokenBucket.html#method.is_full)
+    /// and
+    /// [TokenBucket.is_empty()](struct.TokenBucket.html#method.is_empty)
+    /// allow for the same difference. Set it to `0.0` for exact
+    /// comparisons, or raise it for buckets counting large amounts.
+    /// Defaults to [EPSILON](constant.EPSILON.html), `1e-9`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `epsilon` is negative or not finite.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// // 0.1 + 0.2 is slightly more than 0.3.
+    /// let mut tb = TokenBucket::builder(0.0, 0.3).build();
+    /// assert!(tb.acquire(0.1 + 0.2).is_ok());
+    ///
+    /// let mut exact = TokenBucket::builder(0.0, 0.3).epsilon(0.0).build();
+    /// assert!(exact.acquire(0.1 + 0.2).is_err());
+    /// ```
+    pub fn epsilon(mut self, epsilon: f64) -> TokenBucketBuilder {
+        assert!(epsilon >= 0.0 && epsilon.is_finite(), "epsilon must be non-negative and finite");
+        self.epsilon = epsilon;
+        self
+    }
+
+    /// Sets how the tokens accrued on every refill are rounded before
+    /// they are credited, e.g. `RoundingMode::Floor` to only ever credit
+    /// whole tokens. See [RoundingMode](enum.RoundingMode.html) for how
+    /// the modes interact with the rate. Defaults to
+    /// `RoundingMode::Fractional`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::{ManualClock, RoundingMode, TokenBucket};
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+    /// let mut tb = TokenBucket::builder(2.0, 10.0).rounding(RoundingMode::Floor).clock(clock.clone()).build();
+    /// tb.drain();
+    /// clock.advance(Duration::from_millis(1250));
+    /// assert_eq!(tb.remaining_after(0.0), Some(2.0));
+    /// ```
+    pub fn rounding(mut self, rounding: RoundingMode) -> TokenBucketBuilder {
+        self.rounding = rounding;
+        self
+    }
+
+    /// Sets the number of initial acquisitions that succeed regardless
+    /// of the tokens in the bucket, e.g. to let a new client complete a
+    /// handshake before limiting kicks in.
+    ///
+    /// Each of the first `grace` acquisitions succeeds without taking
+    /// any tokens, unless it is invalid, e.g. exceeds the `max_cost`.
+    /// After that the bucket limits as usual.
+    /// [TokenBucket.reset()](struct.TokenBucket.html#method.reset)
+    /// restores the allowance. Defaults to `0`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut tb = TokenBucket::builder(0.0, 1.0).grace(2).build();
+    /// assert!(tb.acquire(1.0).is_ok());
+    /// assert!(tb.acquire(1.0).is_ok());
+    /// assert!(tb.acquire(1.0).is_ok());
+    /// assert!(tb.acquire(1.0).is_err());
+    /// ```
+    pub fn grace(mut self, grace: u64) -> TokenBucketBuilder {
+        self.grace = grace;
+        self
+    }
+
+    /// Sets the policy consulted on every acquisition after the token
+    /// check, which may veto or override the bucket's decision. See
+    /// [AdmissionPolicy](trait.AdmissionPolicy.html). By default the
+    /// token check decides alone, like with
+    /// [AlwaysAdmit](struct.AlwaysAdmit.html).
+    pub fn admission_policy(mut self, policy: Arc<dyn AdmissionPolicy>) -> TokenBucketBuilder {
+        self.admission = Some(policy);
+        self
+    }
+
+    /// Lets the burst value adapt to the client's behavior within
+    /// `[min_b, max_b]`, starting from `b` clamped to that range.
+    ///
+    /// Every successful acquisition that leaves at least half the burst
+    /// value in the bucket, i.e. by a client staying under the rate,
+    /// grows `b` by 5% of the range up to `max_b`. Every acquisition
+    /// denied for lack of tokens, i.e. by a client spiking, halves the
+    /// distance between `b` and `min_b`. Well-behaved clients are thus
+    /// slowly rewarded with larger bursts, which they quickly lose
+    /// again when misbehaving. Shrinking `b` discards any tokens above
+    /// the new burst value, while growing it doesn't add any.
+    ///
+    /// A full bucket denying a cost above `b` isn't spiking, so such a
+    /// denial grows `b` like a well-behaved acquisition, until the cost
+    /// fits again. Blocking acquisitions only reject costs above
+    /// `max_b` as exceeding the burst.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min_b` is larger than `max_b`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut tb = TokenBucket::builder(5.0, 10.0)
+    ///     .adaptive_burst(10.0, 50.0)
+    ///     .build();
+    /// tb.acquire(1.0).unwrap();
+    /// assert_eq!(tb.status().burst, 12.0);
+    /// ```
+    pub fn adaptive_burst(mut self, min_b: f64, max_b: f64) -> TokenBucketBuilder {
+        assert!(min_b <= max_b, "adaptive burst range must not be empty");
+        self.adaptive = Some((min_b, max_b));
+        self
+    }
+
+    /// Makes every acquisition denied for lack of tokens remove another
+    /// `penalty` tokens from the bucket, without ever taking it more
+    /// than `max_debt` tokens below empty.
+    ///
+    /// This deliberately deviates from pure token bucket semantics,
+    /// where a denial costs nothing: a client hammering an empty bucket
+    /// pushes its own recovery further out with every denied request,
+    /// by up to `max_debt / r` seconds, while a client that backs off
+    /// recovers as usual. Defaults to no penalty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `penalty` or `max_debt` is negative.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// use std::time::Duration;
+    ///
+    /// let mut tb = TokenBucket::builder(1.0, 1.0)
+    ///     .denial_penalty(1.0, 10.0)
+    ///     .build();
+    /// tb.acquire(1.0).unwrap();
+    /// assert!(tb.acquire(1.0).is_err());
+    /// assert!(tb.time_until_available(1.0) > Duration::from_secs(1));
+    /// ```
+    pub fn denial_penalty(mut self, penalty: f64, max_debt: f64) -> TokenBucketBuilder {
+        assert!(penalty >= 0.0 && max_debt >= 0.0, "denial penalty must not be negative");
+        self.penalty = Some((penalty, max_debt));
+        self
+    }
+
+    /// Makes the bucket keep a moving average of the rate at which
+    /// tokens are acquired, with a time constant of `window`.
+    ///
+    /// See
+    /// [TokenBucket.observed_rate()](struct.TokenBucket.html#method.observed_rate)
+    /// and
+    /// [TokenBucket.rate_diverges()](struct.TokenBucket.html#method.rate_diverges).
+    /// Defaults to not observing the rate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is zero.
+    pub fn observe_rate(mut self, window: Duration) -> TokenBucketBuilder {
+        assert!(!window.is_zero(), "rate observation window must not be zero");
+        self.observe = Some(window);
+        self
+    }
+
+    /// Limits the tokens that accrue over a single idle stretch, i.e.
+    /// a time without any acquisition attempts, to those accruing
+    /// within `max_idle`.
+    ///
+    /// After a long idle period a bucket is full and allows its whole
+    /// burst at once, which can hammer a backend that just recovered.
+    /// With this setting it holds at most what it held before the idle
+    /// stretch plus `max_idle` worth of tokens, independently of the
+    /// burst value. Denied attempts end an idle stretch, so a caller
+    /// that keeps retrying still sees tokens accrue at the full rate,
+    /// although waiting for more tokens than accrue within `max_idle`
+    /// may take several attempts. Defaults to no limit.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::{ManualClock, TokenBucket};
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+    /// let mut tb = TokenBucket::builder(1.0, 100.0)
+    ///     .max_idle_accrual(Duration::from_secs(10))
+    ///     .clock(clock.clone())
+    ///     .build();
+    /// tb.drain();
+    ///
+    /// clock.advance(Duration::from_secs(3600));
+    /// assert_eq!(tb.drain(), 10.0);
+    /// ```
+    pub fn max_idle_accrual(mut self, max_idle: Duration) -> TokenBucketBuilder {
+        self.idle_cap = Some(max_idle);
+        self
+    }
+
+    /// Sets the names the bucket reports the outcome of every
+    /// acquisition and its fill level under to the recorder installed
+    /// with [set_recorder()](fn.set_recorder.html), e.g. to tell
+    /// buckets apart. Defaults to
+    /// [MetricNames::DEFAULT](struct.MetricNames.html#associatedconstant.DEFAULT).
+    ///
+    /// Only available with the `metrics` feature.
+    #[cfg(feature = "metrics")]
+    pub fn metric_names(mut self, names: MetricNames) -> TokenBucketBuilder {
+        self.metric_names = names;
+        self
+    }
+
+    /// Makes the bucket report the outcome of every acquisition and its
+    /// fill level to `meter`, e.g. counters and a gauge of an
+    /// OpenTelemetry `Meter`. See [Meter](trait.Meter.html) for the
+    /// reported metrics.
+    ///
+    /// Only available with the `opentelemetry` feature.
+    #[cfg(feature = "opentelemetry")]
+    pub fn with_meter(mut self, meter: Arc<dyn Meter>) -> TokenBucketBuilder {
+        self.meter = Some(meter);
+        self
+    }
+
+    /// Starts the bucket with a random number of tokens in `[0, b]`
+    /// instead of full.
+    ///
+    /// When many identical instances start at once, buckets that all
+    /// start full let the whole fleet burst against its backend in the
+    /// same second. Randomizing the initial fill phase-shifts the
+    /// buckets and smooths the aggregate startup load.
+    ///
+    /// Every call picks a different seed. Use
+    /// [jitter_initial_tokens_with_seed()](#method.jitter_initial_tokens_with_seed)
+    /// for reproducible results.
+    ///
+    /// Only available with the `rand` feature.
+    #[cfg(feature = "rand")]
+    pub fn jitter_initial_tokens(self) -> TokenBucketBuilder {
+        let seed = RandomState::new().build_hasher().finish();
+        self.jitter_initial_tokens_with_seed(seed)
+    }
+
+    /// Starts the bucket with a number of tokens in `[0, b]` derived
+    /// from `seed` instead of full.
+    ///
+    /// The same seed always yields the same initial token count. See
+    /// [jitter_initial_tokens()](#method.jitter_initial_tokens).
+    ///
+    /// Only available with the `rand` feature.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut tb = TokenBucket::builder(5.0, 100.0)
+    ///     .jitter_initial_tokens_with_seed(42)
+    ///     .build();
+    /// assert!(tb.drain() <= 100.0);
+    /// ```
+    #[cfg(feature = "rand")]
+    pub fn jitter_initial_tokens_with_seed(mut self, seed: u64) -> TokenBucketBuilder {
+        self.jitter = Some(seed);
+        self
+    }
+
+    /// Returns the configured TokenBucket.
+    pub fn build(self) -> TokenBucket {
+        let last = match &self.clock {
+            Some(clock) => clock.now(),
+            None => SystemTime::now(),
+        };
+        let b = match self.adaptive {
+            Some((min_b, max_b)) => self.b.clamp(min_b, max_b),
+            None => self.b,
+        };
+        #[cfg(feature = "rand")]
+        let tokens = match self.jitter {
+            Some(seed) => b * unit_interval(seed),
+            None => b,
+        };
+        #[cfg(not(feature = "rand"))]
+        let tokens = b;
+
+        TokenBucket {
+            tokens,
+            adaptive: self.adaptive,
+            penalty: self.penalty,
+            idle_cap: self.idle_cap,
+            shadow: self.shadow,
+            epsilon: self.epsilon,
+            rounding: self.rounding,
+            grace: self.grace,
+            grace_left: self.grace,
+            admission: self.admission,
+            observed: self.observe.map(Ewma::new),
+            max_cost: self.max_cost,
+            reserve: self.reserve,
+            integer_only: self.integer_only,
+            clock: self.clock,
+            #[cfg(feature = "opentelemetry")]
+            meter: self.meter,
+            #[cfg(feature = "metrics")]
+            metric_names: Some(self.metric_names),
+            last,
+            ..TokenBucket::new(self.r, b)
+        }
+    }
+
+    /// Returns the configured TokenBucket wrapped in a
+    /// [SharedTokenBucket](struct.SharedTokenBucket.html), ready to be
+    /// used from several threads.
+    ///
+    /// Cloning the returned handle does not copy the bucket: every
+    /// clone draws from the same tokens.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let bucket = TokenBucket::builder(5.0, 1.0).build_shared();
+    /// let other = bucket.clone();
+    /// assert!(bucket.acquire(1.0).is_ok());
+    /// assert!(other.acquire(1.0).is_err());
+    /// ```
+    pub fn build_shared(self) -> SharedTokenBucket {
+        SharedTokenBucket::new(self.build())
+    }
+}
+
+// Maps `seed` to a uniformly distributed value in `[0, 1]` using the
+// SplitMix64 finalizer, so that neighbouring seeds such as process ids
+// still yield unrelated values.
+pub(crate) fn unit_interval(seed: u64) -> f64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+
+    (z >> 11) as f64 / ((1u64 << 53) - 1) as f64
+}
@@ -0,0 +1,89 @@
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+/// Represents a source of the current time for a
+/// [TokenBucket](struct.TokenBucket.html).
+///
+/// Buckets read the system clock by default. Supplying a different
+/// clock through
+/// [TokenBucketBuilder.clock()](struct.TokenBucketBuilder.html#method.clock)
+/// allows driving a bucket deterministically, e.g. in tests or
+/// simulations.
+pub trait Clock: Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> SystemTime;
+}
+
+/// Represents the system clock. This is the clock used by a
+/// TokenBucket unless another one is configured.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Represents a clock that only moves when told to.
+///
+/// Clones share the same underlying time, so a test can keep one
+/// handle and hand another to a bucket.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::{ManualClock, TokenBucket};
+/// use std::time::{Duration, SystemTime};
+///
+/// let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+/// let mut tb = TokenBucket::builder(1.0, 1.0)
+///     .clock(clock.clone())
+///     .build();
+/// assert!(tb.acquire(1.0).is_ok());
+/// assert!(tb.acquire(1.0).is_err());
+/// clock.advance(Duration::from_secs(1));
+/// assert!(tb.acquire(1.0).is_ok());
+/// ```
+#[derive(Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+impl ManualClock {
+    /// Returns a new ManualClock reading `start`.
+    pub fn new(start: SystemTime) -> ManualClock {
+        ManualClock {
+            now: Arc::new(Mutex::new(start)),
+        }
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        *self.lock() += duration;
+    }
+
+    /// Sets the clock to `now`. This may move the clock backwards.
+    pub fn set(&self, now: SystemTime) {
+        *self.lock() = now;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SystemTime> {
+        self.now.lock().expect("manual clock poisoned")
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        *self.lock()
+    }
+}
+
+impl fmt::Debug for ManualClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ManualClock")
+            .field("now", &*self.lock())
+            .finish()
+    }
+}
@@ -0,0 +1,235 @@
+use crate::{TokenBucket, TokenBucketBuilder};
+use std::fmt;
+use std::str::FromStr;
+
+/// Represents the configuration of a
+/// [TokenBucket](struct.TokenBucket.html), without any of its runtime
+/// state.
+///
+/// A BucketConfig only describes how a bucket behaves, so it can be
+/// kept in application config files and used to produce fresh buckets
+/// with [build()](#method.build). Use
+/// [TokenBucket.snapshot()](struct.TokenBucket.html#method.snapshot)
+/// to persist the runtime state instead.
+///
+/// A BucketConfig can be parsed from and written as plain text, with
+/// one `key = value` pair per line. `r` and `b` are required, all
+/// other keys are optional. Rates and amounts of tokens must be finite
+/// and non-negative. Blank lines and lines starting with `#` are
+/// ignored.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::BucketConfig;
+/// // Allow 5 tokens per second, bursting up to 100.
+/// let config: BucketConfig = "
+///     r = 5
+///     b = 100
+///     max_cost = 10
+/// ".parse().unwrap();
+///
+/// let mut tb = config.build();
+/// assert!(tb.acquire(10.0).is_ok());
+/// assert!(tb.acquire(20.0).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BucketConfig {
+    /// The number of tokens added to the bucket every second.
+    pub r:            f64,
+    /// The maximum number of tokens the bucket can hold.
+    pub b:            f64,
+    /// The largest number of tokens a single acquisition may request.
+    /// See
+    /// [TokenBucketBuilder.max_cost()](struct.TokenBucketBuilder.html#method.max_cost).
+    pub max_cost:     Option<f64>,
+    /// The number of tokens held back from ordinary acquisitions. See
+    /// [TokenBucketBuilder.reserve()](struct.TokenBucketBuilder.html#method.reserve).
+    pub reserve:      f64,
+    /// Whether the bucket only hands out whole tokens. See
+    /// [TokenBucketBuilder.integer_only()](struct.TokenBucketBuilder.html#method.integer_only).
+    pub integer_only: bool,
+}
+
+/// The error returned when parsing a
+/// [BucketConfig](struct.BucketConfig.html) fails.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseBucketConfigError {
+    message: String,
+}
+
+impl BucketConfig {
+    /// Returns a new BucketConfig with the given `r` and `b` and all
+    /// optional settings left at their defaults.
+    pub fn new(r: f64, b: f64) -> BucketConfig {
+        BucketConfig {
+            r,
+            b,
+            max_cost: None,
+            reserve: 0.0,
+            integer_only: false,
+        }
+    }
+
+    /// Returns a [TokenBucketBuilder](struct.TokenBucketBuilder.html)
+    /// preconfigured with this configuration, for adding settings that
+    /// can't be expressed in a config, such as a clock.
+    pub fn builder(&self) -> TokenBucketBuilder {
+        let builder = TokenBucket::builder(self.r, self.b)
+            .reserve(self.reserve)
+            .integer_only(self.integer_only);
+
+        match self.max_cost {
+            Some(max_cost) => builder.max_cost(max_cost),
+            None => builder,
+        }
+    }
+
+    /// Returns a new, full TokenBucket with this configuration.
+    pub fn build(&self) -> TokenBucket {
+        self.builder().build()
+    }
+}
+
+impl fmt::Display for BucketConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "r = {}", self.r)?;
+        writeln!(f, "b = {}", self.b)?;
+        if let Some(max_cost) = self.max_cost {
+            writeln!(f, "max_cost = {}", max_cost)?;
+        }
+        writeln!(f, "reserve = {}", self.reserve)?;
+        writeln!(f, "integer_only = {}", self.integer_only)
+    }
+}
+
+impl FromStr for BucketConfig {
+    type Err = ParseBucketConfigError;
+
+    fn from_str(s: &str) -> Result<BucketConfig, ParseBucketConfigError> {
+        let mut r = None;
+        let mut b = None;
+        let mut config = BucketConfig::new(0.0, 0.0);
+
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(ParseBucketConfigError::new(format!("expected `key = value`, found `{}`", line))),
+            };
+
+            match key {
+                "r" => r = Some(parse_amount(key, value)?),
+                "b" => b = Some(parse_amount(key, value)?),
+                "max_cost" => config.max_cost = Some(parse_amount(key, value)?),
+                "reserve" => config.reserve = parse_amount(key, value)?,
+                "integer_only" => config.integer_only = parse_value(key, value)?,
+                _ => return Err(ParseBucketConfigError::new(format!("unknown key `{}`", key))),
+            }
+        }
+
+        config.r = r.ok_or_else(|| ParseBucketConfigError::new("missing key `r`".to_string()))?;
+        config.b = b.ok_or_else(|| ParseBucketConfigError::new("missing key `b`".to_string()))?;
+        Ok(config)
+    }
+}
+
+// Parses the value of `key`, reporting the key on failure.
+pub(crate) fn parse_value<T: FromStr>(key: &str, value: &str) -> Result<T, ParseBucketConfigError> {
+    value
+        .parse()
+        .map_err(|_| ParseBucketConfigError::new(format!("invalid value `{}` for key `{}`", value, key)))
+}
+
+// Parses the value of `key` as a rate or an amount of tokens, which
+// must be finite and non-negative.
+pub(crate) fn parse_amount(key: &str, value: &str) -> Result<f64, ParseBucketConfigError> {
+    match parse_value::<f64>(key, value)? {
+        amount if amount.is_finite() && amount >= 0.0 => Ok(amount),
+        _ => Err(ParseBucketConfigError::new(format!(
+            "invalid value `{}` for key `{}`, expected a finite, non-negative number",
+            value, key
+        ))),
+    }
+}
+
+impl ParseBucketConfigError {
+    fn new(message: String) -> ParseBucketConfigError {
+        ParseBucketConfigError { message }
+    }
+}
+
+impl fmt::Display for ParseBucketConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ParseBucketConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TokenAcquisitionError;
+
+    // This module contains unit tests for the BucketConfig implementation.
+
+    /// 1. **Load and Build**:
+    ///    - Test parsing a config with comments and optional keys, then building a bucket from it.
+    ///    - The bucket should be full and honour the configured `max_cost` and `reserve`.
+    #[test]
+    fn test_load_config_and_build() {
+        let config: BucketConfig = "
+            # Upstream API limits.
+            r = 0
+            b = 10
+            max_cost = 8
+            reserve = 2
+        "
+        .parse()
+        .unwrap();
+        assert_eq!(
+            config,
+            BucketConfig {
+                max_cost: Some(8.0),
+                reserve: 2.0,
+                ..BucketConfig::new(0.0, 10.0)
+            }
+        );
+
+        let mut bucket = config.build();
+        assert_eq!(
+            bucket.acquire(9.0),
+            Err(TokenAcquisitionError::CostExceedsMax { count: 9.0, max_cost: 8.0 })
+        );
+        assert!(bucket.acquire(8.0).is_ok());
+        assert!(bucket.acquire(1.0).is_err());
+        assert!(bucket.acquire_reserve(2.0).is_ok());
+    }
+
+    /// 2. **Round Trip and Errors**:
+    ///    - Test writing a config as text and parsing it back, and parsing malformed configs.
+    ///    - The round trip should yield an equal config, and malformed input, including negative and non-finite amounts, should be rejected.
+    #[test]
+    fn test_round_trip_and_errors() {
+        let config = BucketConfig {
+            integer_only: true,
+            ..BucketConfig::new(2.5, 40.0)
+        };
+        assert_eq!(config.to_string().parse(), Ok(config));
+
+        assert!("b = 10".parse::<BucketConfig>().is_err());
+        assert!("r = 1\nb = ten".parse::<BucketConfig>().is_err());
+        assert!("r = 1\nb = 10\nburst = 3".parse::<BucketConfig>().is_err());
+        assert!("r 1".parse::<BucketConfig>().is_err());
+
+        for value in &["-1", "NaN", "inf"] {
+            assert!(format!("r = {}\nb = 10", value).parse::<BucketConfig>().is_err());
+            assert!(format!("r = 1\nb = {}", value).parse::<BucketConfig>().is_err());
+            assert!(format!("r = 1\nb = 10\nreserve = {}", value).parse::<BucketConfig>().is_err());
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use crate::{TokenAcquisitionResult, TokenBucket};
+use std::time::Instant;
+
+/// Represents a context that carries the deadline of the work it
+/// belongs to, e.g. the request context of a web framework.
+///
+/// Implement it for a framework's context type to acquire from a
+/// [TokenBucket](struct.TokenBucket.html) with
+/// [acquire_ctx()](struct.TokenBucket.html#method.acquire_ctx) without
+/// extracting the deadline in every handler.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::{HasDeadline, TokenBucket};
+/// use std::time::{Duration, Instant};
+///
+/// struct RequestContext {
+///     started: Instant,
+///     timeout: Option<Duration>,
+/// }
+///
+/// impl HasDeadline for RequestContext {
+///     fn deadline(&self) -> Option<Instant> {
+///         self.timeout.map(|timeout| self.started + timeout)
+///     }
+/// }
+///
+/// let ctx = RequestContext {
+///     started: Instant::now(),
+///     timeout: Some(Duration::from_millis(50)),
+/// };
+/// let mut token_bucket = TokenBucket::new(1.0, 1.0);
+/// assert!(token_bucket.acquire_ctx(1.0, &ctx).is_ok());
+/// // The next token is a second away, well past the deadline.
+/// assert!(token_bucket.acquire_ctx(1.0, &ctx).is_err());
+/// ```
+pub trait HasDeadline {
+    /// Returns the time by which the work must be done, or `None` if
+    /// it has no deadline.
+    fn deadline(&self) -> Option<Instant>;
+}
+
+impl HasDeadline for Instant {
+    fn deadline(&self) -> Option<Instant> {
+        Some(*self)
+    }
+}
+
+impl TokenBucket {
+    /// Acquires `count` tokens from the bucket within the deadline
+    /// carried by `ctx`, see
+    /// [TokenBucket.acquire_with_deadline()](struct.TokenBucket.html#method.acquire_with_deadline).
+    ///
+    /// A context without a deadline waits as long as it takes, like
+    /// [TokenBucket.acquire_wait()](struct.TokenBucket.html#method.acquire_wait).
+    pub fn acquire_ctx(&mut self, count: f64, ctx: &impl HasDeadline) -> TokenAcquisitionResult {
+        match ctx.deadline() {
+            Some(deadline) => self.acquire_with_deadline(count, deadline),
+            None => self.acquire_wait(count),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // This module contains unit tests for the HasDeadline implementation.
+
+    struct Context(Option<Instant>);
+
+    impl HasDeadline for Context {
+        fn deadline(&self) -> Option<Instant> {
+            self.0
+        }
+    }
+
+    /// 1. **Context Deadlines**:
+    ///    - Test acquiring with contexts whose deadlines fall before and after the next token, and with one without a deadline.
+    ///    - A deadline that can't be met should fail, while the others should wait for the token.
+    #[test]
+    fn test_acquire_ctx() {
+        let mut bucket = TokenBucket::new(50.0, 1.0);
+        assert!(bucket.acquire(1.0).is_ok());
+
+        let start = Instant::now();
+        assert!(bucket.acquire_ctx(1.0, &Context(Some(start + Duration::from_millis(1)))).is_err());
+
+        assert!(bucket.acquire_ctx(1.0, &Context(Some(start + Duration::from_secs(5)))).is_ok());
+        assert!(start.elapsed() >= Duration::from_millis(15));
+
+        assert!(bucket.acquire_ctx(1.0, &Context(None)).is_ok());
+        assert!(start.elapsed() >= Duration::from_millis(35));
+    }
+}
@@ -0,0 +1,313 @@
+use crate::TokenBucket;
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+/// Represents the limit a [TokenBucket](struct.TokenBucket.html)
+/// enforces, in a form that can be published to its clients, as
+/// returned by
+/// [TokenBucket.descriptor()](struct.TokenBucket.html#method.descriptor).
+///
+/// A LimitDescriptor is written as a JSON object with its `Display`
+/// implementation and parsed back from one with `FromStr`, so a
+/// service can advertise its limits, e.g. from a discovery endpoint,
+/// and clients can throttle themselves to match with
+/// [build()](#method.build).
+///
+/// JSON has no representation for infinite or NaN numbers, so writing
+/// a descriptor whose `rate` or `burst` isn't finite fails with
+/// `fmt::Error`, which makes `to_string()` panic, and parsing rejects
+/// numbers that aren't finite and non-negative.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::{LimitDescriptor, TokenBucket};
+/// let mut descriptor = TokenBucket::new(5.0, 100.0).descriptor();
+/// descriptor.unit = "request".to_string();
+///
+/// let json = descriptor.to_string();
+/// assert_eq!(json, r#"{"rate":5,"burst":100,"unit":"request"}"#);
+///
+/// let advertised: LimitDescriptor = json.parse().unwrap();
+/// assert_eq!(advertised, descriptor);
+/// let mut tb = advertised.build();
+/// assert!(tb.acquire(100.0).is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimitDescriptor {
+    /// The number of units added to the bucket every second.
+    pub rate:  f64,
+    /// The maximum number of units the bucket can hold.
+    pub burst: f64,
+    /// What a single token stands for, e.g. `"request"` or `"byte"`.
+    /// This is informational only and defaults to `"token"`.
+    pub unit:  String,
+}
+
+/// The error returned when parsing a
+/// [LimitDescriptor](struct.LimitDescriptor.html) fails.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseLimitDescriptorError {
+    message: String,
+}
+
+impl LimitDescriptor {
+    /// Returns a new, full TokenBucket enforcing the described limit.
+    pub fn build(&self) -> TokenBucket {
+        TokenBucket::new(self.rate, self.burst)
+    }
+}
+
+impl TokenBucket {
+    /// Returns a descriptor of the limit the bucket enforces, for
+    /// advertising it to clients. See
+    /// [LimitDescriptor](struct.LimitDescriptor.html).
+    ///
+    /// The descriptor only holds the current rate and burst value;
+    /// optional settings such as `max_cost` and the runtime state are
+    /// not part of it. Its unit is `"token"`.
+    pub fn descriptor(&self) -> LimitDescriptor {
+        LimitDescriptor {
+            rate:  self.r,
+            burst: self.b,
+            unit:  "token".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for LimitDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.rate.is_finite() || !self.burst.is_finite() {
+            return Err(fmt::Error);
+        }
+        write!(f, "{{\"rate\":{},\"burst\":{},\"unit\":", self.rate, self.burst)?;
+        write_string(f, &self.unit)?;
+        f.write_char('}')
+    }
+}
+
+impl FromStr for LimitDescriptor {
+    type Err = ParseLimitDescriptorError;
+
+    fn from_str(s: &str) -> Result<LimitDescriptor, ParseLimitDescriptorError> {
+        let mut rate = None;
+        let mut burst = None;
+        let mut unit = None;
+
+        let mut parser = Parser { rest: s };
+        parser.expect('{')?;
+        if !parser.eat('}') {
+            loop {
+                let key = parser.string()?;
+                parser.expect(':')?;
+                match key.as_str() {
+                    "rate" => rate = Some(parser.number()?),
+                    "burst" => burst = Some(parser.number()?),
+                    "unit" => unit = Some(parser.string()?),
+                    _ => return Err(ParseLimitDescriptorError::new(format!("unknown key `{}`", key))),
+                }
+
+                if parser.eat('}') {
+                    break;
+                }
+                parser.expect(',')?;
+            }
+        }
+        if !parser.rest.trim_start().is_empty() {
+            return Err(ParseLimitDescriptorError::new("trailing characters after object".to_string()));
+        }
+
+        Ok(LimitDescriptor {
+            rate:  rate.ok_or_else(|| ParseLimitDescriptorError::new("missing key `rate`".to_string()))?,
+            burst: burst.ok_or_else(|| ParseLimitDescriptorError::new("missing key `burst`".to_string()))?,
+            unit:  unit.unwrap_or_else(|| "token".to_string()),
+        })
+    }
+}
+
+// Writes `s` as a JSON string literal.
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+// Represents a minimal JSON reader over the part of the input that
+// hasn't been consumed yet. It only understands the flat objects
+// written by `LimitDescriptor`'s `Display` implementation.
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl Parser<'_> {
+    // Consumes `c` after any whitespace, returning whether it was
+    // there.
+    fn eat(&mut self, c: char) -> bool {
+        self.rest = self.rest.trim_start();
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ParseLimitDescriptorError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(ParseLimitDescriptorError::new(format!("expected `{}`", c)))
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, ParseLimitDescriptorError> {
+        self.rest = self.rest.trim_start();
+        let end = self
+            .rest
+            .find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c)))
+            .unwrap_or(self.rest.len());
+        let (number, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        // Numbers too large for an f64, e.g. `1e999`, parse as infinity.
+        match number.parse::<f64>() {
+            Ok(value) if value.is_finite() && value >= 0.0 => Ok(value),
+            _ => Err(ParseLimitDescriptorError::new(format!("invalid number `{}`", number))),
+        }
+    }
+
+    // Reads the four hex digits of a `\u` escape.
+    fn hex4(chars: &mut std::str::CharIndices) -> Result<u32, ParseLimitDescriptorError> {
+        let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+        match u32::from_str_radix(&hex, 16) {
+            Ok(unit) if hex.len() == 4 => Ok(unit),
+            _ => Err(ParseLimitDescriptorError::new(format!("invalid escape `\\u{}`", hex))),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseLimitDescriptorError> {
+        self.expect('"')?;
+        let mut out = String::new();
+        let mut chars = self.rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &self.rest[i + 1..];
+                    return Ok(out);
+                },
+                '\\' => {
+                    let escaped = match chars.next().map(|(_, c)| c) {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let mut unit = Parser::hex4(&mut chars)?;
+                            // Characters outside the basic multilingual
+                            // plane are escaped as a UTF-16 surrogate pair.
+                            if (0xd800..0xdc00).contains(&unit) {
+                                let low = match (chars.next(), chars.next()) {
+                                    (Some((_, '\\')), Some((_, 'u'))) => Parser::hex4(&mut chars)?,
+                                    _ => 0,
+                                };
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return Err(ParseLimitDescriptorError::new("unpaired surrogate".to_string()));
+                                }
+                                unit = 0x10000 + ((unit - 0xd800) << 10) + (low - 0xdc00);
+                            }
+                            char::from_u32(unit)
+                                .ok_or_else(|| ParseLimitDescriptorError::new("unpaired surrogate".to_string()))?
+                        },
+                        _ => return Err(ParseLimitDescriptorError::new("invalid escape".to_string())),
+                    };
+                    out.push(escaped);
+                },
+                c => out.push(c),
+            }
+        }
+        Err(ParseLimitDescriptorError::new("unterminated string".to_string()))
+    }
+}
+
+impl ParseLimitDescriptorError {
+    fn new(message: String) -> ParseLimitDescriptorError {
+        ParseLimitDescriptorError { message }
+    }
+}
+
+impl fmt::Display for ParseLimitDescriptorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ParseLimitDescriptorError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // This module contains unit tests for the LimitDescriptor implementation.
+
+    /// 1. **JSON Serialization**:
+    ///    - Test writing a descriptor with a fractional rate and an escaped unit as JSON, parsing it back, and parsing malformed JSON.
+    ///    - The output should be exact JSON, the round trip should yield an equal descriptor, and malformed input should be rejected.
+    #[test]
+    fn test_json_round_trip() {
+        let descriptor = LimitDescriptor {
+            unit: "req\"uest\n".to_string(),
+            ..TokenBucket::new(2.5, 40.0).descriptor()
+        };
+        let json = descriptor.to_string();
+        assert_eq!(json, r#"{"rate":2.5,"burst":40,"unit":"req\"uest\n"}"#);
+        assert_eq!(json.parse(), Ok(descriptor));
+
+        let spaced: LimitDescriptor = " { \"burst\" : 1e2 , \"rate\" : 5 } ".parse().unwrap();
+        assert_eq!(spaced, TokenBucket::new(5.0, 100.0).descriptor());
+
+        assert!(r#"{"rate":5}"#.parse::<LimitDescriptor>().is_err());
+        assert!(r#"{"rate":5,"burst":"ten"}"#.parse::<LimitDescriptor>().is_err());
+        assert!(r#"{"rate":5,"burst":10,"period":1}"#.parse::<LimitDescriptor>().is_err());
+        assert!(r#"{"rate":5,"burst":10"#.parse::<LimitDescriptor>().is_err());
+        assert!(r#"{"rate":5,"burst":10} x"#.parse::<LimitDescriptor>().is_err());
+    }
+
+    /// 2. **Non-Finite Numbers and Surrogate Pairs**:
+    ///    - Test writing descriptors with an infinite or NaN rate, parsing out of range and negative numbers, and parsing units escaped as UTF-16 surrogate pairs.
+    ///    - Writing and parsing non-finite or negative numbers should fail, a surrogate pair should decode to its character, and an unpaired surrogate should be rejected.
+    #[test]
+    fn test_json_edge_cases() {
+        for &rate in &[f64::INFINITY, f64::NAN] {
+            let descriptor = LimitDescriptor {
+                rate,
+                ..TokenBucket::new(1.0, 1.0).descriptor()
+            };
+            let mut json = String::new();
+            assert!(write!(json, "{}", descriptor).is_err());
+        }
+        assert!(r#"{"rate":1e999,"burst":10}"#.parse::<LimitDescriptor>().is_err());
+        assert!(r#"{"rate":-1,"burst":10}"#.parse::<LimitDescriptor>().is_err());
+
+        let parsed: LimitDescriptor = r#"{"rate":1,"burst":1,"unit":"\ud83d\ude80 \u00e9"}"#.parse().unwrap();
+        assert_eq!(parsed.unit, "\u{1f680} \u{e9}");
+        assert_eq!(parsed.to_string().parse(), Ok(parsed));
+        for unit in &[r#"\ud83d"#, r#"\ud83d\u0041"#, r#"\ude80"#, r#"\u12"#] {
+            let json = format!(r#"{{"rate":1,"burst":1,"unit":"{}"}}"#, unit);
+            assert!(json.parse::<LimitDescriptor>().is_err(), "{}", unit);
+        }
+    }
+}
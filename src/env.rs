@@ -0,0 +1,186 @@
+use crate::config::{parse_amount, parse_value};
+use crate::{BucketConfig, TokenBucket};
+use std::env::{self, VarError};
+use std::fmt;
+use std::str::FromStr;
+
+/// The error returned when reading a
+/// [BucketConfig](struct.BucketConfig.html) from environment variables
+/// fails.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnvError {
+    /// The required variable `var` is not set.
+    Missing { var: String },
+    /// The variable `var` is set to `value`, which isn't valid for it.
+    /// Values that aren't valid unicode are reported lossily.
+    Invalid { var: String, value: String },
+}
+
+impl BucketConfig {
+    /// Returns the BucketConfig described by the environment variables
+    /// starting with `prefix`, e.g. for twelve-factor apps.
+    ///
+    /// With a `prefix` of `RATE_LIMIT` the variables read are:
+    ///
+    /// * `RATE_LIMIT_RPS` - the rate `r`, in tokens per second. Required.
+    /// * `RATE_LIMIT_BURST` - the burst value `b`. Required.
+    /// * `RATE_LIMIT_MAX_COST` - the `max_cost`. Optional.
+    /// * `RATE_LIMIT_RESERVE` - the `reserve`. Optional.
+    /// * `RATE_LIMIT_INTEGER_ONLY` - `true` or `false`. Optional.
+    ///
+    /// Values are parsed like the values of a BucketConfig in text
+    /// form, after trimming whitespace, so rates and amounts must be
+    /// finite and non-negative. Returns
+    /// `Err(EnvError::Missing { .. })` for the first required variable
+    /// that isn't set and `Err(EnvError::Invalid { .. })` for the first
+    /// variable that doesn't parse.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::{BucketConfig, EnvError};
+    /// std::env::set_var("UPLOADS_RPS", "5");
+    /// std::env::set_var("UPLOADS_BURST", "100");
+    /// let config = BucketConfig::from_env("UPLOADS").unwrap();
+    /// assert_eq!(config, BucketConfig::new(5.0, 100.0));
+    ///
+    /// std::env::remove_var("UPLOADS_BURST");
+    /// assert_eq!(
+    ///     BucketConfig::from_env("UPLOADS"),
+    ///     Err(EnvError::Missing { var: "UPLOADS_BURST".to_string() })
+    /// );
+    /// ```
+    pub fn from_env(prefix: &str) -> Result<BucketConfig, EnvError> {
+        let required = |suffix| {
+            let var = format!("{}_{}", prefix, suffix);
+            read_amount(&var)?.ok_or(EnvError::Missing { var })
+        };
+        let mut config = BucketConfig::new(required("RPS")?, required("BURST")?);
+
+        config.max_cost = read_amount(&format!("{}_MAX_COST", prefix))?;
+        if let Some(reserve) = read_amount(&format!("{}_RESERVE", prefix))? {
+            config.reserve = reserve;
+        }
+        if let Some(integer_only) = read(&format!("{}_INTEGER_ONLY", prefix))? {
+            config.integer_only = integer_only;
+        }
+        Ok(config)
+    }
+}
+
+impl TokenBucket {
+    /// Returns a new, full TokenBucket configured by the environment
+    /// variables starting with `prefix`, see
+    /// [BucketConfig::from_env()](struct.BucketConfig.html#method.from_env).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// std::env::set_var("RATE_LIMIT_RPS", "5");
+    /// std::env::set_var("RATE_LIMIT_BURST", "100");
+    /// let mut token_bucket = TokenBucket::from_env("RATE_LIMIT").unwrap();
+    /// assert!(token_bucket.acquire(100.0).is_ok());
+    /// ```
+    pub fn from_env(prefix: &str) -> Result<TokenBucket, EnvError> {
+        BucketConfig::from_env(prefix).map(|config| config.build())
+    }
+}
+
+// Reads and parses the variable `var`, returning `None` if it isn't set.
+fn read<T: FromStr>(var: &str) -> Result<Option<T>, EnvError> {
+    let value = match env::var(var) {
+        Ok(value) => value,
+        Err(VarError::NotPresent) => return Ok(None),
+        Err(VarError::NotUnicode(value)) => {
+            return Err(EnvError::Invalid {
+                var:   var.to_string(),
+                value: value.to_string_lossy().into_owned(),
+            })
+        },
+    };
+
+    match parse_value(var, value.trim()) {
+        Ok(parsed) => Ok(Some(parsed)),
+        Err(_) => Err(EnvError::Invalid { var: var.to_string(), value }),
+    }
+}
+
+// Reads the variable `var` like `read`, as a rate or an amount of
+// tokens, which must be finite and non-negative.
+fn read_amount(var: &str) -> Result<Option<f64>, EnvError> {
+    let value: Option<String> = read(var)?;
+    match value {
+        Some(value) => match parse_amount(var, value.trim()) {
+            Ok(amount) => Ok(Some(amount)),
+            Err(_) => Err(EnvError::Invalid { var: var.to_string(), value }),
+        },
+        None => Ok(None),
+    }
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EnvError::Missing { var } => write!(f, "environment variable `{}` is not set", var),
+            EnvError::Invalid { var, value } => {
+                write!(f, "invalid value `{}` for environment variable `{}`", value, var)
+            },
+        }
+    }
+}
+
+impl std::error::Error for EnvError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TokenAcquisitionError;
+
+    // This module contains unit tests for the from_env implementation.
+
+    /// 1. **Configuration From the Environment**:
+    ///    - Test building a bucket from required and optional variables, then with a required variable unset and with an unparsable value.
+    ///    - The bucket should honour every variable set, and the failures, including negative and non-finite amounts, should name the variable at fault.
+    #[test]
+    fn test_from_env() {
+        env::set_var("TEST_FROM_ENV_RPS", "0");
+        env::set_var("TEST_FROM_ENV_BURST", " 10 ");
+        env::set_var("TEST_FROM_ENV_MAX_COST", "8");
+        env::set_var("TEST_FROM_ENV_RESERVE", "2");
+
+        let mut bucket = TokenBucket::from_env("TEST_FROM_ENV").unwrap();
+        assert!(matches!(bucket.acquire(9.0), Err(TokenAcquisitionError::CostExceedsMax { .. })));
+        assert!(bucket.acquire(8.0).is_ok());
+        assert!(bucket.acquire(1.0).is_err());
+        assert!(bucket.acquire_reserve(2.0).is_ok());
+
+        env::set_var("TEST_FROM_ENV_INTEGER_ONLY", "yes");
+        let err = TokenBucket::from_env("TEST_FROM_ENV").err().unwrap();
+        assert_eq!(
+            err,
+            EnvError::Invalid {
+                var:   "TEST_FROM_ENV_INTEGER_ONLY".to_string(),
+                value: "yes".to_string(),
+            }
+        );
+        assert_eq!(err.to_string(), "invalid value `yes` for environment variable `TEST_FROM_ENV_INTEGER_ONLY`");
+
+        env::set_var("TEST_FROM_ENV_INTEGER_ONLY", "false");
+        for value in &["-5", "NaN", "inf"] {
+            env::set_var("TEST_FROM_ENV_BURST", value);
+            assert_eq!(
+                TokenBucket::from_env("TEST_FROM_ENV").err(),
+                Some(EnvError::Invalid {
+                    var:   "TEST_FROM_ENV_BURST".to_string(),
+                    value: value.to_string(),
+                })
+            );
+        }
+
+        env::remove_var("TEST_FROM_ENV_RPS");
+        let err = TokenBucket::from_env("TEST_FROM_ENV").err().unwrap();
+        assert_eq!(err, EnvError::Missing { var: "TEST_FROM_ENV_RPS".to_string() });
+        assert_eq!(err.to_string(), "environment variable `TEST_FROM_ENV_RPS` is not set");
+    }
+}
@@ -0,0 +1,46 @@
+use std::time::{Duration, SystemTime};
+
+// Represents an exponentially weighted moving average of the rate at
+// which tokens are acquired, with a time constant of `window`.
+//
+// Every acquisition adds `count / window` to the average, which
+// decays by `e^(-elapsed / window)` over time. A steady stream of
+// acquisitions at `x` tokens per second thus converges to `x`.
+#[derive(Clone, Copy)]
+pub(crate) struct Ewma {
+    window: f64,
+    rate:   f64,
+    at:     Option<SystemTime>,
+}
+
+impl Ewma {
+    pub(crate) fn new(window: Duration) -> Ewma {
+        Ewma {
+            window: window.as_secs_f64(),
+            rate:   0.0,
+            at:     None,
+        }
+    }
+
+    // Records an acquisition of `count` tokens at `now`.
+    pub(crate) fn record(&mut self, count: f64, now: SystemTime) {
+        self.rate = self.rate_at(now) + count / self.window;
+        self.at = Some(now);
+    }
+
+    // Moves the time of the latest acquisition with `rebase`, e.g.
+    // when the bucket switches clocks.
+    pub(crate) fn rebase(&mut self, rebase: impl Fn(SystemTime) -> SystemTime) {
+        self.at = self.at.map(rebase);
+    }
+
+    // Returns the average at `now`, decayed since the latest
+    // acquisition.
+    pub(crate) fn rate_at(&self, now: SystemTime) -> f64 {
+        let elapsed = match self.at {
+            Some(at) => now.duration_since(at).unwrap_or(Duration::ZERO),
+            None => return 0.0,
+        };
+        self.rate * (-elapsed.as_secs_f64() / self.window).exp()
+    }
+}
@@ -0,0 +1,186 @@
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Receives the metrics of every [TokenBucket](struct.TokenBucket.html)
+/// once installed with [set_recorder()](fn.set_recorder.html).
+///
+/// This is the bridge to the `metrics` crate facade: implement it by
+/// forwarding to `metrics::counter!(name).increment(value)` and
+/// `metrics::gauge!(name).set(value)`, and whatever exporter is
+/// installed for the facade receives the bucket metrics. Every
+/// acquisition that is allowed or denied for lack of tokens reports,
+/// under the names set with
+/// [TokenBucketBuilder.metric_names()](struct.TokenBucketBuilder.html#method.metric_names):
+///
+/// * `allowed` - A counter of allowed acquisitions.
+/// * `denied` - A counter of acquisitions denied for lack of tokens.
+/// * `fill` - A gauge holding the fraction of the burst value in the
+///   bucket after the acquisition.
+///
+/// See the [crate documentation](index.html#metrics) for which
+/// acquisitions are reported. Only available with the `metrics`
+/// feature.
+pub trait Recorder: Send + Sync {
+    /// Adds `value` to the counter `name`.
+    fn increment_counter(&self, name: &'static str, value: u64);
+
+    /// Sets the gauge `name` to `value`.
+    fn set_gauge(&self, name: &'static str, value: f64);
+}
+
+/// Represents the names a [TokenBucket](struct.TokenBucket.html)
+/// reports its metrics under to the installed
+/// [Recorder](trait.Recorder.html).
+///
+/// Only available with the `metrics` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetricNames {
+    /// The counter of allowed acquisitions.
+    pub allowed: &'static str,
+    /// The counter of acquisitions denied for lack of tokens.
+    pub denied:  &'static str,
+    /// The gauge of the fraction of the burst value in the bucket.
+    pub fill:    &'static str,
+}
+
+impl MetricNames {
+    /// The names used unless configured otherwise:
+    /// `tokenbucket.allowed`, `tokenbucket.denied` and
+    /// `tokenbucket.fill_ratio`.
+    pub const DEFAULT: MetricNames = MetricNames {
+        allowed: "tokenbucket.allowed",
+        denied:  "tokenbucket.denied",
+        fill:    "tokenbucket.fill_ratio",
+    };
+}
+
+impl Default for MetricNames {
+    fn default() -> MetricNames {
+        MetricNames::DEFAULT
+    }
+}
+
+/// The error returned by [set_recorder()](fn.set_recorder.html) if a
+/// recorder is already installed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetRecorderError;
+
+impl fmt::Display for SetRecorderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a tokenbucket metrics recorder is already installed")
+    }
+}
+
+impl std::error::Error for SetRecorderError {}
+
+static RECORDER: OnceLock<&'static dyn Recorder> = OnceLock::new();
+
+/// Installs `recorder` as the receiver of the metrics of every bucket,
+/// for the rest of the program.
+///
+/// Like the `metrics` crate facade, a recorder can only be installed
+/// once. Returns `Err(SetRecorderError)` if one already is. Until a
+/// recorder is installed, buckets report nothing. Only available with
+/// the `metrics` feature.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::{Recorder, TokenBucket};
+/// use std::sync::atomic::{AtomicU64, Ordering};
+///
+/// struct Allowed(AtomicU64);
+///
+/// impl Recorder for Allowed {
+///     fn increment_counter(&self, name: &'static str, value: u64) {
+///         if name == "tokenbucket.allowed" {
+///             self.0.fetch_add(value, Ordering::Relaxed);
+///         }
+///     }
+///
+///     fn set_gauge(&self, _name: &'static str, _value: f64) {}
+/// }
+///
+/// static ALLOWED: Allowed = Allowed(AtomicU64::new(0));
+/// tokenbucket::set_recorder(&ALLOWED).unwrap();
+/// assert!(tokenbucket::set_recorder(&ALLOWED).is_err());
+///
+/// let mut tb = TokenBucket::new(5.0, 100.0);
+/// tb.acquire(1.0).unwrap();
+/// assert_eq!(ALLOWED.0.load(Ordering::Relaxed), 1);
+/// ```
+pub fn set_recorder(recorder: &'static dyn Recorder) -> Result<(), SetRecorderError> {
+    RECORDER.set(recorder).map_err(|_| SetRecorderError)
+}
+
+// Returns whether a recorder is installed.
+#[inline(always)]
+pub(crate) fn installed() -> bool {
+    RECORDER.get().is_some()
+}
+
+// Reports the outcome of an acquisition that left `tokens` in a bucket
+// with burst value `b` to the installed recorder, if any.
+pub(crate) fn record(names: &MetricNames, allowed: bool, tokens: f64, b: f64) {
+    if let Some(recorder) = RECORDER.get() {
+        recorder.increment_counter(if allowed { names.allowed } else { names.denied }, 1);
+        recorder.set_gauge(names.fill, crate::fill_ratio(tokens, b));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TokenBucket;
+    use std::sync::Mutex;
+
+    // This module contains unit tests for the metrics facade
+    // instrumentation.
+
+    // Records every metric reported under a name starting with
+    // `test.`, since the recorder is shared with every other test.
+    struct TestRecorder(Mutex<Vec<(&'static str, f64)>>);
+
+    impl Recorder for TestRecorder {
+        fn increment_counter(&self, name: &'static str, value: u64) {
+            if name.starts_with("test.") {
+                self.0.lock().unwrap().push((name, value as f64));
+            }
+        }
+
+        fn set_gauge(&self, name: &'static str, value: f64) {
+            if name.starts_with("test.") {
+                self.0.lock().unwrap().push((name, value));
+            }
+        }
+    }
+
+    static RECORDED: TestRecorder = TestRecorder(Mutex::new(Vec::new()));
+
+    /// 1. **Reported Metrics**:
+    ///    - Test making allowed, denied and invalid acquisitions from a bucket with custom metric names, with a test recorder installed.
+    ///    - Allowed and denied acquisitions should each report their counter and the fill level under the configured names, and invalid ones nothing.
+    #[test]
+    fn test_metric_names() {
+        set_recorder(&RECORDED).unwrap();
+        assert_eq!(set_recorder(&RECORDED), Err(SetRecorderError));
+
+        let names = MetricNames {
+            allowed: "test.allowed",
+            denied:  "test.denied",
+            fill:    "test.fill",
+        };
+        let mut tb = TokenBucket::builder(0.0, 4.0).max_cost(10.0).metric_names(names).build();
+
+        assert!(tb.acquire(3.0).is_ok());
+        assert!(tb.acquire(2.0).is_err());
+        assert!(tb.acquire(20.0).is_err());
+
+        assert_eq!(*RECORDED.0.lock().unwrap(), [
+            ("test.allowed", 1.0),
+            ("test.fill", 0.25),
+            ("test.denied", 1.0),
+            ("test.fill", 0.25),
+        ]);
+    }
+}
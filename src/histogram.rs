@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+// Represents the number of buckets per power of two. Every recorded
+// value is counted in a bucket no wider than 1/16th of its value, so
+// percentiles are accurate to about 6%.
+const SUB_BUCKETS: u64 = 16;
+
+// Represents the largest value recorded, in microseconds, about 19
+// hours. Larger values are counted as this one.
+const MAX_MICROS: u64 = (1 << 36) - 1;
+
+// Represents a log-linear histogram of durations with microsecond
+// resolution, in the style of an HDR histogram. Values below 16µs get
+// a bucket each, and every power of two above is split into
+// `SUB_BUCKETS` buckets of equal width.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Histogram {
+    // Represents the count of every bucket. Empty until a value is
+    // recorded, so a histogram that is never used doesn't allocate.
+    counts: Vec<u64>,
+    total:  u64,
+}
+
+impl Histogram {
+    pub(crate) const fn new() -> Histogram {
+        Histogram {
+            counts: Vec::new(),
+            total:  0,
+        }
+    }
+
+    // Counts `value` as one more sample.
+    pub(crate) fn record(&mut self, value: Duration) {
+        let micros = (value.as_micros() as u64).min(MAX_MICROS);
+        if self.counts.is_empty() {
+            self.counts = vec![0; index(MAX_MICROS) + 1];
+        }
+        self.counts[index(micros)] += 1;
+        self.total += 1;
+    }
+
+    // Returns the smallest value that at least the fraction `quantile`
+    // of the samples don't exceed, rounded up to the end of its
+    // bucket. Returns zero for an empty histogram.
+    pub(crate) fn percentile(&self, quantile: f64) -> Duration {
+        let rank = ((quantile * self.total as f64).ceil() as u64).clamp(1, self.total.max(1));
+        let mut seen = 0;
+        for (index, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_micros(highest(index));
+            }
+        }
+        Duration::ZERO
+    }
+}
+
+// Returns the bucket `micros` is counted in.
+fn index(micros: u64) -> usize {
+    if micros < SUB_BUCKETS {
+        return micros as usize;
+    }
+    let exponent = 63 - u64::from(micros.leading_zeros());
+    let sub = (micros >> (exponent - 4)) - SUB_BUCKETS;
+    ((exponent - 3) * SUB_BUCKETS + sub) as usize
+}
+
+// Returns the largest value counted in the bucket `index`.
+fn highest(index: usize) -> u64 {
+    let index = index as u64;
+    if index < SUB_BUCKETS {
+        return index;
+    }
+    let exponent = index / SUB_BUCKETS + 3;
+    let sub = index % SUB_BUCKETS + SUB_BUCKETS;
+    ((sub + 1) << (exponent - 4)) - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TokenBucket;
+
+    // This module contains unit tests for the wait histogram
+    // implementation.
+
+    /// 1. **Wait Percentiles**:
+    ///    - Test the percentiles of a histogram fed waits of 1 to 100 milliseconds, of an empty histogram, and of a bucket after acquisitions had to wait, including a granted one.
+    ///    - The percentiles should be within the histogram's precision of the exact ones, zero without any waits, and count every wait of the bucket, covering the longest.
+    #[test]
+    fn test_wait_percentiles() {
+        let mut histogram = Histogram::new();
+        assert_eq!(histogram.percentile(0.99), Duration::ZERO);
+
+        for millis in (1..=100).rev() {
+            histogram.record(Duration::from_millis(millis));
+        }
+        for &(quantile, exact) in &[(0.5, 50.0), (0.95, 95.0), (0.99, 99.0), (1.0, 100.0)] {
+            let millis = histogram.percentile(quantile).as_secs_f64() * 1000.0;
+            assert!(millis >= exact && millis <= exact * 1.07, "p{}: {}", quantile, millis);
+        }
+        for micros in 0..SUB_BUCKETS * 64 {
+            assert!(highest(index(micros)) >= micros);
+        }
+
+        let mut bucket = TokenBucket::new(100.0, 1.0);
+        assert_eq!(bucket.wait_percentiles(), [Duration::ZERO; 3]);
+        assert!(bucket.acquire_wait(1.0).is_ok());
+        assert!(bucket.acquire_wait(1.0).is_ok());
+        let [p50, p95, p99] = bucket.wait_percentiles();
+        assert_eq!(bucket.waits.total, 2);
+        assert!(p50 <= p95 && p95 >= Duration::from_millis(9) && p99 == p95);
+
+        let waited = bucket.acquire_wait_granted(1.0).unwrap().waited;
+        assert!(waited >= Duration::from_millis(9));
+        assert!(bucket.wait_percentiles()[0] >= Duration::from_millis(9));
+    }
+}
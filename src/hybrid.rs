@@ -0,0 +1,176 @@
+use crate::{TokenAcquisitionError, TokenAcquisitionResult, TokenBucket};
+use std::time::{Duration, SystemTime};
+
+/// Represents a [TokenBucket](struct.TokenBucket.html) paired with a
+/// fixed-window counter, for upstreams that enforce a hard cap per
+/// calendar window on top of a smooth rate.
+///
+/// Windows are aligned to the wall clock, e.g. a one minute window
+/// resets at the start of every minute, and are read from the bucket's
+/// clock. An acquisition only succeeds if both the bucket and the
+/// current window allow it, and consumes from both.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::{HybridLimiter, TokenBucket};
+/// // Smooth out to 10/s, with a hard cap of 600 per calendar minute.
+/// let mut limiter = HybridLimiter::per_minute(TokenBucket::new(10.0, 10.0), 600.0);
+/// assert!(limiter.acquire(1.0).is_ok());
+/// ```
+pub struct HybridLimiter {
+    bucket: TokenBucket,
+    limit:  f64,
+    window: Duration,
+    // Represents the index, counted from the UNIX epoch, of the window
+    // `used` refers to.
+    index:  u128,
+    // Represents the number of tokens acquired in the current window.
+    used:   f64,
+}
+
+impl HybridLimiter {
+    /// Returns a new HybridLimiter allowing at most `limit` tokens per
+    /// wall-clock aligned `window`, in addition to the limits of
+    /// `bucket`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is zero.
+    pub fn new(bucket: TokenBucket, limit: f64, window: Duration) -> HybridLimiter {
+        assert!(!window.is_zero(), "a HybridLimiter window must not be zero");
+
+        HybridLimiter {
+            bucket,
+            limit,
+            window,
+            index: 0,
+            used: 0.0,
+        }
+    }
+
+    /// Returns a new HybridLimiter allowing at most `limit` tokens per
+    /// calendar minute, in addition to the limits of `bucket`.
+    pub fn per_minute(bucket: TokenBucket, limit: f64) -> HybridLimiter {
+        HybridLimiter::new(bucket, limit, Duration::from_secs(60))
+    }
+
+    /// Attempts to acquire `count` tokens from both the bucket and the
+    /// current window.
+    ///
+    /// Returns `Err(TokenAcquisitionError::WindowExhausted { .. })` if
+    /// the window can't afford `count`, leaving the bucket untouched.
+    /// Otherwise returns the result of the bucket's acquisition, and
+    /// only counts `count` against the window if it succeeded.
+    pub fn acquire(&mut self, count: f64) -> TokenAcquisitionResult {
+        let since_epoch = self.bucket.now()
+                              .duration_since(SystemTime::UNIX_EPOCH)
+                              .unwrap_or(Duration::ZERO);
+        let window_ns = self.window.as_nanos();
+        let index = since_epoch.as_nanos() / window_ns;
+
+        // Windows only move forward, so a clock that jumps backwards
+        // can't reopen an exhausted window.
+        if index > self.index {
+            self.index = index;
+            self.used = 0.0;
+        }
+
+        if self.used + count > self.limit {
+            let window_end = (self.index + 1) * window_ns;
+            let resets_in = Duration::from_nanos((window_end - since_epoch.as_nanos()) as u64);
+            return Err(TokenAcquisitionError::WindowExhausted {
+                limit: self.limit,
+                resets_in,
+            });
+        }
+
+        let result = self.bucket.acquire(count);
+        if result.is_ok() {
+            self.used += count;
+        }
+        result
+    }
+
+    /// Returns the number of tokens that can still be acquired in the
+    /// current window, as of the last acquisition.
+    pub fn window_remaining(&self) -> f64 {
+        (self.limit - self.used).max(0.0)
+    }
+
+    /// Returns a reference to the underlying bucket.
+    pub fn bucket(&self) -> &TokenBucket {
+        &self.bucket
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ManualClock;
+
+    // This module contains unit tests for the HybridLimiter implementation.
+
+    /// 1. **Window Cap and Reset**:
+    ///    - Test exhausting the per-minute cap shortly before a minute boundary, then crossing it via the injected clock.
+    ///    - Acquisitions should be denied by the window while the bucket still has tokens, and allowed again once the next minute begins.
+    #[test]
+    fn test_window_cap_resets_on_minute_boundary() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(59));
+        let bucket = TokenBucket::builder(10.0, 10.0).clock(clock.clone()).build();
+        let mut limiter = HybridLimiter::per_minute(bucket, 3.0);
+
+        for _ in 0..3 {
+            assert!(limiter.acquire(1.0).is_ok());
+        }
+        assert_eq!(
+            limiter.acquire(1.0),
+            Err(TokenAcquisitionError::WindowExhausted {
+                limit: 3.0,
+                resets_in: Duration::from_secs(1),
+            })
+        );
+        assert_eq!(limiter.window_remaining(), 0.0);
+
+        clock.advance(Duration::from_secs(1));
+        assert!(limiter.acquire(1.0).is_ok());
+        assert_eq!(limiter.window_remaining(), 2.0);
+    }
+
+    /// 2. **Bucket Still Applies**:
+    ///    - Test acquiring more than the bucket's burst within a generous window.
+    ///    - The bucket's denial should be returned, and the denied tokens should not count against the window.
+    #[test]
+    fn test_bucket_still_applies() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let bucket = TokenBucket::builder(1.0, 2.0).clock(clock.clone()).build();
+        let mut limiter = HybridLimiter::per_minute(bucket, 100.0);
+
+        assert!(limiter.acquire(2.0).is_ok());
+        assert!(matches!(
+            limiter.acquire(1.0),
+            Err(TokenAcquisitionError::RateLimited { .. })
+        ));
+        assert_eq!(limiter.window_remaining(), 98.0);
+    }
+
+    /// 3. **Backwards Clock**:
+    ///    - Test exhausting a window right after a minute boundary, then moving the injected clock back into the previous minute.
+    ///    - The exhausted window should stay exhausted instead of being reset by the regression.
+    #[test]
+    fn test_backwards_clock_keeps_window() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(60));
+        let bucket = TokenBucket::builder(10.0, 10.0).clock(clock.clone()).build();
+        let mut limiter = HybridLimiter::per_minute(bucket, 2.0);
+        assert!(limiter.acquire(2.0).is_ok());
+
+        clock.set(SystemTime::UNIX_EPOCH + Duration::from_secs(59));
+        assert_eq!(
+            limiter.acquire(1.0),
+            Err(TokenAcquisitionError::WindowExhausted {
+                limit: 2.0,
+                resets_in: Duration::from_secs(61),
+            })
+        );
+    }
+}
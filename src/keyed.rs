@@ -0,0 +1,276 @@
+use crate::{TokenAcquisitionError, TokenAcquisitionResult, TokenBucket, TokenBucketBuilder};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::time::{Duration, SystemTime};
+
+/// Represents a rate limit per key, e.g. per client, under a parent
+/// budget shared by all keys.
+///
+/// Every key gets its own [TokenBucket](struct.TokenBucket.html), built
+/// from a common configuration the first time the key is seen, and an
+/// acquisition must be allowed by both the key's bucket and the parent
+/// bucket. As with a
+/// [MultiRateLimiter](struct.MultiRateLimiter.html), a denial by one of
+/// them leaves the tokens of the other untouched.
+///
+/// By default the parent budget is handed out first-come,
+/// first-served, so one noisy key can use it all up. With
+/// [fair_queuing()](#method.fair_queuing) enabled, it is shared
+/// fairly among the active keys while it is scarce.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::{KeyedRateLimiter, TokenBucket};
+/// // 10 per second per client, but no more than 15 per second overall.
+/// let mut limiter = KeyedRateLimiter::new(TokenBucket::new(15.0, 15.0), TokenBucket::builder(10.0, 10.0));
+/// assert!(limiter.acquire("alice", 10.0).is_ok());
+/// assert!(limiter.acquire("alice", 1.0).is_err());
+/// assert!(limiter.acquire("bob", 5.0).is_ok());
+/// assert!(limiter.acquire("carol", 1.0).is_err());
+/// ```
+pub struct KeyedRateLimiter<K> {
+    parent:  TokenBucket,
+    per_key: TokenBucketBuilder,
+    keys:    HashMap<K, Key>,
+    fair:    bool,
+    // Represents the virtual times of the active keys as a multiset,
+    // keyed by their bits, which order like the non-negative values.
+    served:  BTreeMap<u64, usize>,
+    // Represents the time of the latest sweep for idle keys, according
+    // to the parent's clock.
+    swept:   SystemTime,
+}
+
+struct Key {
+    bucket:    TokenBucket,
+    // Represents the tokens the key was served from the parent budget,
+    // i.e. its virtual time. Only compared between keys in fair
+    // queuing mode.
+    served:    f64,
+    // Represents the time of the key's latest acquisition attempt,
+    // according to the parent's clock.
+    attempted: SystemTime,
+    // Represents whether the key's virtual time is counted among those
+    // of the active keys.
+    active:    bool,
+}
+
+impl<K: Hash + Eq> KeyedRateLimiter<K> {
+    /// Returns a new KeyedRateLimiter limiting every key by a bucket
+    /// built from `per_key`, and all keys together by `parent`.
+    pub fn new(parent: TokenBucket, per_key: TokenBucketBuilder) -> KeyedRateLimiter<K> {
+        KeyedRateLimiter {
+            parent,
+            per_key,
+            keys:   HashMap::new(),
+            fair:   false,
+            served: BTreeMap::new(),
+            swept:  SystemTime::UNIX_EPOCH,
+        }
+    }
+
+    /// Sets whether the parent budget is shared fairly among the active
+    /// keys while it is scarce, rather than first-come, first-served.
+    ///
+    /// In fair queuing mode every key has a virtual time, the number of
+    /// tokens it was served from the parent budget. While an
+    /// acquisition would leave the parent less than half full, a key
+    /// whose virtual time is already more than the requested tokens
+    /// ahead of the least served active key is denied, which leaves
+    /// the remaining budget to the keys that are behind. An active key
+    /// is one that attempted an acquisition within the time the parent
+    /// takes to refill its burst value. Keys are only swept for
+    /// inactivity once per that time, so a key may count as active for
+    /// up to twice as long. A key joining, or returning
+    /// after a break, starts level with the least served active key
+    /// rather than with credit for the time it was away. Defaults to
+    /// `false`.
+    pub fn fair_queuing(&mut self, fair: bool) {
+        self.fair = fair;
+    }
+
+    /// Attempts to acquire `count` tokens for `key` from both its own
+    /// bucket and the parent bucket.
+    ///
+    /// Returns the error of the key's bucket if it can't afford
+    /// `count`, then that of the parent bucket. A key denied in fair
+    /// queuing mode to leave the scarce parent budget to other keys
+    /// gets `Err(TokenAcquisitionError::RateLimited { .. })` as well,
+    /// reporting the parent's refill rate and a zero `scarce_for`.
+    /// Otherwise acquires from both and returns the result of the
+    /// parent bucket.
+    pub fn acquire(&mut self, key: K, count: f64) -> TokenAcquisitionResult {
+        let now = self.parent.now();
+        if now.duration_since(self.swept).is_ok_and(|since| since >= refill_time(&self.parent)) {
+            self.evict_idle(now);
+        }
+        let floor = self.served.keys().next().map(|&bits| f64::from_bits(bits));
+        let per_key = &self.per_key;
+        let entry = self.keys.entry(key).or_insert_with(|| Key {
+            bucket:    per_key.clone().build(),
+            served:    0.0,
+            attempted: SystemTime::UNIX_EPOCH,
+            active:    false,
+        });
+        if !entry.active {
+            entry.served = entry.served.max(floor.unwrap_or(0.0));
+            entry.active = true;
+            *self.served.entry(entry.served.to_bits()).or_insert(0) += 1;
+        }
+        entry.attempted = now;
+
+        // The key's bucket is checked and acquired from at a single
+        // reading of its clock. If it denies, it is acquired from right
+        // away to reach the same denial and record it as usual.
+        let key_now = entry.bucket.now();
+        let key_reserve = entry.bucket.reserve;
+        if entry.bucket.check_at(count, key_reserve, key_now).is_err() {
+            return entry.bucket.acquire_above(count, key_reserve, key_now).0;
+        }
+
+        let (tokens, _) = self.parent.replenished(now.max(self.parent.last));
+        let parent_reserve = self.parent.reserve;
+        let scarce = !self.parent.covers(tokens - parent_reserve, count) || tokens - count < self.parent.b / 2.0;
+        if self.fair && scarce && floor.is_some_and(|floor| entry.served - floor > count) {
+            return Err(TokenAcquisitionError::RateLimited {
+                rate:       self.parent.r,
+                scarce_for: Duration::ZERO,
+            });
+        }
+
+        let rate = self.parent.acquire_above(count, parent_reserve, now).0?;
+        entry.bucket.acquire_above(count, key_reserve, key_now).0?;
+        forget(&mut self.served, entry.served);
+        entry.served += count;
+        *self.served.entry(entry.served.to_bits()).or_insert(0) += 1;
+        Ok(rate)
+    }
+
+    /// Returns the parent bucket shared by all keys.
+    pub fn parent(&self) -> &TokenBucket {
+        &self.parent
+    }
+
+    /// Returns the number of keys currently tracked. A key is forgotten
+    /// by the first sweep after it has been idle for longer than both
+    /// the parent bucket and its own bucket take to refill their burst
+    /// value. Sweeps run on acquisitions, at most once per the time the
+    /// parent bucket takes to refill its burst value.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns whether no key is currently tracked.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    // Stops counting the virtual times of the keys that are no longer
+    // active at `now`, and forgets those whose buckets have also
+    // refilled since their latest acquisition attempt, so a key coming
+    // back gets the same fresh bucket and virtual time it would have
+    // kept.
+    fn evict_idle(&mut self, now: SystemTime) {
+        let horizon = refill_time(&self.parent);
+        let served = &mut self.served;
+        self.keys.retain(|_, k| {
+            let idle = now.duration_since(k.attempted).unwrap_or(Duration::ZERO);
+            if k.active && idle > horizon {
+                forget(served, k.served);
+                k.active = false;
+            }
+            idle <= horizon.max(refill_time(&k.bucket))
+        });
+        self.swept = now;
+    }
+}
+
+// Removes one occurrence of the virtual time `served` from the
+// multiset `set`.
+fn forget(set: &mut BTreeMap<u64, usize>, served: f64) {
+    if let Some(n) = set.get_mut(&served.to_bits()) {
+        *n -= 1;
+        if *n == 0 {
+            set.remove(&served.to_bits());
+        }
+    }
+}
+
+// Returns the time `bucket` takes to refill its burst value from
+// empty.
+fn refill_time(bucket: &TokenBucket) -> Duration {
+    Duration::try_from_secs_f64(bucket.b / bucket.r).unwrap_or(Duration::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ManualClock;
+
+    // This module contains unit tests for the KeyedRateLimiter implementation.
+
+    // Runs a noisy key attempting five acquisitions and a quiet key
+    // attempting one every 100ms for ten seconds, under a parent
+    // budget of 10 per second, and returns how many acquisitions each
+    // key was granted.
+    fn contend(fair: bool) -> (u32, u32) {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let parent = TokenBucket::builder(10.0, 10.0).clock(clock.clone()).build();
+        let per_key = TokenBucket::builder(1e9, 1e9).clock(clock.clone());
+        let mut limiter = KeyedRateLimiter::new(parent, per_key);
+        limiter.fair_queuing(fair);
+
+        let (mut noisy, mut quiet) = (0, 0);
+        for _ in 0..100 {
+            clock.advance(Duration::from_millis(100));
+            for _ in 0..5 {
+                if limiter.acquire("noisy", 1.0).is_ok() {
+                    noisy += 1;
+                }
+            }
+            if limiter.acquire("quiet", 1.0).is_ok() {
+                quiet += 1;
+            }
+        }
+        (noisy, quiet)
+    }
+
+    /// 1. **Fair Sharing**:
+    ///    - Test a noisy and a quiet key contending for a scarce parent budget, first come first served and with fair queuing.
+    ///    - Without fair queuing the noisy key should starve the quiet one, while with it both should get about half of the budget.
+    #[test]
+    fn test_fair_queuing() {
+        let (noisy, quiet) = contend(false);
+        assert!(quiet <= 5, "noisy {}, quiet {}", noisy, quiet);
+
+        let (noisy, quiet) = contend(true);
+        assert!(noisy + quiet >= 100, "noisy {}, quiet {}", noisy, quiet);
+        assert!(quiet >= 45 && noisy >= 45, "noisy {}, quiet {}", noisy, quiet);
+    }
+
+    /// 2. **Key Denials and Idle Keys**:
+    ///    - Test a key whose integer-only bucket rejects a fractional acquisition, then two keys of which one stops acquiring.
+    ///    - The rejected acquisition should leave the parent budget untouched, and the idle key should be forgotten by the first sweep after both buckets could have refilled, but not before.
+    #[test]
+    fn test_key_denials_and_eviction() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let parent = TokenBucket::builder(10.0, 10.0).clock(clock.clone()).build();
+        let per_key = TokenBucket::builder(1.0, 2.0).integer_only(true).clock(clock.clone());
+        let mut limiter = KeyedRateLimiter::new(parent, per_key);
+
+        assert!(matches!(limiter.acquire("idle", 0.5), Err(TokenAcquisitionError::FractionalCount { .. })));
+        assert_eq!(limiter.parent().remaining_after(0.0), Some(10.0));
+        assert!(limiter.acquire("idle", 1.0).is_ok());
+        assert!(limiter.acquire("busy", 1.0).is_ok());
+        assert_eq!(limiter.len(), 2);
+
+        clock.advance(Duration::from_secs(2));
+        assert!(limiter.acquire("busy", 1.0).is_ok());
+        assert_eq!(limiter.len(), 2);
+
+        clock.advance(Duration::from_secs(1));
+        assert!(limiter.acquire("busy", 1.0).is_ok());
+        assert_eq!(limiter.len(), 1);
+    }
+}
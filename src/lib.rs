@@ -5,7 +5,7 @@
 //!
 //! ```no_run
 //! use tokenbucket::TokenBucket;
-//! use tokenbucket::TokenAcquisitionResult;
+//! use tokenbucket::{TokenAcquisitionError, TokenAcquisitionResult};
 //! use std::{thread, time};
 //! 
 //! // Will acquire tokens at the specified rate for the specified duration.
@@ -18,7 +18,10 @@
 //!         // Determine the acquisition result.
 //!         match acquisition {
 //!             Ok(rate)  => println!("rate/allow: {}, true", rate),
-//!             Err(rate) => println!("rate/allow: {}, false", rate),
+//!             Err(TokenAcquisitionError::RateLimited { rate, .. }) => {
+//!                 println!("rate/allow: {}, false", rate)
+//!             },
+//!             Err(err) => println!("error: {:?}", err),
 //!         }
 //!         
 //!         // Sleep for enough time to match the desired rate/second.
@@ -39,8 +42,105 @@
 //!     run(&mut token_bucket, 2, 10);
 //! }
 //! ```
+//!
+//! # Metrics
+//!
+//! With the `prometheus`, `opentelemetry` or `metrics` feature, a bucket
+//! reports every acquisition that is allowed or denied for lack of
+//! tokens, along with its fill ratio afterwards, through a `Registry`, a
+//! `Meter` or a `Recorder` respectively. Acquisitions rejected as
+//! invalid, e.g. for exceeding `max_cost`, are not reported.
+
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::future::Future;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+mod admission;
+mod atomic;
+mod binding;
+mod builder;
+mod clock;
+mod config;
+mod deadline;
+mod descriptor;
+mod env;
+mod ewma;
+#[cfg(feature = "metrics")]
+mod facade;
+#[cfg(feature = "histogram")]
+mod histogram;
+mod hybrid;
+mod keyed;
+mod limiter;
+mod local;
+mod local_scheduler;
+mod macros;
+mod multi;
+#[cfg(feature = "opentelemetry")]
+mod otel;
+#[cfg(feature = "prometheus")]
+mod prometheus;
+mod quota;
+mod rate_limited;
+mod reservation;
+mod retry;
+mod rounding;
+mod scheduler;
+mod set;
+mod simulation;
+mod shared;
+mod ticks;
+mod tiered;
+mod timer;
+mod tokens;
+#[cfg(test)]
+mod test_util;
+
+pub use admission::{Admission, AdmissionPolicy, AlwaysAdmit};
+pub use atomic::AtomicTokenBucket;
+pub use binding::BindingConstraint;
+pub use builder::TokenBucketBuilder;
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use config::{BucketConfig, ParseBucketConfigError};
+pub use deadline::HasDeadline;
+pub use descriptor::{LimitDescriptor, ParseLimitDescriptorError};
+pub use env::EnvError;
+#[cfg(feature = "metrics")]
+pub use facade::{set_recorder, MetricNames, Recorder, SetRecorderError};
+pub use hybrid::HybridLimiter;
+pub use keyed::KeyedRateLimiter;
+pub use limiter::Limiter;
+pub use local::LocalTokenBucket;
+pub use local_scheduler::LocalScheduler;
+pub use multi::MultiRateLimiter;
+#[cfg(feature = "opentelemetry")]
+pub use otel::Meter;
+#[cfg(feature = "prometheus")]
+pub use prometheus::Registry;
+pub use quota::QuotaLimiter;
+pub use rate_limited::RateLimited;
+pub use reservation::Reservation;
+pub use retry::{Backoff, RetryPolicy};
+pub use rounding::RoundingMode;
+pub use scheduler::WeightedScheduler;
+pub use set::TokenBucketSet;
+pub use shared::SharedTokenBucket;
+pub use simulation::SimulationReport;
+pub use ticks::Ticks;
+pub use tiered::TieredTokenBucket;
+pub use tokens::Tokens;
 
-use std::time::SystemTime;
+/// The default tolerance for float rounding when a
+/// [TokenBucket](struct.TokenBucket.html) compares token counts, in
+/// tokens. See
+/// [TokenBucketBuilder.epsilon()](struct.TokenBucketBuilder.html#method.epsilon).
+pub const EPSILON: f64 = 1e-9;
 
 /// Represents a thread-safe token bucket object.
 pub struct TokenBucket {
@@ -59,32 +159,399 @@ pub struct TokenBucket {
     // Represents the last time at which one or more tokens was
     // acquired from the bucket.
     last:   SystemTime,
+    // Represents the largest number of tokens a single acquisition
+    // may request. `None` means acquisitions are not validated.
+    max_cost: Option<f64>,
+    // Represents the number of tokens held back from ordinary
+    // acquisitions. Only `acquire_reserve` may dip below this level.
+    reserve: f64,
+    // Invoked with the number of tokens added to the bucket whenever
+    // accrued tokens are committed to it.
+    on_replenish: Option<Box<dyn FnMut(f64) + Send>>,
+    // Invoked with the requested count and the tokens available
+    // whenever an acquisition is denied for lack of tokens.
+    on_denied: Option<Box<dyn FnMut(f64, f64) + Send>>,
+    // Represents the source of the current time. `None` means the
+    // system clock is read directly.
+    clock: Option<Arc<dyn Clock>>,
+    // Represents the time of the first denial since the last
+    // successful acquisition. `None` while no acquisition is denied.
+    scarce_since: Option<SystemTime>,
+    // Represents whether the bucket only hands out whole tokens.
+    integer_only: bool,
+    // Represents the bounds `b` is adapted within. `None` means `b` is
+    // fixed.
+    adaptive: Option<(f64, f64)>,
+    // Represents the number of tokens removed by every denial and the
+    // largest debt below empty they may add up to. `None` means
+    // denials are free.
+    penalty: Option<(f64, f64)>,
+    // Represents the moving average of the acquisition rate. `None`
+    // unless the rate is observed.
+    observed: Option<ewma::Ewma>,
+    // Represents the cumulative number of accrued tokens discarded
+    // because the bucket was full.
+    overflowed: f64,
+    // Represents the deductions reserved with `reserve_at` that
+    // haven't been committed yet, as `(time, count)` ordered by time.
+    // Deductions that are due are already reflected by `replenished`.
+    scheduled: Vec<(SystemTime, f64)>,
+    // Represents whether `last` is still the UNIX epoch set by
+    // `const_new`, rather than a time read from the clock.
+    lazy: bool,
+    // Represents the longest stretch without acquisition attempts
+    // that tokens accrue over. `None` means accrual is only bounded by
+    // the burst value.
+    idle_cap: Option<Duration>,
+    // Represents the time of the latest denied acquisition. Accrual
+    // continues for up to `idle_cap` after the later of it and `last`.
+    denied_at: SystemTime,
+    // Represents the time cut from earlier idle stretches since the
+    // last acquisition, which no longer accrues tokens, along with the
+    // value of `last` it refers to. Any other value of `last` means no
+    // time was cut.
+    forfeited: (SystemTime, Duration),
+    // Represents whether denials are only counted in `shadow_denials`
+    // rather than reported to the caller.
+    shadow: bool,
+    shadow_denials: u64,
+    // Represents the tolerance for float rounding when comparing token
+    // counts.
+    epsilon: f64,
+    // Represents how the tokens accrued on every refill are rounded.
+    rounding: RoundingMode,
+    // Represents the tokens accrued by `last` that the rounding mode
+    // didn't credit yet, or credited ahead of time if negative. Always
+    // zero without rounding.
+    remainder: f64,
+    // Represents the number of initial acquisitions allowed without
+    // tokens, and how many of them are left.
+    grace:      u64,
+    grace_left: u64,
+    // Represents the policy consulted after the token check. `None`
+    // means the token check decides alone.
+    admission: Option<Arc<dyn AdmissionPolicy>>,
+    // Represents the timer waking the task last polled by
+    // `poll_acquire`. `None` until it returns `Poll::Pending`.
+    timer: Option<timer::Timer>,
+    // Represents the metrics the bucket reports acquisitions to.
+    // `None` until the bucket is registered.
+    #[cfg(feature = "prometheus")]
+    metrics: Option<Arc<prometheus::Series>>,
+    #[cfg(feature = "opentelemetry")]
+    meter:   Option<Arc<dyn Meter>>,
+    // Represents the names the bucket reports metrics under to the
+    // installed recorder. `None` means the bucket never reports.
+    #[cfg(feature = "metrics")]
+    metric_names: Option<MetricNames>,
+    // Represents the histogram of the time blocking acquisitions
+    // waited.
+    #[cfg(feature = "histogram")]
+    waits: histogram::Histogram,
 }
 
 /// Represents the acquisition result from a call to 
 /// [TokenBucket.acquire()](struct.TokenBucket.html#method.acquire).
 ///
 /// Err() is called if the number of tokens desired is not currently
-/// available in the bucket. Otherwise, Ok() is called.
+/// available in the bucket, or if the request itself is invalid for
+/// the bucket. Otherwise, Ok() is called.
+///
+/// Ok() will supply the current rate of the Bucket in tokens acquired
+/// per second. See
+/// [TokenAcquisitionError](enum.TokenAcquisitionError.html) for what
+/// Err() supplies.
+pub type TokenAcquisitionResult = Result<f64, TokenAcquisitionError>;
+
+/// Represents the reason a call to
+/// [TokenBucket.acquire()](struct.TokenBucket.html#method.acquire)
+/// did not acquire any tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenAcquisitionError {
+    /// The number of tokens desired is not currently available in the
+    /// bucket. `rate` is the current rate of the bucket in tokens
+    /// acquired per second. `scarce_for` is how long the bucket has
+    /// been continuously denying acquisitions, measured from the first
+    /// denial since the last successful acquisition.
+    RateLimited { rate: f64, scarce_for: Duration },
+    /// The number of tokens desired is larger than the `max_cost`
+    /// configured on the bucket. The bucket is left untouched.
+    CostExceedsMax { count: f64, max_cost: f64 },
+    /// The number of tokens desired is larger than the burst value of
+    /// the bucket (less any reserve), so it could never be satisfied no matter how long
+    /// the caller waits. Only returned by the blocking acquisition
+    /// methods.
+    CostExceedsBurst { count: f64, burst: f64 },
+    /// The fixed window of a
+    /// [HybridLimiter](struct.HybridLimiter.html) can't afford the
+    /// number of tokens desired. `limit` is the cap per window and
+    /// `resets_in` is how long until the next window begins.
+    WindowExhausted { limit: f64, resets_in: Duration },
+    /// The number of tokens desired is not a whole number, but the
+    /// bucket is configured to only hand out whole tokens.
+    FractionalCount { count: f64 },
+    /// The caller cancelled a blocking acquisition before the tokens
+    /// became available. See
+    /// [TokenBucket.acquire_wait_cancellable()](struct.TokenBucket.html#method.acquire_wait_cancellable).
+    Cancelled,
+    /// Reserving `count` tokens at the requested time would overdraw
+    /// the bucket at some point, counting the reservations already
+    /// made. See
+    /// [TokenBucket.reserve_at()](struct.TokenBucket.html#method.reserve_at).
+    Overbooked { count: f64 },
+    /// The bucket's
+    /// [AdmissionPolicy](trait.AdmissionPolicy.html) vetoed the
+    /// acquisition. The bucket is left untouched.
+    Vetoed,
+    /// A [LocalTokenBucket](struct.LocalTokenBucket.html) was acquired
+    /// from while an acquisition from it was already in progress, e.g.
+    /// from within one of the bucket's own callbacks.
+    Reentrant,
+}
+
+impl fmt::Display for TokenAcquisitionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenAcquisitionError::RateLimited { scarce_for, .. } => {
+                write!(f, "rate limited: not enough tokens available, scarce for {:?}", scarce_for)
+            },
+            TokenAcquisitionError::CostExceedsMax { count, max_cost } => {
+                write!(f, "requested {} tokens, more than the maximum cost of {}", count, max_cost)
+            },
+            TokenAcquisitionError::CostExceedsBurst { count, burst } => {
+                write!(f, "requested {} tokens, more than the burst value of {} can ever hold", count, burst)
+            },
+            TokenAcquisitionError::WindowExhausted { limit, resets_in } => {
+                write!(f, "window limit of {} tokens exhausted, resets in {:?}", limit, resets_in)
+            },
+            TokenAcquisitionError::FractionalCount { count } => {
+                write!(f, "requested {} tokens, but only whole tokens are handed out", count)
+            },
+            TokenAcquisitionError::Cancelled => f.write_str("acquisition cancelled"),
+            TokenAcquisitionError::Overbooked { count } => {
+                write!(f, "reserving {} tokens would overbook the bucket", count)
+            },
+            TokenAcquisitionError::Vetoed => f.write_str("acquisition vetoed by the admission policy"),
+            TokenAcquisitionError::Reentrant => f.write_str("acquisition made while another one is in progress"),
+        }
+    }
+}
+
+impl std::error::Error for TokenAcquisitionError {}
+
+impl TokenAcquisitionError {
+    /// Returns the stable, machine-readable reason for the error, e.g.
+    /// for branching on in clients or for a field in logs, without
+    /// matching on the fields of every variant.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::{DenyReason, TokenBucket};
+    /// let mut token_bucket = TokenBucket::new(5.0, 1.0);
+    /// assert!(token_bucket.acquire(1.0).is_ok());
+    ///
+    /// let reason = token_bucket.acquire(1.0).unwrap_err().reason();
+    /// assert_eq!(reason, DenyReason::InsufficientTokens);
+    /// assert_eq!(reason.as_str(), "insufficient_tokens");
+    /// ```
+    pub fn reason(&self) -> DenyReason {
+        match self {
+            TokenAcquisitionError::RateLimited { .. } => DenyReason::InsufficientTokens,
+            TokenAcquisitionError::CostExceedsMax { .. } => DenyReason::ExceedsCapacity,
+            TokenAcquisitionError::CostExceedsBurst { .. } => DenyReason::ExceedsCapacity,
+            TokenAcquisitionError::WindowExhausted { .. } => DenyReason::WindowExhausted,
+            TokenAcquisitionError::FractionalCount { .. } => DenyReason::InvalidCount,
+            TokenAcquisitionError::Cancelled => DenyReason::Cancelled,
+            TokenAcquisitionError::Overbooked { .. } => DenyReason::Overbooked,
+            TokenAcquisitionError::Vetoed => DenyReason::Vetoed,
+            TokenAcquisitionError::Reentrant => DenyReason::Reentrant,
+        }
+    }
+}
+
+/// Represents the cause of a
+/// [TokenAcquisitionError](enum.TokenAcquisitionError.html) as a
+/// stable code, as returned by
+/// [TokenAcquisitionError.reason()](enum.TokenAcquisitionError.html#method.reason).
+///
+/// New reasons may be added as new denial causes are, so matches on it
+/// need a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DenyReason {
+    /// The bucket doesn't currently hold the tokens desired, but will
+    /// after refilling for a while.
+    InsufficientTokens,
+    /// The tokens desired are more than the bucket allows at once, e.g.
+    /// more than its `max_cost` or burst value, so waiting won't help.
+    ExceedsCapacity,
+    /// The fixed window of a
+    /// [HybridLimiter](struct.HybridLimiter.html) is used up until it
+    /// resets.
+    WindowExhausted,
+    /// The number of tokens desired isn't valid for the bucket, e.g. a
+    /// fraction for a bucket that only hands out whole tokens.
+    InvalidCount,
+    /// The caller cancelled the acquisition.
+    Cancelled,
+    /// A reservation would overdraw the bucket.
+    Overbooked,
+    /// The bucket's admission policy vetoed the acquisition.
+    Vetoed,
+    /// The bucket was acquired from during another acquisition from it.
+    Reentrant,
+}
+
+impl DenyReason {
+    /// Returns the reason as a snake case code, e.g.
+    /// `"insufficient_tokens"`, that stays the same across releases.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DenyReason::InsufficientTokens => "insufficient_tokens",
+            DenyReason::ExceedsCapacity => "exceeds_capacity",
+            DenyReason::WindowExhausted => "window_exhausted",
+            DenyReason::InvalidCount => "invalid_count",
+            DenyReason::Cancelled => "cancelled",
+            DenyReason::Overbooked => "overbooked",
+            DenyReason::Vetoed => "vetoed",
+            DenyReason::Reentrant => "reentrant",
+        }
+    }
+}
+
+impl fmt::Display for DenyReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Represents the outcome of an acquisition made with
+/// [TokenBucket.decide()](struct.TokenBucket.html#method.decide).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// The tokens were acquired.
+    Allow,
+    /// The acquisition was denied, and the same acquisition can succeed
+    /// after `retry_after`, e.g. for a `Retry-After` header.
+    /// `Duration::MAX` means it never will on its own.
+    Throttle { retry_after: Duration },
+}
+
+/// Represents the runtime state of a
+/// [TokenBucket](struct.TokenBucket.html), as captured by
+/// [TokenBucket.snapshot()](struct.TokenBucket.html#method.snapshot).
+///
+/// The state is independent of any clock, so it can be persisted in
+/// any format, e.g. in Redis, and restored with
+/// [TokenBucket::from_snapshot()](struct.TokenBucket.html#method.from_snapshot).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BucketState {
+    /// The number of tokens stored in the bucket, not counting tokens
+    /// accrued over `elapsed`.
+    pub tokens:  f64,
+    /// The time that had passed since the last acquisition when the
+    /// snapshot was taken. Tokens accrued over this time have not yet
+    /// been added to `tokens`.
+    pub elapsed: Duration,
+}
+
+impl BucketState {
+    /// The length of the encoding written by
+    /// [to_bytes()](#method.to_bytes).
+    pub const ENCODED_LEN: usize = 16;
+
+    /// Returns the state encoded as 16 bytes, for storing it compactly,
+    /// e.g. as a value in a key-value store.
+    ///
+    /// The layout is, in little-endian byte order:
+    ///
+    /// * bytes 0-7: `tokens` as an IEEE 754 `f64`.
+    /// * bytes 8-15: `elapsed` in whole nanoseconds as a `u64`,
+    ///   saturating at `u64::MAX` (about 584 years).
+    pub fn to_bytes(&self) -> [u8; BucketState::ENCODED_LEN] {
+        let elapsed = u64::try_from(self.elapsed.as_nanos()).unwrap_or(u64::MAX);
+        let mut bytes = [0; BucketState::ENCODED_LEN];
+        bytes[..8].copy_from_slice(&self.tokens.to_le_bytes());
+        bytes[8..].copy_from_slice(&elapsed.to_le_bytes());
+        bytes
+    }
+
+    /// Returns the state encoded in `bytes` by
+    /// [to_bytes()](#method.to_bytes), or `None` if `bytes` doesn't
+    /// have the length of an encoded state.
+    pub fn from_bytes(bytes: &[u8]) -> Option<BucketState> {
+        if bytes.len() != BucketState::ENCODED_LEN {
+            return None;
+        }
+
+        let (tokens, elapsed) = bytes.split_at(8);
+        Some(BucketState {
+            tokens:  f64::from_le_bytes(tokens.try_into().ok()?),
+            elapsed: Duration::from_nanos(u64::from_le_bytes(elapsed.try_into().ok()?)),
+        })
+    }
+}
+
+/// Represents the status of a [TokenBucket](struct.TokenBucket.html)
+/// at a single point in time, as returned by
+/// [TokenBucket.status()](struct.TokenBucket.html#method.status).
 ///
-/// Both Ok() and Err() will supply the current rate of the Bucket in
-/// tokens acquired per second.
-pub type TokenAcquisitionResult = Result<f64, f64>;
+/// This is meant for debugging and monitoring, e.g. to back a
+/// `/debug/ratelimit` endpoint.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BucketStatus {
+    /// The number of tokens added to the bucket every second.
+    pub rate:   f64,
+    /// The maximum number of tokens the bucket can hold.
+    pub burst:  f64,
+    /// The number of tokens in the bucket, including tokens accrued
+    /// since the last acquisition.
+    pub tokens: f64,
+    /// The fraction of the burst value currently in the bucket, from
+    /// `0.0` for empty to `1.0` for full.
+    pub fill:   f64,
+    /// The time since the last successful acquisition.
+    pub idle:   Duration,
+}
+
+/// Represents a successful blocking acquisition, as returned by
+/// [TokenBucket.acquire_wait_granted()](struct.TokenBucket.html#method.acquire_wait_granted).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Grant {
+    /// The current rate of the bucket in tokens acquired per second,
+    /// as returned by
+    /// [TokenBucket.acquire()](struct.TokenBucket.html#method.acquire).
+    pub rate:   f64,
+    /// How long the acquisition waited for tokens before it was
+    /// granted.
+    pub waited: Duration,
+    /// The number of tokens left in the bucket right after the grant.
+    pub tokens: f64,
+}
 
 impl TokenBucket {
     /// Returns a new TokenBucket object.
     ///
     /// # Arguments
     ///
-    /// * `r` -  The number of tokens that should be added to the
-    ///          bucket every second. This can also be described as
-    ///          the maximum rate per second that the bucket can
-    ///          sustain before rate limiting.
+    /// * `r` - The number of tokens that should be added to the
+    ///   bucket every second. This can also be described as the
+    ///   maximum rate per second that the bucket can sustain before
+    ///   rate limiting.
     ///
     /// * `b` - The "burst" value for the bucket. This is the maximum
-    ///         number of tokens that can be consumed at one time when
-    ///         the bucket is full. It can also be desribed as the
-    ///         maximum volume of the bucket.
+    ///   number of tokens that can be consumed at one time when the
+    ///   bucket is full. It can also be desribed as the maximum volume
+    ///   of the bucket.
+    ///
+    /// Note that an acquisition of more than `b` tokens can never
+    /// succeed. In particular, a bucket meant to have no burst must
+    /// still have `b` of at least the smallest cost it is acquired
+    /// with, otherwise every `acquire(1.0)` against e.g. `b = 0.5` is
+    /// rate limited forever. Use
+    /// [TokenBucket::new_no_burst()](struct.TokenBucket.html#method.new_no_burst)
+    /// for such buckets.
     ///
     /// # Example
     ///
@@ -93,14 +560,313 @@ impl TokenBucket {
     /// let mut tb = TokenBucket::new(5.0, 100.0);
     /// ```
     pub fn new(r: f64, b: f64) -> TokenBucket {
+        TokenBucket {
+            last: SystemTime::now(),
+            lazy: false,
+            ..TokenBucket::const_new(r, b)
+        }
+    }
+
+    /// Returns a new, full TokenBucket in a constant context, e.g. to
+    /// initialize a `static`.
+    ///
+    /// A constant can't read the clock, so the bucket is clocked
+    /// lazily: it behaves as if it had been idle since the UNIX epoch
+    /// and is therefore full at its first acquisition, after which it
+    /// behaves exactly like one returned by
+    /// [TokenBucket::new()](struct.TokenBucket.html#method.new). Until
+    /// then, e.g.
+    /// [idle_time()](struct.TokenBucket.html#method.idle_time) reports
+    /// the time since the epoch. See
+    /// [token_bucket!](macro.token_bucket.html) for a shorthand.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// use std::sync::Mutex;
+    ///
+    /// static LIMITER: Mutex<TokenBucket> = Mutex::new(TokenBucket::const_new(5.0, 100.0));
+    /// assert!(LIMITER.lock().unwrap().acquire(100.0).is_ok());
+    /// ```
+    pub const fn const_new(r: f64, b: f64) -> TokenBucket {
         TokenBucket {
             r,
             b,
             tokens: b,
-            last: SystemTime::now(),
+            last: SystemTime::UNIX_EPOCH,
+            max_cost: None,
+            reserve: 0.0,
+            on_replenish: None,
+            on_denied: None,
+            clock: None,
+            scarce_since: None,
+            integer_only: false,
+            adaptive: None,
+            penalty: None,
+            observed: None,
+            overflowed: 0.0,
+            lazy: true,
+            idle_cap: None,
+            denied_at: SystemTime::UNIX_EPOCH,
+            forfeited: (SystemTime::UNIX_EPOCH, Duration::ZERO),
+            shadow: false,
+            shadow_denials: 0,
+            epsilon: EPSILON,
+            rounding: RoundingMode::Fractional,
+            remainder: 0.0,
+            grace: 0,
+            grace_left: 0,
+            admission: None,
+            scheduled: Vec::new(),
+            timer: None,
+            #[cfg(feature = "prometheus")]
+            metrics: None,
+            #[cfg(feature = "opentelemetry")]
+            meter: None,
+            #[cfg(feature = "metrics")]
+            metric_names: Some(MetricNames::DEFAULT),
+            #[cfg(feature = "histogram")]
+            waits: histogram::Histogram::new(),
         }
     }
 
+    /// Returns a new TokenBucket that allows no bursts, handing out
+    /// single tokens at rate `r`.
+    ///
+    /// The burst value is exactly one token, the least that still lets
+    /// `acquire(1.0)` succeed. After one token is acquired the next one
+    /// becomes available one refill interval, `1 / r` seconds, later,
+    /// and tokens don't build up while the bucket is idle.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut tb = TokenBucket::new_no_burst(10.0);
+    /// assert!(tb.acquire(1.0).is_ok());
+    /// assert!(tb.acquire(1.0).is_err());
+    /// ```
+    pub fn new_no_burst(r: f64) -> TokenBucket {
+        TokenBucket::new(r, 1.0)
+    }
+
+    /// Returns a new, full TokenBucket holding up to `b` tokens that is
+    /// only ever replenished through
+    /// [TokenBucket.tick()](struct.TokenBucket.html#method.tick).
+    ///
+    /// The bucket's rate is zero, so no tokens accrue with time and
+    /// acquisitions only check and deduct. This lets replenishment be
+    /// driven on the caller's own schedule, e.g. once per frame of a
+    /// game loop or per step of a simulation.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut tb = TokenBucket::manual(3.0);
+    /// assert!(tb.acquire(3.0).is_ok());
+    /// assert!(tb.acquire(1.0).is_err());
+    /// tb.tick(1.0);
+    /// assert!(tb.acquire(1.0).is_ok());
+    /// ```
+    pub fn manual(b: f64) -> TokenBucket {
+        TokenBucket::new(0.0, b)
+    }
+
+    /// Returns a new TokenBucket whose burst value allows `seconds`
+    /// worth of tokens at rate `r`, i.e. `b = r * seconds`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// // 10 tokens per second, allowing a 5 second burst of 50 tokens.
+    /// let mut tb = TokenBucket::with_burst_seconds(10.0, 5.0);
+    /// assert!(tb.acquire(50.0).is_ok());
+    /// ```
+    pub fn with_burst_seconds(r: f64, seconds: f64) -> TokenBucket {
+        TokenBucket::new(r, r * seconds)
+    }
+
+    /// Returns a new TokenBucket from operational terms: a `peak` rate,
+    /// a `sustained` rate, and how long the peak rate may last.
+    ///
+    /// The bucket is derived as:
+    ///
+    /// ```ignore
+    /// r = sustained
+    /// b = sustained + (peak - sustained) * burst_window_seconds
+    /// ```
+    ///
+    /// Running at `peak` drains the bucket by `peak - sustained` tokens
+    /// per second, so a full bucket sustains the peak for at least
+    /// `burst_window`. The extra `sustained` tokens give one second of
+    /// steady-rate headroom, so that even `peak == sustained` yields a
+    /// usable bucket.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// # use std::time::Duration;
+    /// // 10/s steady, allowing 50/s for up to 3 seconds.
+    /// // b = 10 + (50 - 10) * 3 = 130
+    /// let mut tb = TokenBucket::from_throughput(50.0, 10.0, Duration::from_secs(3));
+    /// assert!(tb.acquire(130.0).is_ok());
+    /// ```
+    pub fn from_throughput(peak: f64, sustained: f64, burst_window: Duration) -> TokenBucket {
+        let b = sustained + (peak - sustained) * burst_window.as_secs_f64();
+        TokenBucket::new(sustained, b)
+    }
+
+    /// Returns a new TokenBucket for limiting bandwidth, configured in
+    /// megabytes and acquired from in bytes.
+    ///
+    /// One token is one byte, so `r` is `mb_per_sec` and `b` is
+    /// `burst_mb` converted to bytes, with a megabyte being 1,000,000
+    /// bytes. Acquire the size of each transfer with
+    /// `acquire(bytes as f64)`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// // 2 MB/s, allowing bursts of up to 5 MB.
+    /// let mut tb = TokenBucket::bytes_per_sec(2.0, 5.0);
+    /// let payload = vec![0u8; 64 * 1024];
+    /// assert!(tb.acquire(payload.len() as f64).is_ok());
+    /// ```
+    pub fn bytes_per_sec(mb_per_sec: f64, burst_mb: f64) -> TokenBucket {
+        const BYTES_PER_MB: f64 = 1_000_000.0;
+        TokenBucket::new(mb_per_sec * BYTES_PER_MB, burst_mb * BYTES_PER_MB)
+    }
+
+    /// Splits the bucket into two buckets, the first receiving `ratio`
+    /// and the second `1 - ratio` of its capacity.
+    ///
+    /// The rate `r`, the burst value `b` and the tokens currently in
+    /// the bucket, including any accrued since the last acquisition,
+    /// are divided between the two, so their sums match the original.
+    /// The `reserve` and any adaptive burst range are divided the same
+    /// way, while `max_cost`, the integer-only mode and the clock are
+    /// kept by both. Registered callbacks are dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `0 < ratio < 1`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// // Carve out 30% of the capacity for a sub-tenant.
+    /// let (mut tenant, mut sub_tenant) = TokenBucket::new(10.0, 100.0).split(0.7);
+    /// assert!(tenant.acquire(70.0).is_ok());
+    /// assert!(sub_tenant.acquire(30.0).is_ok());
+    /// ```
+    pub fn split(self, ratio: f64) -> (TokenBucket, TokenBucket) {
+        assert!(ratio > 0.0 && ratio < 1.0, "split ratio must be in (0, 1)");
+
+        let now = self.now().max(self.last);
+        let (tokens, _) = self.replenished(now);
+        let part = |share: f64| TokenBucket {
+            tokens: tokens * share,
+            last: now,
+            max_cost: self.max_cost,
+            reserve: self.reserve * share,
+            clock: self.clock.clone(),
+            integer_only: self.integer_only,
+            adaptive: self.adaptive.map(|(min_b, max_b)| (min_b * share, max_b * share)),
+            penalty: self.penalty.map(|(penalty, max_debt)| (penalty * share, max_debt * share)),
+            idle_cap: self.idle_cap,
+            shadow: self.shadow,
+            epsilon: self.epsilon,
+            rounding: self.rounding,
+            grace: self.grace,
+            grace_left: self.grace_left,
+            admission: self.admission.clone(),
+            scheduled: self.scheduled
+                .iter()
+                .filter(|&&(when, _)| when > now)
+                .map(|&(when, count)| (when, count * share))
+                .collect(),
+            ..TokenBucket::new(self.r * share, self.b * share)
+        };
+
+        (part(ratio), part(1.0 - ratio))
+    }
+
+    /// Returns a [TokenBucketBuilder](struct.TokenBucketBuilder.html)
+    /// for configuring a TokenBucket with optional settings.
+    ///
+    /// `r` and `b` have the same meaning as in
+    /// [TokenBucket::new()](struct.TokenBucket.html#method.new).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut tb = TokenBucket::builder(5.0, 100.0)
+    ///     .max_cost(10.0)
+    ///     .build();
+    /// ```
+    pub fn builder(r: f64, b: f64) -> TokenBucketBuilder {
+        TokenBucketBuilder::new(r, b)
+    }
+
+    /// Returns a TokenBucket configured by `config` whose runtime state
+    /// is restored from `state`.
+    ///
+    /// The time anchor is re-based onto the bucket's clock: the last
+    /// acquisition is placed `state.elapsed` before the current time,
+    /// so tokens accrued before the snapshot are preserved while the
+    /// time spent in storage is not credited. The restored tokens are
+    /// clamped to `[0, b]`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(5.0, 100.0);
+    /// token_bucket.acquire(40.0).unwrap();
+    /// let state = token_bucket.snapshot();
+    ///
+    /// let mut restored = TokenBucket::from_snapshot(TokenBucket::builder(5.0, 100.0), state);
+    /// assert!(restored.acquire(60.0).is_ok());
+    /// ```
+    pub fn from_snapshot(config: TokenBucketBuilder, state: BucketState) -> TokenBucket {
+        let mut bucket = config.build();
+        let now = bucket.now();
+
+        bucket.tokens = state.tokens.max(0.0).min(bucket.b);
+        bucket.last = now.checked_sub(state.elapsed).unwrap_or(now);
+        bucket
+    }
+
+    /// Returns a TokenBucket configured by `config` whose runtime state
+    /// is restored from `bytes` written by
+    /// [TokenBucket.to_bytes()](struct.TokenBucket.html#method.to_bytes),
+    /// like
+    /// [TokenBucket::from_snapshot()](struct.TokenBucket.html#method.from_snapshot).
+    ///
+    /// Returns `None` if `bytes` isn't an encoded state.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(5.0, 100.0);
+    /// token_bucket.acquire(40.0).unwrap();
+    /// let bytes = token_bucket.to_bytes();
+    ///
+    /// let mut restored = TokenBucket::from_bytes(TokenBucket::builder(5.0, 100.0), &bytes).unwrap();
+    /// assert!(restored.acquire(60.0).is_ok());
+    /// ```
+    pub fn from_bytes(config: TokenBucketBuilder, bytes: &[u8]) -> Option<TokenBucket> {
+        BucketState::from_bytes(bytes).map(|state| TokenBucket::from_snapshot(config, state))
+    }
+
     /// Attempts to acquire `count` tokens from the bucket. 
     ///
     /// Returns a
@@ -112,10 +878,10 @@ impl TokenBucket {
     /// Every time the acquire() function is called:
     ///
     ///    1. `self.r` tokens will be added for every second that has
-    ///        elapsed since the last invocation of acquire().
+    ///       elapsed since the last invocation of acquire().
     ///    2. `count` tokens will be removed from the bucket if there are enough tokens available.
     ///    3. The tokens will never exceed the maximum burst value
-    ///        configured in `self.b`, nor will it be less than 0.
+    ///       configured in `self.b`, nor will it be less than 0.
     ///
     /// ```ignore
     /// self.tokens = min { b, tokens + rS }
@@ -128,107 +894,3950 @@ impl TokenBucket {
     /// # Returns
     ///
     /// * `Ok(rate)` - if the requested number of tokens were successfully acquired. `rate` is the rate of token acquisition in tokens per second.
-    /// * `Err(rate)` - if the requested number of tokens could not be acquired. `rate` is the rate of token acquisition in tokens per second.
+    /// * `Err(TokenAcquisitionError::RateLimited { rate, scarce_for })` - if the requested number of tokens could not be acquired. `rate` is the rate of token acquisition in tokens per second. `scarce_for` is how long acquisitions have been continuously denied.
+    /// * `Err(TokenAcquisitionError::CostExceedsMax { .. })` - if `count` is larger than the configured `max_cost`. No tokens are removed and the bucket is not replenished.
+    ///
+    /// # Performance
+    ///
+    /// An acquisition performs a single clock read and never allocates,
+    /// on either the allowed or the denied path. Its cost is dominated
+    /// by the clock read, `SystemTime::now()`; run
+    /// `cargo bench --bench acquire` to measure it on a given host.
+    ///
+    /// While the tokens already in the bucket cover `count` and none of
+    /// the optional settings that observe acquisitions, such as
+    /// [on_replenish()](#method.on_replenish), integer-only mode or
+    /// scheduled deductions, are in use, the acquisition takes a
+    /// shorter path that skips most of the bookkeeping.
     ///
     /// # Example
     ///
     /// ```
-    /// # use tokenbucket::TokenBucket;
+    /// # use tokenbucket::{TokenBucket, TokenAcquisitionError};
     /// let mut token_bucket = TokenBucket::new(5.0, 100.0);
     /// match token_bucket.acquire(1.0) {
     ///    Ok(rate)  => println!("acquired: rate = {}", rate),
-    ///    Err(rate) => println!("rate limited: rate = {}", rate),
+    ///    Err(TokenAcquisitionError::RateLimited { rate, .. }) => {
+    ///        println!("rate limited: rate = {}", rate)
+    ///    },
+    ///    Err(err) => println!("invalid acquisition: {:?}", err),
     /// };
     /// ```
-    pub fn acquire(&mut self, count: f64) -> TokenAcquisitionResult {
-        let now = SystemTime::now();
-        let duration_ms: u128 = now.duration_since(self.last)
-                                   .expect("clock went backwards")
-                                   .as_millis();
+    pub fn acquire(&mut self, count: impl Into<Tokens>) -> TokenAcquisitionResult {
+        let Tokens(count) = count.into();
+        let now = self.now();
+        match self.acquire_plentiful(count, now) {
+            Some(rate) => Ok(rate),
+            None => self.acquire_above(count, self.reserve, now).0,
+        }
+    }
 
-        // Replenish tokens based on the time passed
-        self.tokens = self.b.min(
-            self.tokens + (self.r * duration_ms as f64) / 1000.0,
-        );
+    /// Attempts to acquire `count` tokens from the bucket and returns
+    /// the result along with the number of tokens left in the bucket
+    /// afterwards.
+    ///
+    /// Both values are computed from the same replenished snapshot, so
+    /// the remaining count is exactly what the acquisition left behind.
+    /// Reading it through a second call would replenish again and
+    /// reflect slightly more time. This is useful for generating,
+    /// e.g., `X-RateLimit-Remaining` headers. The remaining count
+    /// includes any configured `reserve`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(5.0, 100.0);
+    /// let (result, remaining) = token_bucket.acquire_with_remaining(10.0);
+    /// assert!(result.is_ok());
+    /// assert_eq!(remaining, 90.0);
+    /// ```
+    pub fn acquire_with_remaining(&mut self, count: f64) -> (TokenAcquisitionResult, f64) {
+        let (result, remaining) = self.acquire_above(count, self.reserve, self.now());
+        (result, self.whole(remaining))
+    }
 
-        // Check if there are enough tokens available
-        let allowed = self.tokens >= count;
+    /// Attempts to acquire `count` tokens from the bucket and returns
+    /// the decision, along with when to retry if it was denied.
+    ///
+    /// This is [acquire()](#method.acquire) in the shape most HTTP
+    /// handlers want: `Decision::Allow`, or `Decision::Throttle` with
+    /// the wait reported by
+    /// [time_until_available()](#method.time_until_available). Denials
+    /// that waiting can't resolve, e.g. for a `count` above `max_cost`
+    /// or the burst value, a fractional count on an integer-only bucket
+    /// or a veto of the admission policy, throttle with a `retry_after`
+    /// of `Duration::MAX`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::{Decision, TokenBucket};
+    /// use std::time::Duration;
+    ///
+    /// let mut token_bucket = TokenBucket::new(0.0, 1.0);
+    /// assert_eq!(token_bucket.decide(1.0), Decision::Allow);
+    /// assert_eq!(token_bucket.decide(1.0), Decision::Throttle { retry_after: Duration::MAX });
+    /// ```
+    pub fn decide(&mut self, count: f64) -> Decision {
+        let affordable = self.r > 0.0 && count + self.reserve <= self.max_burst();
+        let retry_after = match self.acquire(count) {
+            Ok(_) => return Decision::Allow,
+            Err(TokenAcquisitionError::RateLimited { .. }) if affordable => self.time_until_available(count),
+            Err(_) => Duration::MAX,
+        };
+        Decision::Throttle { retry_after }
+    }
 
-        if allowed {
-            self.tokens -= count;
-            self.last = now;
-            let rate: f64 = (1f64 / duration_ms as f64) * 1000.0;
-            Ok(rate)
+    /// Attempts to acquire `count` tokens from the bucket and returns
+    /// the result along with how many of them came out of the burst
+    /// allowance rather than steady accrual.
+    ///
+    /// The tokens up to the one-second line, `r`, are what a client
+    /// acquiring at the sustained rate draws on. Anything above it was
+    /// saved up while the client acquired less, so an acquisition that
+    /// takes tokens from above the line relies on the burst value.
+    /// Tokens are taken from the top, so the second value is the part
+    /// of `count` that was above the line before the acquisition, and
+    /// `0.0` if the acquisition failed. This helps spot clients that
+    /// only stay under the limit thanks to their burst.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::{ManualClock, TokenBucket};
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+    /// let mut token_bucket = TokenBucket::builder(10.0, 100.0).clock(clock.clone()).build();
+    ///
+    /// // 95 of the 100 tokens are above the line of 10.
+    /// let (result, burst) = token_bucket.acquire_with_burst(95.0);
+    /// assert!(result.is_ok());
+    /// assert_eq!(burst, 90.0);
+    /// ```
+    pub fn acquire_with_burst(&mut self, count: f64) -> (TokenAcquisitionResult, f64) {
+        let now = self.now().max(self.last);
+        let (before, _) = self.replenished(now);
+        let (result, after) = self.acquire_above(count, self.reserve, now);
+
+        let line = self.r.min(self.b);
+        let burst = match result {
+            Ok(_) => (before - after.max(line)).max(0.0),
+            Err(_) => 0.0,
+        };
+        (result, burst)
+    }
+
+    /// Attempts to acquire a single token from the bucket, returning
+    /// whether it was allowed.
+    ///
+    /// This is equivalent to `acquire(1.0).is_ok()`, specialized for
+    /// the hottest paths of high-QPS callers: the clock is read once
+    /// and an allowed acquisition skips computing the rate and building
+    /// the result. Denials take the regular path, so callbacks and
+    /// bookkeeping behave exactly as with `acquire(1.0)`.
+    ///
+    /// Both are dominated by reading the system clock, so the saving is
+    /// small; `cargo bench --bench acquire` compares the two.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(5.0, 1.0);
+    /// assert!(token_bucket.allow1());
+    /// assert!(!token_bucket.allow1());
+    /// ```
+    #[inline(always)]
+    pub fn allow1(&mut self) -> bool {
+        let now = self.now().max(self.last);
+        let (tokens, duration_ns) = self.replenished(now);
+
+        let plain = self.grace_left == 0 && self.admission.is_none() && self.max_cost.is_none_or(|max_cost| max_cost >= 1.0);
+        if plain && self.covers(tokens - self.reserve, 1.0) {
+            self.grant(1.0, tokens, now);
+            true
         } else {
-            let rate: f64 = (1f64 / duration_ms as f64) * 1000.0;
-            Err(rate)
+            self.settle(1.0, self.reserve, now, tokens, duration_ns).0.is_ok()
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::{thread, time::Duration};
+    /// Attempts to acquire a number of tokens computed lazily by
+    /// `cost_fn`.
+    ///
+    /// `cost_fn` is only evaluated if the bucket holds more than zero
+    /// tokens available to ordinary acquisitions, i.e. if
+    /// `tokens - reserve > 0` after replenishing. Otherwise any positive
+    /// cost would be denied, so `Err(TokenAcquisitionError::RateLimited
+    /// { .. })` is returned without evaluating it. This avoids
+    /// expensive cost computations against an exhausted bucket. Since
+    /// no cost was ever requested, such an early denial isn't reported
+    /// to [on_denied()](#method.on_denied), the penalty or any metrics.
+    /// A bucket that could still let the acquisition through, in
+    /// shadow mode, with a grace allowance left or with an admission
+    /// policy, always evaluates `cost_fn`. Both the check and the
+    /// acquisition use the same point in time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(5.0, 100.0);
+    /// let result = token_bucket.acquire_with(|| {
+    ///     // e.g. estimate the size of a payload
+    ///     42.0
+    /// });
+    /// assert!(result.is_ok());
+    /// ```
+    pub fn acquire_with<F: FnOnce() -> f64>(&mut self, cost_fn: F) -> TokenAcquisitionResult {
+        let now = self.now().max(self.last);
+        let (tokens, duration_ns) = self.replenished(now);
 
-    /// This module contains unit tests for the TokenBucket implementation.
+        let certain = !self.shadow && self.grace_left == 0 && self.admission.is_none();
+        if certain && tokens - self.reserve <= 0.0 {
+            return Err(self.rate_limited(now, duration_ns));
+        }
 
-    /// 1. **Initial Token Acquisition**:
-    ///    - Test acquiring tokens immediately after creating a new TokenBucket.
-    ///    - The bucket should have enough tokens initially, so the acquisition should succeed.
-    #[test]
-    fn test_initial_acquire() {
-        let mut bucket = TokenBucket::new(1.0, 1.0);
-        let result = bucket.acquire(1.0);
-        assert!(result.is_ok());
+        self.acquire_above(cost_fn(), self.reserve, now).0
     }
 
-    /// 2. **Token Acquisition When Tokens Are Available**:
-    ///    - Test acquiring tokens after waiting for some time.
-    ///    - After waiting for a sufficient duration, the bucket should have replenished tokens, so the acquisition should succeed.
-    #[test]
-    fn test_acquire_when_tokens_available() {
-        let mut bucket = TokenBucket::new(1.0, 1.0);
-        let result = bucket.acquire(1.0);
-        assert!(result.is_ok());
-        thread::sleep(Duration::from_secs(1));
-        let result = bucket.acquire(1.0);
-        assert!(result.is_ok());
+    /// Attempts to acquire a time-based `cost` from the bucket,
+    /// treating tokens as seconds.
+    ///
+    /// This is a thin adapter over
+    /// [TokenBucket.acquire()](struct.TokenBucket.html#method.acquire)
+    /// for budgets measured in time consumed, e.g. CPU-seconds, where
+    /// `r` is the number of seconds of budget added every second and
+    /// `b` is the largest budget in seconds that can be spent at once.
+    /// `cost` is converted to fractional seconds, so sub-second costs
+    /// are charged precisely.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// use std::time::Duration;
+    ///
+    /// // Allow half a CPU-second per second, up to 2 CPU-seconds at once.
+    /// let mut cpu_budget = TokenBucket::new(0.5, 2.0);
+    /// assert!(cpu_budget.acquire_duration(Duration::from_millis(1500)).is_ok());
+    /// assert!(cpu_budget.acquire_duration(Duration::from_secs(1)).is_err());
+    /// ```
+    pub fn acquire_duration(&mut self, cost: Duration) -> TokenAcquisitionResult {
+        self.acquire(cost.as_secs_f64())
     }
 
-    /// 3. **Token Acquisition When Tokens Are Not Available**:
-    ///    - Test acquiring more tokens than available in the bucket.
-    ///    - If the requested number of tokens exceeds the available tokens, the acquisition should fail.
-    #[test]
-    fn test_acquire_when_tokens_not_available() {
-        let mut bucket = TokenBucket::new(1.0, 1.0);
-        let result = bucket.acquire(2.0);
-        assert!(result.is_err());
-    }
+    /// Attempts to acquire `count` tokens from the bucket, like
+    /// [acquire()](#method.acquire), and returns the tokens before and
+    /// after the refill step along with the result.
+    ///
+    /// `before` is the number of tokens stored in the bucket since the
+    /// last acquisition and `after` the number available once the
+    /// tokens accrued since then were added, before `count` is taken.
+    /// This is meant for diagnosing why an acquisition succeeded or
+    /// failed, e.g. by logging it in production; it takes the full
+    /// acquisition path, so use `acquire()` otherwise.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::{ManualClock, TokenBucket};
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+    /// let mut token_bucket = TokenBucket::builder(5.0, 100.0).clock(clock.clone()).build();
+    /// token_bucket.acquire(100.0).unwrap();
+    ///
+    /// clock.advance(Duration::from_secs(2));
+    /// let (before, after, result) = token_bucket.acquire_detailed(20.0);
+    /// assert_eq!((before, after), (0.0, 10.0));
+    /// assert!(result.is_err());
+    /// ```
+    pub fn acquire_detailed(&mut self, count: f64) -> (f64, f64, TokenAcquisitionResult) {
+        let now = self.now().max(self.last);
+        let before = self.tokens;
+        let (after, _) = self.replenished(now);
+        let (result, _) = self.acquire_above(count, self.reserve, now);
+        (before, after, result)
+    }
+
+    /// Attempts to acquire `count` tokens from the bucket, like
+    /// [acquire()](#method.acquire), and writes a line describing the
+    /// outcome to `sink`.
+    ///
+    /// Allowed and rate limited acquisitions are written as
+    /// `rate/allow: <rate>, true` and `rate/allow: <rate>, false`, any
+    /// other error as `error: <error>`. This is meant for command line
+    /// tools and demos, e.g. writing to `std::io::stdout()`, or to a
+    /// `Vec<u8>` in tests. A failure to write doesn't change the
+    /// result, which is returned either way.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(5.0, 1.0);
+    /// let mut log = Vec::new();
+    /// assert!(token_bucket.acquire_logged(2.0, &mut log).is_err());
+    /// assert!(String::from_utf8(log).unwrap().ends_with(", false\n"));
+    /// ```
+    pub fn acquire_logged(&mut self, count: f64, sink: &mut impl Write) -> TokenAcquisitionResult {
+        let result = self.acquire(count);
+        let _ = match &result {
+            Ok(rate) => writeln!(sink, "rate/allow: {}, true", rate),
+            Err(TokenAcquisitionError::RateLimited { rate, .. }) => writeln!(sink, "rate/allow: {}, false", rate),
+            Err(err) => writeln!(sink, "error: {}", err),
+        };
+        result
+    }
+
+    /// Attempts to acquire `count` tokens scaled by
+    /// `priority_multiplier` from the bucket, i.e.
+    /// `count * priority_multiplier` tokens.
+    ///
+    /// This is a simple knob for soft prioritization within a single
+    /// bucket: giving low-priority callers a multiplier above `1.0`
+    /// makes their acquisitions more expensive, so they are throttled
+    /// sooner, while high-priority callers pay the nominal `count`
+    /// with a multiplier of `1.0`. In
+    /// [integer-only](struct.TokenBucketBuilder.html#method.integer_only)
+    /// mode the scaled cost must still be a whole number.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `priority_multiplier` is negative or not finite.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(5.0, 100.0);
+    /// // Background jobs pay triple.
+    /// assert!(token_bucket.acquire_weighted(10.0, 3.0).is_ok());
+    /// assert_eq!(token_bucket.remaining_after(0.0).map(f64::round), Some(70.0));
+    /// ```
+    pub fn acquire_weighted(&mut self, count: f64, priority_multiplier: f64) -> TokenAcquisitionResult {
+        assert!(
+            priority_multiplier.is_finite() && priority_multiplier >= 0.0,
+            "priority_multiplier must be finite and not negative"
+        );
+        self.acquire(count * priority_multiplier)
+    }
+
+    /// Attempts to acquire `fraction` of the bucket's burst value, i.e.
+    /// `fraction * b` tokens.
+    ///
+    /// This expresses a cost relative to the burst budget, e.g. `0.1`
+    /// for a request that uses up a tenth of it, so the cost follows
+    /// along when the burst value changes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fraction` is not between `0.0` and `1.0`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(5.0, 200.0);
+    /// assert!(token_bucket.acquire_fraction(0.1).is_ok());
+    /// assert_eq!(token_bucket.remaining_after(0.0).map(f64::round), Some(180.0));
+    /// ```
+    pub fn acquire_fraction(&mut self, fraction: f64) -> TokenAcquisitionResult {
+        assert!((0.0..=1.0).contains(&fraction), "fraction must be between 0 and 1");
+        self.acquire(fraction * self.b)
+    }
+
+    /// Attempts to acquire the tokens a task running for `duration` at
+    /// `tokens_per_sec` will consume, i.e.
+    /// `duration * tokens_per_sec` tokens, all at once.
+    ///
+    /// This is a thin adapter over
+    /// [TokenBucket.acquire()](struct.TokenBucket.html#method.acquire)
+    /// for costs proportional to time, e.g. pre-acquiring the bandwidth
+    /// of a stream with a known bitrate and length.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tokens_per_sec` is negative or not finite, since the
+    /// computed cost would be meaningless.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// use std::time::Duration;
+    ///
+    /// // A 30 second clip at 50 kilobytes per second costs 1500 kilobytes.
+    /// let mut bandwidth = TokenBucket::new(100.0, 2000.0);
+    /// assert!(bandwidth.acquire_for_duration(Duration::from_secs(30), 50.0).is_ok());
+    /// assert_eq!(bandwidth.remaining_after(0.0).map(f64::round), Some(500.0));
+    /// ```
+    pub fn acquire_for_duration(&mut self, duration: Duration, tokens_per_sec: f64) -> TokenAcquisitionResult {
+        assert!(
+            tokens_per_sec.is_finite() && tokens_per_sec >= 0.0,
+            "tokens_per_sec must be finite and not negative"
+        );
+        self.acquire(duration.as_secs_f64() * tokens_per_sec)
+    }
+
+    /// Attempts to acquire `cost` tokens for each of `items` equal-cost
+    /// items and returns how many were `(admitted, rejected)`.
+    ///
+    /// Items are admitted greedily from a single replenished snapshot,
+    /// so once one item is rejected every remaining item is rejected
+    /// too. This is cheaper than collecting individual results when
+    /// only the totals matter.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(5.0, 100.0);
+    /// assert_eq!(token_bucket.acquire_batch(30, 4.0), (25, 5));
+    /// ```
+    pub fn acquire_batch(&mut self, items: usize, cost: f64) -> (usize, usize) {
+        let now = self.now();
+        let admitted = (0..items)
+            .take_while(|_| self.acquire_above(cost, self.reserve, now).0.is_ok())
+            .count();
+
+        (admitted, items - admitted)
+    }
+
+    /// Attempts to acquire `count` tokens from the bucket as if the
+    /// current time were `now`.
+    ///
+    /// Replenishment is computed against the supplied time instead of
+    /// the bucket's clock, which allows replaying recorded traffic at
+    /// its original timestamps or running deterministic simulations.
+    /// A `now` earlier than the last acquisition is clamped to it and
+    /// treated as no time having passed. Otherwise this behaves exactly
+    /// like [TokenBucket.acquire()](struct.TokenBucket.html#method.acquire).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let mut token_bucket = TokenBucket::new(1.0, 1.0);
+    /// let start = SystemTime::now();
+    /// assert!(token_bucket.try_acquire_at(1.0, start).is_ok());
+    /// assert!(token_bucket.try_acquire_at(1.0, start + Duration::from_millis(500)).is_err());
+    /// assert!(token_bucket.try_acquire_at(1.0, start + Duration::from_secs(1)).is_ok());
+    /// ```
+    pub fn try_acquire_at(&mut self, count: f64, now: SystemTime) -> TokenAcquisitionResult {
+        self.acquire_above(count, self.reserve, now).0
+    }
+
+    /// Attempts to acquire `count` tokens from the bucket, including
+    /// any tokens held back by the configured `reserve`.
+    ///
+    /// Ordinary calls to
+    /// [TokenBucket.acquire()](struct.TokenBucket.html#method.acquire)
+    /// treat the reserve as the floor of the bucket, leaving that
+    /// headroom for emergency-only acquisitions made through this
+    /// method. Otherwise this behaves exactly like `acquire()`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::builder(1.0, 10.0)
+    ///     .reserve(2.0)
+    ///     .build();
+    /// assert!(token_bucket.acquire(9.0).is_err());
+    /// assert!(token_bucket.acquire_reserve(9.0).is_ok());
+    /// ```
+    pub fn acquire_reserve(&mut self, count: f64) -> TokenAcquisitionResult {
+        self.acquire_above(count, 0.0, self.now()).0
+    }
+
+    // Performs an acquisition at `now` that may not leave fewer than
+    // `floor` tokens in the bucket. Returns the result along with the
+    // number of tokens left in the bucket afterwards.
+    //
+    // A `now` earlier than `self.last` is treated as no time having
+    // passed, so a clock that jumps backwards neither panics nor moves
+    // `self.last` back and credits the same interval twice.
+    fn acquire_above(
+        &mut self,
+        count: f64,
+        floor: f64,
+        now: SystemTime,
+    ) -> (TokenAcquisitionResult, f64) {
+        let now = now.max(self.last);
+        let (tokens, duration_ns) = self.replenished(now);
+        self.settle(count, floor, now, tokens, duration_ns)
+    }
+
+    // Decides an acquisition of `count` tokens at `now`, given the
+    // `tokens` replenished over `duration_ns`, as computed by
+    // `acquire_above`. In shadow mode a denial for lack of tokens is
+    // only counted.
+    fn settle(
+        &mut self,
+        count: f64,
+        floor: f64,
+        now: SystemTime,
+        tokens: f64,
+        duration_ns: u128,
+    ) -> (TokenAcquisitionResult, f64) {
+        match self.enforce(count, floor, now, tokens, duration_ns) {
+            (Err(TokenAcquisitionError::RateLimited { .. }), tokens) if self.shadow => {
+                self.shadow_denials += 1;
+                let rate: f64 = (1f64 / duration_ns as f64) * 1_000_000_000.0;
+                (Ok(rate), tokens)
+            },
+            decided => decided,
+        }
+    }
+
+    // Decides an acquisition for `settle`, enforcing the limit.
+    fn enforce(
+        &mut self,
+        count: f64,
+        floor: f64,
+        now: SystemTime,
+        tokens: f64,
+        duration_ns: u128,
+    ) -> (TokenAcquisitionResult, f64) {
+        if let Some(max_cost) = self.max_cost {
+            if count > max_cost {
+                let err = TokenAcquisitionError::CostExceedsMax {
+                    count,
+                    max_cost,
+                };
+                return (Err(err), tokens);
+            }
+        }
+
+        if self.integer_only && count.fract() != 0.0 {
+            return (Err(TokenAcquisitionError::FractionalCount { count }), tokens);
+        }
+
+        if self.grace_left > 0 {
+            self.grace_left -= 1;
+            let rate: f64 = (1f64 / duration_ns as f64) * 1_000_000_000.0;
+            return (Ok(rate), tokens);
+        }
+
+        // Check if there are enough tokens available, then let the
+        // admission policy have its say.
+        let covered = self.covers(tokens - floor, count);
+        let allowed = match self.admission.as_ref().map(|policy| policy.admit(count, covered)) {
+            Some(Admission::Veto) => return (Err(TokenAcquisitionError::Vetoed), tokens),
+            Some(Admission::Override) if !covered => {
+                let rate: f64 = (1f64 / duration_ns as f64) * 1_000_000_000.0;
+                return (Ok(rate), tokens);
+            },
+            _ => covered,
+        };
+
+        if allowed {
+            self.grant(count, tokens, now);
+            let rate: f64 = (1f64 / duration_ns as f64) * 1_000_000_000.0;
+            (Ok(rate), self.tokens)
+        } else {
+            let rate: f64 = (1f64 / duration_ns as f64) * 1_000_000_000.0;
+            let since = *self.scarce_since.get_or_insert(now);
+            if self.idle_cap.is_some() {
+                self.end_idle(now);
+            }
+            let scarce_for = now.duration_since(since).unwrap_or(Duration::ZERO);
+            if let Some(callback) = self.on_denied.as_mut() {
+                callback(count, tokens);
+            }
+            if !self.shadow {
+                // A full bucket denying more than its burst value isn't
+                // facing a spike, but a cost its shrunk burst value no
+                // longer allows, so it recovers towards `max_b` instead.
+                let outgrown = tokens + self.epsilon >= self.b && !self.covers(self.b - floor, count);
+                self.adapt_burst(outgrown);
+                self.penalize(tokens);
+            }
+            #[cfg(any(feature = "prometheus", feature = "opentelemetry", feature = "metrics"))]
+            self.record_metrics(false, tokens);
+            (Err(TokenAcquisitionError::RateLimited { rate, scarce_for }), tokens)
+        }
+    }
+
+    // Returns the result `acquire_above` would currently reach for an
+    // acquisition at `now`, without making it or recording anything.
+    // Lets limiters combining several buckets check that every one of
+    // them allows an acquisition before taking it from any, as
+    // `peek_allow` only looks at the tokens.
+    fn check_at(&self, count: f64, floor: f64, now: SystemTime) -> Result<(), TokenAcquisitionError> {
+        let now = now.max(self.last);
+        let (tokens, duration_ns) = self.replenished(now);
+
+        if let Some(max_cost) = self.max_cost {
+            if count > max_cost {
+                return Err(TokenAcquisitionError::CostExceedsMax {
+                    count,
+                    max_cost,
+                });
+            }
+        }
+        if self.integer_only && count.fract() != 0.0 {
+            return Err(TokenAcquisitionError::FractionalCount { count });
+        }
+        if self.grace_left > 0 {
+            return Ok(());
+        }
+
+        let covered = self.covers(tokens - floor, count);
+        match self.admission.as_ref().map(|policy| policy.admit(count, covered)) {
+            Some(Admission::Veto) => Err(TokenAcquisitionError::Vetoed),
+            Some(Admission::Override) => Ok(()),
+            _ if covered || self.shadow => Ok(()),
+            _ => Err(self.rate_limited(now, duration_ns)),
+        }
+    }
+
+    /// Returns whether `acquire(count)` would currently succeed,
+    /// without acquiring anything.
+    ///
+    /// Another caller may still take the tokens before they are
+    /// acquired.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let token_bucket = TokenBucket::new(5.0, 100.0);
+    /// assert!(token_bucket.peek_allow(100.0));
+    /// assert!(!token_bucket.peek_allow(101.0));
+    /// ```
+    pub fn peek_allow(&self, count: f64) -> bool {
+        self.remaining_after(count).is_some()
+    }
+
+    /// Returns the number of tokens an ordinary acquisition could take
+    /// from the bucket right now, counting any tokens accrued since the
+    /// last acquisition and leaving out the `reserve`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::{TokenBucket, Tokens};
+    /// let mut token_bucket = TokenBucket::builder(0.0, 10.0).reserve(2.0).build();
+    /// assert_eq!(token_bucket.available(), Tokens(8.0));
+    /// token_bucket.acquire(Tokens(8.0)).unwrap();
+    /// assert_eq!(token_bucket.available(), Tokens::ZERO);
+    /// ```
+    pub fn available(&self) -> Tokens {
+        let (tokens, _) = self.replenished(self.now());
+        Tokens(self.whole((tokens - self.reserve).max(0.0)))
+    }
+
+    /// Returns whether the bucket currently holds its burst value of
+    /// tokens, counting any tokens accrued since the last acquisition.
+    ///
+    /// Like acquisitions, the comparison allows for the rounding
+    /// tolerance set with
+    /// [TokenBucketBuilder.epsilon()](struct.TokenBucketBuilder.html#method.epsilon),
+    /// so a bucket a rounding error short of full is full.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(0.0, 1.0);
+    /// assert!(token_bucket.is_full());
+    /// assert!(token_bucket.acquire(0.3).is_ok());
+    /// assert!(!token_bucket.is_full());
+    /// ```
+    pub fn is_full(&self) -> bool {
+        let (tokens, _) = self.replenished(self.now());
+        tokens + self.epsilon >= self.b
+    }
+
+    /// Returns whether the bucket currently holds no tokens, counting
+    /// any tokens accrued since the last acquisition. A bucket below
+    /// empty, e.g. after a denial penalty, is empty as well.
+    ///
+    /// Like [is_full()](#method.is_full), the comparison allows for the
+    /// rounding tolerance, so a bucket a rounding error above empty is
+    /// empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(0.0, 1.0);
+    /// assert!(token_bucket.acquire(0.1 + 0.2).is_ok());
+    /// assert!(token_bucket.acquire(0.7).is_ok());
+    /// assert!(token_bucket.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        let (tokens, _) = self.replenished(self.now());
+        tokens <= self.epsilon
+    }
+
+    /// Returns the number of tokens that would remain in the bucket
+    /// after acquiring `count` tokens, without acquiring anything.
+    ///
+    /// Returns `Some(tokens - count)`, counting any tokens accrued
+    /// since the last acquisition, if `acquire(count)` would currently
+    /// succeed. Returns `None` otherwise, which includes any `count`
+    /// larger than the burst value `self.b` or the configured
+    /// `max_cost`. The remaining tokens include any configured
+    /// `reserve`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let token_bucket = TokenBucket::new(5.0, 100.0);
+    /// assert_eq!(token_bucket.remaining_after(30.0), Some(70.0));
+    /// assert_eq!(token_bucket.remaining_after(101.0), None);
+    /// ```
+    pub fn remaining_after(&self, count: f64) -> Option<f64> {
+        if self.max_cost.is_some_and(|max_cost| count > max_cost) {
+            return None;
+        }
+
+        let (tokens, _) = self.replenished(self.now());
+        if self.covers(tokens - self.reserve, count) {
+            Some(self.clamp_dust(tokens - count))
+        } else {
+            None
+        }
+    }
+
+    /// Removes every token currently available in the bucket and
+    /// returns how many were taken.
+    ///
+    /// The bucket is replenished first, so the returned value
+    /// includes any tokens accrued since the last acquisition. After
+    /// the call the bucket is empty and `self.last` is updated to
+    /// the current time, so replenishment starts over from zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(5.0, 100.0);
+    /// let drained = token_bucket.drain();
+    /// assert_eq!(drained, 100.0);
+    /// assert!(token_bucket.acquire(1.0).is_err());
+    /// ```
+    pub fn drain(&mut self) -> f64 {
+        let now = self.now().max(self.last);
+        let (tokens, _) = self.replenished(now);
+        let drained = self.whole(tokens.max(0.0));
+
+        self.notify_replenish(tokens, now);
+        self.tokens = tokens - drained;
+        self.last = now;
+        self.forget_due(now);
+        drained
+    }
+
+    /// Replaces the clock the bucket reads the current time from, e.g.
+    /// to inject clock skew or jumps into a running bucket in tests.
+    ///
+    /// The bucket's own notion of time is rebased onto the new clock:
+    /// every time it keeps, such as that of the last acquisition, is
+    /// moved by the difference between the two clocks' current
+    /// readings. The tokens accrued so far are therefore kept, and
+    /// replenishment carries on from them as the new clock advances,
+    /// rather than crediting or forfeiting the difference between the
+    /// clocks. Time jumps of the new clock after the swap are handled
+    /// like those of any clock.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::{ManualClock, TokenBucket};
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let mut token_bucket = TokenBucket::new(1.0, 10.0);
+    /// token_bucket.drain();
+    ///
+    /// // A clock an hour ahead doesn't refill the bucket by itself...
+    /// let skewed = ManualClock::new(SystemTime::now() + Duration::from_secs(3_600));
+    /// token_bucket.set_clock(skewed.clone());
+    /// assert!(token_bucket.acquire(5.0).is_err());
+    ///
+    /// // ...but its jumps after the swap do.
+    /// skewed.advance(Duration::from_secs(5));
+    /// assert!(token_bucket.acquire(5.0).is_ok());
+    /// ```
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        let before = self.now().max(self.last);
+        self.clock = Some(Arc::new(clock));
+        // A lazily clocked bucket hasn't read a time yet.
+        if self.lazy {
+            return;
+        }
+
+        let after = self.now();
+        let rebase = |at: SystemTime| {
+            let moved = match after.duration_since(before) {
+                Ok(ahead) => at.checked_add(ahead),
+                Err(behind) => at.checked_sub(behind.duration()),
+            };
+            moved.unwrap_or(at)
+        };
+        self.last = rebase(self.last);
+        self.scarce_since = self.scarce_since.map(rebase);
+        self.denied_at = rebase(self.denied_at);
+        self.forfeited.0 = rebase(self.forfeited.0);
+        for (when, _) in &mut self.scheduled {
+            *when = rebase(*when);
+        }
+        if let Some(observed) = self.observed.as_mut() {
+            observed.rebase(rebase);
+        }
+    }
+
+    /// Resets the bucket to the state it was built in: full, with its
+    /// grace allowance restored.
+    ///
+    /// Any pending reservations made with
+    /// [reserve_at()](#method.reserve_at) are dropped, and
+    /// replenishment starts over from the current time. The
+    /// configuration and the cumulative counters, such as
+    /// [overflowed()](#method.overflowed), are kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(0.0, 10.0);
+    /// assert!(token_bucket.acquire(10.0).is_ok());
+    /// assert!(token_bucket.acquire(1.0).is_err());
+    ///
+    /// token_bucket.reset();
+    /// assert!(token_bucket.acquire(10.0).is_ok());
+    /// ```
+    pub fn reset(&mut self) {
+        self.tokens = self.b;
+        self.last = self.now().max(self.last);
+        self.remainder = 0.0;
+        self.lazy = false;
+        self.scarce_since = None;
+        self.scheduled.clear();
+        self.grace_left = self.grace;
+    }
+
+    /// Sets the number of tokens in the bucket to `tokens`, e.g. when
+    /// restoring a token level kept in an external store.
+    ///
+    /// `tokens` is clamped to `[0, b]`. Any tokens accrued since the
+    /// last acquisition are discarded and `self.last` is updated to
+    /// the current time, so replenishment starts over from the given
+    /// level. Use
+    /// [TokenBucket::from_snapshot()](struct.TokenBucket.html#method.from_snapshot)
+    /// to restore a level that should keep accruing from an earlier
+    /// point in time instead.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(5.0, 100.0);
+    /// token_bucket.set_tokens(150.0);
+    /// assert_eq!(token_bucket.drain(), 100.0);
+    /// ```
+    pub fn set_tokens(&mut self, tokens: f64) {
+        self.tokens = tokens.clamp(0.0, self.b);
+        self.last = self.now().max(self.last);
+        self.remainder = 0.0;
+        self.lazy = false;
+        self.forget_due(self.last);
+    }
+
+    /// Adds `tokens` to the bucket, up to the burst value `self.b`,
+    /// without consulting any clock.
+    ///
+    /// This is how a bucket created with
+    /// [TokenBucket::manual()](struct.TokenBucket.html#method.manual)
+    /// is replenished. On any other bucket the tokens are added on top
+    /// of those accruing with time. The
+    /// [on_replenish()](struct.TokenBucket.html#method.on_replenish)
+    /// callback isn't invoked, since it only reports tokens accrued
+    /// over time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tokens` is negative or not finite.
+    pub fn tick(&mut self, tokens: f64) {
+        assert!(tokens >= 0.0 && tokens.is_finite(), "ticked tokens must be finite and not negative");
+        self.tokens = (self.tokens + tokens).min(self.b);
+    }
+
+    /// Corrects an acquisition of an estimated `reserved` tokens once
+    /// its `actual` cost is known, e.g. the time an operation
+    /// really took.
+    ///
+    /// An overestimate returns the `reserved - actual` tokens that
+    /// weren't used to the bucket, up to the burst value. An
+    /// underestimate takes the missing `actual - reserved` tokens from
+    /// the bucket, including any accrued since the last acquisition,
+    /// but never takes the bucket below zero: the part that can't be
+    /// covered is forgiven.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `reserved` or `actual` is negative.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(0.0, 100.0);
+    /// token_bucket.acquire(30.0).unwrap();
+    ///
+    /// // The operation turned out to cost 40 tokens.
+    /// token_bucket.reconcile(30.0, 40.0);
+    /// assert_eq!(token_bucket.remaining_after(0.0), Some(60.0));
+    /// ```
+    pub fn reconcile(&mut self, reserved: f64, actual: f64) {
+        assert!(reserved >= 0.0 && actual >= 0.0, "reconciled costs must not be negative");
+        if actual <= reserved {
+            self.tick(reserved - actual);
+            return;
+        }
+
+        let now = self.now().max(self.last);
+        let (tokens, _) = self.replenished(now);
+        self.notify_replenish(tokens, now);
+        // A bucket already in debt from a denial penalty stays where
+        // it is rather than being raised to zero.
+        self.tokens = (tokens - (actual - reserved)).max(tokens.min(0.0));
+        self.last = now;
+        self.forget_due(now);
+    }
+
+    /// Returns how long it has been since the last successful
+    /// acquisition, according to the bucket's clock.
+    ///
+    /// This is useful for monitoring and for evicting idle buckets.
+    /// Returns `Duration::ZERO` if the clock reads a time before the
+    /// last acquisition.
+    pub fn idle_time(&self) -> Duration {
+        self.now().duration_since(self.last).unwrap_or(Duration::ZERO)
+    }
+
+    /// Reconfigures the bucket from `config`, e.g. after a config file
+    /// was reloaded, while it keeps limiting.
+    ///
+    /// Tokens accrued so far are added at the old rate first. The
+    /// bucket then keeps its relative fill level, so a bucket that was
+    /// half full holds half of the new burst value. The `max_cost`,
+    /// the `reserve` and the integer-only mode are replaced as well.
+    /// The clock, the registered callbacks and the
+    /// [overflowed()](#method.overflowed) count are kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::{BucketConfig, TokenBucket};
+    /// let mut token_bucket = TokenBucket::new(0.0, 100.0);
+    /// token_bucket.acquire(50.0).unwrap();
+    ///
+    /// token_bucket.apply_config(&BucketConfig::new(0.0, 10.0));
+    /// assert_eq!(token_bucket.drain(), 5.0);
+    /// ```
+    pub fn apply_config(&mut self, config: &BucketConfig) {
+        let now = self.now().max(self.last);
+        let (tokens, _) = self.replenished(now);
+        let fill = if self.b > 0.0 { tokens / self.b } else { 0.0 };
+        self.notify_replenish(tokens, now);
+
+        self.r = config.r;
+        self.b = config.b;
+        self.tokens = fill * config.b;
+        self.last = now;
+        self.forget_due(now);
+        self.max_cost = config.max_cost;
+        self.reserve = config.reserve;
+        self.integer_only = config.integer_only;
+    }
+
+    /// Returns the cumulative number of tokens that accrued while the
+    /// bucket was full and were therefore discarded.
+    ///
+    /// A steadily growing value means the rate `r` is over-provisioned
+    /// relative to demand, which helps right-size limits. Tokens are
+    /// accounted for when accrued tokens are added to the bucket,
+    /// i.e. on a successful acquisition or a call to
+    /// [TokenBucket.drain()](struct.TokenBucket.html#method.drain).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let token_bucket = TokenBucket::new(5.0, 100.0);
+    /// assert_eq!(token_bucket.overflowed(), 0.0);
+    /// ```
+    pub fn overflowed(&self) -> f64 {
+        self.overflowed
+    }
+
+    /// Returns the number of acquisitions a bucket in shadow mode would
+    /// have denied. See
+    /// [TokenBucketBuilder.shadow()](struct.TokenBucketBuilder.html#method.shadow).
+    pub fn shadow_denials(&self) -> u64 {
+        self.shadow_denials
+    }
+
+    /// Returns a snapshot of the bucket's runtime state for
+    /// persistence.
+    ///
+    /// The configuration (rate, burst and optional settings) is not
+    /// part of the snapshot. See
+    /// [TokenBucket::from_snapshot()](struct.TokenBucket.html#method.from_snapshot)
+    /// for restoring it.
+    pub fn snapshot(&self) -> BucketState {
+        BucketState {
+            tokens: self.tokens,
+            elapsed: self.idle_time(),
+        }
+    }
+
+    /// Returns a snapshot of the bucket's runtime state encoded as 16
+    /// bytes. See
+    /// [BucketState.to_bytes()](struct.BucketState.html#method.to_bytes)
+    /// for the layout and
+    /// [TokenBucket::from_bytes()](struct.TokenBucket.html#method.from_bytes)
+    /// for restoring it.
+    pub fn to_bytes(&self) -> [u8; BucketState::ENCODED_LEN] {
+        self.snapshot().to_bytes()
+    }
+
+    /// Returns the bucket's current status for debugging and
+    /// monitoring.
+    ///
+    /// Every value is computed from a single reading of the bucket's
+    /// clock, so unlike separate calls to the individual accessors the
+    /// values always agree with each other. Nothing is acquired.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(0.0, 100.0);
+    /// token_bucket.acquire(25.0).unwrap();
+    /// let status = token_bucket.status();
+    /// assert_eq!(status.tokens, 75.0);
+    /// assert_eq!(status.fill, 0.75);
+    /// ```
+    pub fn status(&self) -> BucketStatus {
+        let now = self.now().max(self.last);
+        let (tokens, _) = self.replenished(now);
+
+        BucketStatus {
+            rate: self.r,
+            burst: self.b,
+            tokens,
+            fill: if self.b > 0.0 { tokens / self.b } else { 0.0 },
+            idle: now.duration_since(self.last).unwrap_or(Duration::ZERO),
+        }
+    }
+
+    /// Returns how many tokens accrue at the bucket's rate over
+    /// `elapsed`, before clamping to the burst value.
+    ///
+    /// This is the core of the replenishment math,
+    /// `r * elapsed_seconds`, and has no side effects.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// # use std::time::Duration;
+    /// let token_bucket = TokenBucket::new(5.0, 100.0);
+    /// assert_eq!(token_bucket.tokens_accrued(Duration::from_secs(60)), 300.0);
+    /// ```
+    pub fn tokens_accrued(&self, elapsed: Duration) -> f64 {
+        self.r * elapsed.as_secs_f64()
+    }
+
+    /// Returns the number of tokens the bucket will hold at `when`,
+    /// according to the bucket's clock, assuming nothing is acquired
+    /// until then.
+    ///
+    /// The projection is clamped to the burst value, just like actual
+    /// replenishment, so sampling it over a range of times traces the
+    /// bucket's recovery curve. A `when` in the past returns the
+    /// current number of tokens. Nothing is acquired.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::{ManualClock, TokenBucket};
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+    /// let mut token_bucket = TokenBucket::builder(5.0, 100.0).clock(clock).build();
+    /// token_bucket.drain();
+    ///
+    /// let in_ten_seconds = SystemTime::UNIX_EPOCH + Duration::from_secs(10);
+    /// assert_eq!(token_bucket.tokens_at(in_ten_seconds), 50.0);
+    /// ```
+    pub fn tokens_at(&self, when: SystemTime) -> f64 {
+        let now = self.now().max(self.last);
+        self.replenished(when.max(now)).0
+    }
+
+    /// Reserves `count` tokens now for work scheduled to run at `when`,
+    /// according to the bucket's clock, on top of every earlier
+    /// reservation.
+    ///
+    /// The reservation is only accepted if the bucket, as projected by
+    /// [TokenBucket.tokens_at()](struct.TokenBucket.html#method.tokens_at),
+    /// can afford it at `when` and every other reservation can still be
+    /// afforded at its own time, i.e. if no reservation would ever
+    /// overdraw the bucket. Otherwise returns
+    /// `Err(TokenAcquisitionError::Overbooked { .. })`, or
+    /// `Err(TokenAcquisitionError::CostExceedsBurst { .. })` if no
+    /// bucket state could ever afford `count`. A `when` in the past
+    /// reserves for the current time.
+    ///
+    /// The bucket keeps a list of pending deductions ordered by time.
+    /// Once `when` arrives the deduction is applied as if `count`
+    /// tokens had been acquired at exactly that time, which every
+    /// method observing the bucket takes into account. Ordinary
+    /// acquisitions in the meantime don't honor the reservations: they
+    /// may take tokens a reservation was counting on, in which case
+    /// applying it leaves the bucket below empty until it recovers.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::{ManualClock, TokenAcquisitionError, TokenBucket};
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+    /// let mut token_bucket = TokenBucket::builder(1.0, 10.0).clock(clock.clone()).build();
+    /// token_bucket.drain();
+    ///
+    /// let in_five_seconds = SystemTime::UNIX_EPOCH + Duration::from_secs(5);
+    /// assert!(token_bucket.reserve_at(5.0, in_five_seconds).is_ok());
+    /// assert_eq!(
+    ///     token_bucket.reserve_at(1.0, in_five_seconds),
+    ///     Err(TokenAcquisitionError::Overbooked { count: 1.0 })
+    /// );
+    /// ```
+    pub fn reserve_at(&mut self, count: f64, when: SystemTime) -> Result<(), TokenAcquisitionError> {
+        if count + self.reserve > self.b {
+            return Err(TokenAcquisitionError::CostExceedsBurst {
+                count,
+                burst: self.b,
+            });
+        }
+
+        let now = self.now().max(self.last);
+        let when = when.max(now);
+        let index = self.scheduled.partition_point(|&(at, _)| at <= when);
+
+        // Replay every pending deduction with the new one in place.
+        let mut tokens = self.replenished(now).0;
+        let mut at = now;
+        let pending = self.scheduled[..index]
+            .iter()
+            .copied()
+            .filter(|&(at, _)| at > now)
+            .chain(std::iter::once((when, count)))
+            .chain(self.scheduled[index..].iter().copied());
+        for (due, cost) in pending {
+            tokens = self.accrue(tokens, at, due);
+            if tokens - self.reserve < cost {
+                return Err(TokenAcquisitionError::Overbooked { count });
+            }
+            tokens -= cost;
+            at = due;
+        }
+
+        self.scheduled.insert(index, (when, count));
+        Ok(())
+    }
+
+    /// Returns how long the caller would have to wait before `count`
+    /// tokens are available in the bucket.
+    ///
+    /// Any configured `reserve` is taken into account, so this is the
+    /// wait before an ordinary `acquire(count)` could succeed.
+    ///
+    /// Returns `Duration::ZERO` if the tokens are available right now.
+    /// Nothing is acquired, so another caller may still take the
+    /// tokens first.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// # use std::time::Duration;
+    /// let mut token_bucket = TokenBucket::new(5.0, 1.0);
+    /// assert_eq!(token_bucket.time_until_available(1.0), Duration::ZERO);
+    /// token_bucket.acquire(1.0).unwrap();
+    /// assert!(token_bucket.time_until_available(1.0) > Duration::ZERO);
+    /// ```
+    pub fn time_until_available(&self, count: f64) -> Duration {
+        let now = self.now().max(self.last);
+        let (tokens, _) = self.replenished(now);
+        if self.covers(tokens - self.reserve, count) {
+            return Duration::ZERO;
+        }
+
+        let needed = self.accrual_needed(count + self.reserve - tokens, now);
+        Duration::try_from_secs_f64(needed.max(0.0) / self.r)
+            .unwrap_or(Duration::MAX)
+    }
+
+    /// Returns the time, according to the bucket's clock, at which
+    /// `count` tokens will be available in the bucket.
+    ///
+    /// This is the absolute counterpart of
+    /// [TokenBucket.time_until_available()](struct.TokenBucket.html#method.time_until_available)
+    /// and returns the current time if the tokens are available right
+    /// now. Returns `None` if the bucket can never afford `count`
+    /// tokens, either because `count + reserve` is larger than the
+    /// burst value `self.b` or because the bucket doesn't replenish.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let token_bucket = TokenBucket::new(5.0, 100.0);
+    /// assert!(token_bucket.next_available_at(10.0).is_some());
+    /// assert!(token_bucket.next_available_at(200.0).is_none());
+    /// ```
+    pub fn next_available_at(&self, count: f64) -> Option<SystemTime> {
+        if !self.covers(self.max_burst() - self.reserve, count) {
+            return None;
+        }
+
+        let now = self.now().max(self.last);
+        now.checked_add(self.time_until_available(count))
+    }
+
+    /// Returns the long-run rate, in tokens per second, that the bucket
+    /// can sustain without rate limiting. This is the refill rate `r`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let token_bucket = TokenBucket::new(5.0, 100.0);
+    /// assert_eq!(token_bucket.sustainable_rate(), 5.0);
+    /// ```
+    pub fn sustainable_rate(&self) -> f64 {
+        self.r
+    }
+
+    /// Returns the moving average of the rate, in tokens per second, at
+    /// which tokens have actually been acquired from the bucket.
+    ///
+    /// Returns `None` unless the bucket was built with
+    /// [TokenBucketBuilder.observe_rate()](struct.TokenBucketBuilder.html#method.observe_rate).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// use std::time::Duration;
+    ///
+    /// let mut token_bucket = TokenBucket::builder(5.0, 100.0)
+    ///     .observe_rate(Duration::from_secs(10))
+    ///     .build();
+    /// token_bucket.acquire(50.0).unwrap();
+    /// assert!(token_bucket.observed_rate().unwrap() > 4.9);
+    /// assert_eq!(TokenBucket::new(5.0, 100.0).observed_rate(), None);
+    /// ```
+    pub fn observed_rate(&self) -> Option<f64> {
+        let now = self.now().max(self.last);
+        self.observed.map(|observed| observed.rate_at(now))
+    }
+
+    /// Returns whether the observed acquisition rate exceeds the
+    /// configured rate `r` by more than a factor of `tolerance`.
+    ///
+    /// A token bucket can't sustain more than `r` tokens per second,
+    /// so a bucket that keeps diverging isn't actually constraining its
+    /// callers, e.g. because its clock is broken or its burst value is
+    /// too large for the averaging window. Use this as a
+    /// self-diagnostic. Always returns `false` unless the rate is
+    /// observed, see
+    /// [observed_rate()](#method.observed_rate).
+    pub fn rate_diverges(&self, tolerance: f64) -> bool {
+        self.observed_rate()
+            .is_some_and(|observed| observed > self.r * tolerance)
+    }
+
+    /// Returns whether sustaining `requested_rate` acquisitions of one
+    /// token per second would eventually be throttled.
+    ///
+    /// This is the case exactly when `requested_rate` exceeds the
+    /// sustainable rate `r`. The burst value only affects *when* an
+    /// over-rate caller is throttled, not *whether* it is: a full
+    /// bucket merely delays the inevitable. See
+    /// [estimate_completion_time()](#method.estimate_completion_time)
+    /// for when.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let token_bucket = TokenBucket::new(5.0, 1_000_000.0);
+    /// assert!(!token_bucket.would_throttle_at(5.0));
+    /// assert!(token_bucket.would_throttle_at(5.1));
+    /// ```
+    pub fn would_throttle_at(&self, requested_rate: f64) -> bool {
+        requested_rate > self.r
+    }
+
+    /// Estimates how long it would take to acquire `count` tokens when
+    /// requesting them at `target_rate` tokens per second, starting
+    /// from the current state of the bucket.
+    ///
+    /// While the bucket holds tokens the job proceeds at `target_rate`.
+    /// If `target_rate` exceeds the sustainable rate `r`, the bucket
+    /// drains and the rest of the job is throttled to `r`. Both cases
+    /// are covered by:
+    ///
+    /// ```ignore
+    /// time = max { count / target_rate, (count - tokens) / r }
+    /// ```
+    ///
+    /// where `tokens` is the number of tokens currently available to
+    /// ordinary acquisitions. Nothing is acquired.
+    ///
+    /// # Example
+    ///
+    /// With `r = 5` and a full bucket of `b = 100`:
+    ///
+    /// * 100 tokens at 60/s fit in the burst and take `100 / 60 ≈ 1.67s`.
+    /// * 200 tokens at 60/s drain the burst, and the remaining 100
+    ///   tokens are throttled to 5/s, taking `(200 - 100) / 5 = 20s`.
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// # use std::time::Duration;
+    /// let token_bucket = TokenBucket::new(5.0, 100.0);
+    /// let fits = token_bucket.estimate_completion_time(100.0, 60.0);
+    /// assert!(fits < Duration::from_secs(2));
+    /// let throttled = token_bucket.estimate_completion_time(200.0, 60.0);
+    /// assert_eq!(throttled, Duration::from_secs(20));
+    /// ```
+    pub fn estimate_completion_time(&self, count: f64, target_rate: f64) -> Duration {
+        let (tokens, _) = self.replenished(self.now());
+        let available = tokens - self.reserve;
+
+        let unthrottled = count / target_rate;
+        let throttled = (count - available) / self.r;
+
+        Duration::try_from_secs_f64(unthrottled.max(throttled))
+            .unwrap_or(Duration::MAX)
+    }
+
+    /// Acquires `count` tokens from the bucket, blocking the current
+    /// thread until enough tokens are available.
+    ///
+    /// Returns `Ok(rate)` once the tokens have been acquired. Returns
+    /// an error without blocking if the acquisition can never succeed,
+    /// either because `count` is larger than the configured `max_cost`
+    /// or because it is larger than the burst value `self.b`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(100.0, 1.0);
+    /// token_bucket.acquire(1.0).unwrap();
+    /// // Blocks for roughly 10ms until the next token is available.
+    /// token_bucket.acquire_wait(1.0).unwrap();
+    /// ```
+    pub fn acquire_wait(&mut self, count: f64) -> TokenAcquisitionResult {
+        #[cfg(feature = "histogram")]
+        let start = Instant::now();
+        let result = loop {
+            match self.acquire_blocking_step(count) {
+                Ok(result) => break result,
+                Err(wait) => thread::sleep(wait),
+            }
+        };
+
+        #[cfg(feature = "histogram")]
+        if result.is_ok() {
+            self.waits.record(start.elapsed());
+        }
+        result
+    }
+
+    /// Returns the 50th, 95th and 99th percentile of the time the
+    /// bucket's blocking acquisitions waited, e.g. for reporting how
+    /// much latency the limiter adds against an SLO.
+    ///
+    /// Every acquisition made with
+    /// [acquire_wait()](#method.acquire_wait),
+    /// [acquire_wait_precise()](#method.acquire_wait_precise) or
+    /// [acquire_wait_granted()](#method.acquire_wait_granted) is
+    /// counted once it succeeds, including those that didn't have to
+    /// wait. Waits are kept in a histogram with microsecond resolution
+    /// whose buckets are at most 1/16th of their value wide, so the
+    /// percentiles are rounded up by at most about 6%. Returns zeros
+    /// until the first blocking acquisition. Only available with the
+    /// `histogram` feature.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// use std::time::Duration;
+    ///
+    /// let mut token_bucket = TokenBucket::new(100.0, 1.0);
+    /// token_bucket.acquire(1.0).unwrap();
+    /// token_bucket.acquire_wait(1.0).unwrap();
+    /// let [p50, _, p99] = token_bucket.wait_percentiles();
+    /// assert!(p50 >= Duration::from_millis(9) && p99 == p50);
+    /// ```
+    #[cfg(feature = "histogram")]
+    pub fn wait_percentiles(&self) -> [Duration; 3] {
+        [
+            self.waits.percentile(0.5),
+            self.waits.percentile(0.95),
+            self.waits.percentile(0.99),
+        ]
+    }
+
+    /// Acquires `count` tokens from the bucket like
+    /// [acquire_wait()](#method.acquire_wait), but busy-waits for the
+    /// last `spin` of the wait instead of sleeping through it, e.g. for
+    /// pacing media packets at sub-millisecond intervals.
+    ///
+    /// Replenishment and the computed wait are always accurate to the
+    /// nanosecond, but `thread::sleep` only guarantees to sleep at least
+    /// as long as asked, and usually oversleeps by tens of microseconds
+    /// on Linux and by up to a scheduler tick, about 1-15ms, elsewhere.
+    /// Sleeping until `spin` before the tokens are due and spinning on
+    /// the clock from there hides that oversleep as long as it stays
+    /// below `spin`, at the cost of keeping a core busy meanwhile.
+    ///
+    /// # Precision
+    ///
+    /// With `spin` above the typical oversleep, e.g. 200µs on Linux,
+    /// acquisitions complete within a few microseconds of the tokens
+    /// becoming available, bounded by the resolution of the clock read
+    /// and by the thread being preempted while spinning. A `spin` of
+    /// zero behaves like `acquire_wait()`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// // One packet every 100µs.
+    /// let mut pacer = TokenBucket::new(10_000.0, 1.0);
+    /// let start = Instant::now();
+    /// for _ in 0..=100 {
+    ///     pacer.acquire_wait_precise(1.0, Duration::from_micros(200)).unwrap();
+    /// }
+    /// assert!(start.elapsed() >= Duration::from_millis(10));
+    /// ```
+    pub fn acquire_wait_precise(&mut self, count: f64, spin: Duration) -> TokenAcquisitionResult {
+        #[cfg(feature = "histogram")]
+        let start = Instant::now();
+        loop {
+            let wait = match self.acquire_blocking_step(count) {
+                #[cfg(feature = "histogram")]
+                Ok(Ok(rate)) => {
+                    self.waits.record(start.elapsed());
+                    return Ok(rate);
+                },
+                Ok(result) => return result,
+                Err(wait) => wait,
+            };
+            if wait > spin {
+                thread::sleep(wait - spin);
+            }
+            // Spin on the clock alone, so only the final attempt counts
+            // as another acquisition. The spin is bounded by the wait,
+            // as tokens that stop accruing, e.g. past
+            // `max_idle_accrual`, only accrue again after an attempt.
+            let until = Instant::now() + wait.min(spin);
+            while !self.time_until_available(count).is_zero() && Instant::now() < until {
+                std::hint::spin_loop();
+            }
+        }
+    }
+
+    /// Acquires `count` tokens from the bucket like
+    /// [TokenBucket.acquire_wait()](struct.TokenBucket.html#method.acquire_wait),
+    /// but only waits if the tokens become available by `deadline`.
+    ///
+    /// If they won't, e.g. because the caller's request is about to
+    /// time out, the error of the failed attempt is returned right
+    /// away instead of sleeping in vain. The wait is estimated from the
+    /// bucket's clock and measured against `deadline` on the monotonic
+    /// clock.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let mut token_bucket = TokenBucket::new(1.0, 1.0);
+    /// token_bucket.acquire(1.0).unwrap();
+    /// // The next token is a second away.
+    /// let deadline = Instant::now() + Duration::from_millis(50);
+    /// assert!(token_bucket.acquire_with_deadline(1.0, deadline).is_err());
+    /// ```
+    pub fn acquire_with_deadline(&mut self, count: f64, deadline: Instant) -> TokenAcquisitionResult {
+        loop {
+            match self.acquire(count) {
+                Err(err @ TokenAcquisitionError::RateLimited { .. }) if count + self.reserve <= self.max_burst() => {
+                    let wait = self.time_until_available(count);
+                    if Instant::now().checked_add(wait).is_none_or(|ready| ready > deadline) {
+                        return Err(err);
+                    }
+                    thread::sleep(wait);
+                },
+                result => return result,
+            }
+        }
+    }
+
+    /// Acquires `count` tokens from the bucket like
+    /// [TokenBucket.acquire_wait()](struct.TokenBucket.html#method.acquire_wait),
+    /// but gives up as soon as `cancel` is set.
+    ///
+    /// The flag is checked before every attempt and at least every
+    /// 10 milliseconds while sleeping, so setting it from another
+    /// thread, e.g. during a graceful shutdown, releases the waiting
+    /// thread promptly. A cancelled acquisition returns
+    /// `Err(TokenAcquisitionError::Cancelled)` and consumes no tokens.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::{TokenAcquisitionError, TokenBucket};
+    /// use std::sync::atomic::AtomicBool;
+    ///
+    /// let mut token_bucket = TokenBucket::new(5.0, 100.0);
+    /// let shutdown = AtomicBool::new(true);
+    /// assert_eq!(
+    ///     token_bucket.acquire_wait_cancellable(1.0, &shutdown),
+    ///     Err(TokenAcquisitionError::Cancelled)
+    /// );
+    /// ```
+    pub fn acquire_wait_cancellable(&mut self, count: f64, cancel: &AtomicBool) -> TokenAcquisitionResult {
+        const CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+        loop {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(TokenAcquisitionError::Cancelled);
+            }
+
+            let mut wait = match self.acquire_blocking_step(count) {
+                Ok(result) => return result,
+                Err(wait) => wait,
+            };
+            while wait > CHECK_INTERVAL && !cancel.load(Ordering::Relaxed) {
+                thread::sleep(CHECK_INTERVAL);
+                wait -= CHECK_INTERVAL;
+            }
+            if !cancel.load(Ordering::Relaxed) {
+                thread::sleep(wait);
+            }
+        }
+    }
+
+    /// Acquires `count` tokens from the bucket like
+    /// [TokenBucket.acquire_wait()](struct.TokenBucket.html#method.acquire_wait),
+    /// and reports how long it waited and how many tokens were left at
+    /// the moment of the grant.
+    ///
+    /// Both values are taken from the bucket's clock at the point the
+    /// acquisition succeeded, so they are consistent with each other
+    /// and need no separate observations. Returns an error without
+    /// blocking if the acquisition can never succeed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(100.0, 1.0);
+    /// token_bucket.acquire(1.0).unwrap();
+    /// let grant = token_bucket.acquire_wait_granted(1.0).unwrap();
+    /// println!("waited {:?}, {} tokens left", grant.waited, grant.tokens);
+    /// ```
+    pub fn acquire_wait_granted(&mut self, count: f64) -> Result<Grant, TokenAcquisitionError> {
+        if count + self.reserve > self.max_burst() {
+            return Err(TokenAcquisitionError::CostExceedsBurst {
+                count,
+                burst: self.max_burst(),
+            });
+        }
+
+        let start = self.now().max(self.last);
+        let mut now = start;
+        loop {
+            match self.acquire_above(count, self.reserve, now) {
+                (Ok(rate), remaining) => {
+                    // Not every grant moves `self.last`, e.g. one made
+                    // in shadow mode, so measure up to the clock read
+                    // it was made at.
+                    let waited = now.duration_since(start).unwrap_or(Duration::ZERO);
+                    #[cfg(feature = "histogram")]
+                    self.waits.record(waited);
+                    return Ok(Grant {
+                        rate,
+                        waited,
+                        tokens: self.whole(remaining),
+                    });
+                },
+                (Err(TokenAcquisitionError::RateLimited { .. }), _) => {
+                    thread::sleep(self.time_until_available(count));
+                    now = self.now();
+                },
+                (Err(err), _) => return Err(err),
+            }
+        }
+    }
+
+    /// Acquires `count` tokens from the bucket, asynchronously waiting
+    /// until enough tokens are available.
+    ///
+    /// This behaves like
+    /// [TokenBucket.acquire_wait()](struct.TokenBucket.html#method.acquire_wait),
+    /// except that instead of blocking the thread it awaits the future
+    /// returned by `sleep`. `sleep` is the injection point for the async
+    /// runtime: it is called with the duration to wait and must return
+    /// a future that completes once that duration has elapsed. This
+    /// keeps the crate independent of any particular runtime, e.g.:
+    ///
+    /// * Tokio: `tokio::time::sleep`
+    /// * async-std: `async_std::task::sleep`
+    /// * smol: `|d| async move { smol::Timer::after(d).await; }`
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(100.0, 1.0);
+    /// token_bucket.acquire_async(1.0, tokio::time::sleep).await?;
+    /// ```
+    pub async fn acquire_async<F, Fut>(
+        &mut self,
+        count: f64,
+        sleep: F,
+    ) -> TokenAcquisitionResult
+    where
+        F: Fn(Duration) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        loop {
+            match self.acquire_blocking_step(count) {
+                Ok(result) => return result,
+                Err(wait) => sleep(wait).await,
+            }
+        }
+    }
+
+    /// Attempts to acquire `count` tokens from the bucket, retrying
+    /// rate limited attempts as configured by `policy` and blocking the
+    /// current thread between attempts.
+    ///
+    /// Returns `Ok(rate)` as soon as an attempt succeeds. Returns the
+    /// error of the last attempt once `policy` runs out of attempts,
+    /// and returns immediately without retrying if the acquisition can
+    /// never succeed, either because `count` is larger than the
+    /// configured `max_cost` or because it is larger than the burst
+    /// value `self.b`. Unlike
+    /// [TokenBucket.acquire_wait()](struct.TokenBucket.html#method.acquire_wait),
+    /// this bounds how long the caller may be held up.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::{RetryPolicy, TokenBucket};
+    /// let policy = RetryPolicy::new(3);
+    /// let mut token_bucket = TokenBucket::new(100.0, 1.0);
+    /// token_bucket.acquire(1.0).unwrap();
+    /// // Waits roughly 10ms for the next token, then acquires it.
+    /// assert!(token_bucket.acquire_with_retry(1.0, &policy).is_ok());
+    /// ```
+    pub fn acquire_with_retry(
+        &mut self,
+        count: f64,
+        policy: &RetryPolicy,
+    ) -> TokenAcquisitionResult {
+        if count + self.reserve > self.max_burst() {
+            return Err(TokenAcquisitionError::CostExceedsBurst {
+                count,
+                burst: self.max_burst(),
+            });
+        }
+
+        let mut retry = 0;
+        loop {
+            match self.acquire(count) {
+                Err(TokenAcquisitionError::RateLimited { .. })
+                    if retry + 1 < policy.max_attempts() =>
+                {
+                    thread::sleep(policy.delay(retry, self.time_until_available(count)));
+                    retry += 1;
+                },
+                result => return result,
+            }
+        }
+    }
+
+    /// Attempts to acquire `count` tokens from the bucket as part of a
+    /// poll-based state machine, e.g. a hand-written future or
+    /// `std::future::poll_fn`.
+    ///
+    /// Returns `Poll::Ready` with the result if the acquisition
+    /// succeeded or can never succeed, just like
+    /// [TokenBucket.acquire_wait()](struct.TokenBucket.html#method.acquire_wait).
+    /// Otherwise returns `Poll::Pending` and arranges for the waker of
+    /// `cx` to be woken at the time reported by
+    /// [TokenBucket.next_available_at()](struct.TokenBucket.html#method.next_available_at),
+    /// at which point the task should poll again.
+    ///
+    /// The wake-up comes from a timer on a background thread. Polling
+    /// again before it fires doesn't start another timer as long as
+    /// the existing one fires early enough; it only swaps in the most
+    /// recent waker, as the `Future` contract requires. A bucket that
+    /// never replenishes never wakes the task.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// use std::future::poll_fn;
+    ///
+    /// # fn block_on(_: impl std::future::Future) {}
+    /// let mut token_bucket = TokenBucket::new(100.0, 1.0);
+    /// let acquire = poll_fn(|cx| token_bucket.poll_acquire(cx, 1.0));
+    /// # block_on(acquire);
+    /// ```
+    pub fn poll_acquire(&mut self, cx: &mut Context<'_>, count: f64) -> Poll<TokenAcquisitionResult> {
+        let wait = match self.acquire_blocking_step(count) {
+            Ok(result) => return Poll::Ready(result),
+            Err(wait) => wait,
+        };
+
+        if let Some(at) = self.next_available_at(count) {
+            let rearmed = self.timer.as_ref().is_some_and(|timer| timer.rearm(at, cx.waker()));
+            if !rearmed {
+                self.timer = Some(timer::Timer::start(at, wait, cx.waker()));
+            }
+        }
+        Poll::Pending
+    }
+
+    /// Attempts to acquire `count` tokens from the bucket, and if it is
+    /// rate limited, waits for the next refill tick and attempts once
+    /// more.
+    ///
+    /// A tick is the moment the bucket next holds another whole token,
+    /// i.e. every `1 / r` seconds while it is being drained. Refills are
+    /// discrete with
+    /// [RoundingMode::Floor](enum.RoundingMode.html#variant.Floor),
+    /// which only credits tokens once they have fully accrued, so the
+    /// ticks are the only moments new tokens become available and
+    /// aligning the retry with them wastes no attempts mid-interval.
+    /// Unlike
+    /// [TokenBucket.acquire_wait()](struct.TokenBucket.html#method.acquire_wait)
+    /// this blocks at most one tick and then returns the result of the
+    /// second attempt, whether it succeeded or not. A bucket that
+    /// doesn't replenish returns the first denial without blocking.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// # use tokenbucket::RoundingMode;
+    /// let mut token_bucket = TokenBucket::builder(100.0, 1.0).rounding(RoundingMode::Floor).build();
+    /// token_bucket.acquire(1.0).unwrap();
+    /// assert!(token_bucket.acquire_until_next_tick(1.0).is_ok());
+    /// ```
+    pub fn acquire_until_next_tick(&mut self, count: f64) -> TokenAcquisitionResult {
+        if self.r <= 0.0 {
+            return self.acquire(count);
+        }
+        if let Ok(result) = self.acquire_blocking_step(count) {
+            return result;
+        }
+
+        thread::sleep(self.next_tick());
+        self.acquire(count)
+    }
+
+    // Returns how long until the bucket holds another whole token, see
+    // `acquire_until_next_tick`.
+    fn next_tick(&self) -> Duration {
+        let now = self.now().max(self.last);
+        let (tokens, _) = self.replenished(now);
+        let needed = self.accrual_needed(tokens.floor() + 1.0 - tokens, now);
+        Duration::try_from_secs_f64(needed.max(0.0) / self.r)
+            .unwrap_or(Duration::MAX)
+    }
+
+    /// Runs `f` once `cost` tokens have been acquired from the bucket,
+    /// blocking the current thread until then, and returns its result.
+    ///
+    /// This turns the bucket into a simple rate-limited executor for
+    /// one-off calls. See
+    /// [TokenBucket.acquire_wait()](struct.TokenBucket.html#method.acquire_wait).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cost` can never be acquired from the bucket, i.e. if
+    /// it is larger than the configured `max_cost` or than `self.b`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(50.0, 1.0);
+    /// for i in 0..3 {
+    ///     // Prints at most 50 lines per second.
+    ///     token_bucket.throttle(1.0, || println!("line {}", i));
+    /// }
+    /// ```
+    pub fn throttle<R>(&mut self, cost: f64, f: impl FnOnce() -> R) -> R {
+        if let Err(err) = self.acquire_wait(cost) {
+            panic!("throttle cost can never be acquired: {:?}", err);
+        }
+
+        f()
+    }
+
+    /// Returns an iterator that, on every step, advances `clock` by
+    /// `interval`, attempts to acquire `cost` tokens and yields whether
+    /// the acquisition was allowed.
+    ///
+    /// `clock` must be the clock the bucket was built with, see
+    /// [TokenBucketBuilder.clock()](struct.TokenBucketBuilder.html#method.clock).
+    /// Nothing sleeps, so this characterizes the allow/deny pattern of
+    /// a bucket at any cadence instantly, e.g. in tests and
+    /// simulations.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::{ManualClock, TokenBucket};
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+    /// let mut tb = TokenBucket::builder(2.0, 2.0).clock(clock.clone()).build();
+    /// // Acquiring 4 times per second at 2 tokens per second.
+    /// let pattern: Vec<bool> = tb.ticks(&clock, Duration::from_millis(250), 1.0).take(6).collect();
+    /// assert_eq!(pattern, [true, true, true, false, true, false]);
+    /// ```
+    pub fn ticks<'a>(
+        &'a mut self,
+        clock: &ManualClock,
+        interval: Duration,
+        cost: f64,
+    ) -> Ticks<'a> {
+        Ticks::new(self, clock, interval, cost)
+    }
+
+    /// Registers a callback that is invoked whenever tokens accrued
+    /// over time are added to the bucket, receiving the number of
+    /// tokens that were added.
+    ///
+    /// Accrued tokens are added during a successful acquisition or a
+    /// call to [TokenBucket.drain()](struct.TokenBucket.html#method.drain);
+    /// the callback is not invoked if no tokens accrued. This is a hook
+    /// for building, e.g., waiter queues on top of the bucket. When no
+    /// callback is registered the refill step does no extra work.
+    ///
+    /// Registering a callback replaces any previously registered one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(5.0, 100.0);
+    /// token_bucket.on_replenish(|amount| println!("replenished {}", amount));
+    /// ```
+    pub fn on_replenish<F>(&mut self, callback: F)
+    where
+        F: FnMut(f64) + Send + 'static,
+    {
+        self.on_replenish = Some(Box::new(callback));
+    }
+
+    /// Registers a callback that is invoked only when an acquisition is
+    /// denied for lack of tokens, receiving the requested count and the
+    /// number of tokens that were available.
+    ///
+    /// This targets exactly the interesting events when debugging
+    /// throttling, without logging every acquisition. Acquisitions
+    /// rejected as invalid, e.g. for exceeding `max_cost`, don't invoke
+    /// it. When no callback is registered the denial path does no extra
+    /// work.
+    ///
+    /// Registering a callback replaces any previously registered one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let mut token_bucket = TokenBucket::new(5.0, 100.0);
+    /// token_bucket.on_denied(|count, tokens| {
+    ///     eprintln!("denied {} tokens, {} available", count, tokens)
+    /// });
+    /// ```
+    pub fn on_denied<F>(&mut self, callback: F)
+    where
+        F: FnMut(f64, f64) + Send + 'static,
+    {
+        self.on_denied = Some(Box::new(callback));
+    }
+
+    // Invokes the `on_replenish` callback, if one is registered, with
+    // the difference between `tokens` and the tokens currently stored
+    // in the bucket, and accounts for the tokens accrued until `now`
+    // that didn't fit. Must be called before `self.tokens` and
+    // `self.last` are updated.
+    fn notify_replenish(&mut self, tokens: f64, now: SystemTime) {
+        // Scheduled deductions due by `now` were already taken out of
+        // `tokens`, so they count neither as overflow nor against the
+        // tokens added.
+        let due: f64 = self.scheduled
+            .iter()
+            .take_while(|&&(when, _)| when <= now)
+            .map(|&(_, count)| count)
+            .sum();
+
+        // The time since the epoch a lazily clocked bucket starts with
+        // never really passed, so nothing overflowed during it.
+        let elapsed = self.accrual_end(now).duration_since(self.last).unwrap_or(Duration::ZERO);
+        let unclamped = self.tokens + self.accrued(elapsed) - due;
+        if self.lazy {
+            self.lazy = false;
+        } else if unclamped > tokens {
+            self.overflowed += unclamped - tokens;
+        }
+
+        // Rounded refills carry what they didn't credit over to the
+        // next one, unless the bucket filled up and it overflowed.
+        self.remainder = if unclamped > tokens { 0.0 } else { self.uncredited(elapsed) };
+
+        if let Some(callback) = self.on_replenish.as_mut() {
+            let added = tokens + due - self.tokens;
+            if added > 0.0 {
+                callback(added);
+            }
+        }
+    }
+
+    // Performs a single step of a blocking acquisition. Returns the
+    // final result if the acquisition succeeded or can never succeed,
+    // otherwise returns how long to sleep before trying again.
+    pub(crate) fn acquire_blocking_step(
+        &mut self,
+        count: f64,
+    ) -> Result<TokenAcquisitionResult, Duration> {
+        if count + self.reserve > self.max_burst() {
+            return Ok(Err(TokenAcquisitionError::CostExceedsBurst {
+                count,
+                burst: self.max_burst(),
+            }));
+        }
+
+        match self.acquire(count) {
+            Err(TokenAcquisitionError::RateLimited { .. }) => {
+                Err(self.time_until_available(count))
+            },
+            result => Ok(result),
+        }
+    }
+
+    // Reports the outcome of an acquisition that left `tokens` in the
+    // bucket to the registered metrics, if any.
+    #[cfg(any(feature = "prometheus", feature = "opentelemetry", feature = "metrics"))]
+    fn record_metrics(&self, allowed: bool, tokens: f64) {
+        #[cfg(feature = "prometheus")]
+        if let Some(metrics) = &self.metrics {
+            metrics.record(allowed, tokens, self.b);
+        }
+        #[cfg(feature = "opentelemetry")]
+        if let Some(meter) = &self.meter {
+            otel::record(meter.as_ref(), allowed, tokens, self.b);
+        }
+        #[cfg(feature = "metrics")]
+        if let Some(names) = &self.metric_names {
+            facade::record(names, allowed, tokens, self.b);
+        }
+    }
+
+    // Commits a successful acquisition of `count` out of the `tokens`
+    // available at `now`.
+    #[inline(always)]
+    fn grant(&mut self, count: f64, tokens: f64, now: SystemTime) {
+        self.notify_replenish(tokens, now);
+        self.tokens = self.clamp_dust(tokens - count);
+        self.last = now;
+        self.forget_due(now);
+        self.scarce_since = None;
+        if self.tokens >= self.b / 2.0 {
+            self.adapt_burst(true);
+        }
+        if let Some(observed) = self.observed.as_mut() {
+            observed.record(count, now);
+        }
+        #[cfg(any(feature = "prometheus", feature = "opentelemetry", feature = "metrics"))]
+        self.record_metrics(true, self.tokens);
+    }
+
+    // Grants `count` tokens at `now` without the full acquisition
+    // machinery, if the tokens stored in the bucket already cover them
+    // before replenishing and no optional setting needs to observe the
+    // acquisition. Returns `None` to defer to `acquire_above`
+    // otherwise, which then reaches the same decision the long way.
+    #[inline(always)]
+    fn acquire_plentiful(&mut self, count: f64, now: SystemTime) -> Option<f64> {
+        if !self.covers(self.tokens - self.reserve, count) || !self.is_plain() {
+            return None;
+        }
+        if self.max_cost.is_some_and(|max_cost| count > max_cost) {
+            return None;
+        }
+
+        let elapsed = now.duration_since(self.last).ok()?;
+        let unclamped = self.tokens + self.tokens_accrued(elapsed);
+        if unclamped > self.b {
+            self.overflowed += unclamped - self.b;
+        }
+
+        self.tokens = self.clamp_dust(unclamped.min(self.b) - count);
+        self.last = now;
+        self.scarce_since = None;
+        Some((1f64 / elapsed.as_nanos() as f64) * 1_000_000_000.0)
+    }
+
+    // Returns the error denying an acquisition at `now` for lack of
+    // tokens, `duration_ns` after the last one, without recording the
+    // denial.
+    fn rate_limited(&self, now: SystemTime, duration_ns: u128) -> TokenAcquisitionError {
+        let rate: f64 = (1f64 / duration_ns as f64) * 1_000_000_000.0;
+        let since = self.scarce_since.unwrap_or(now);
+        TokenAcquisitionError::RateLimited {
+            rate,
+            scarce_for: now.duration_since(since).unwrap_or(Duration::ZERO),
+        }
+    }
+
+    // Returns a copy of the bucket's state and configuration that reads
+    // the time from `clock`. Callbacks, metrics and any pending timer
+    // are not part of the copy.
+    fn detached(&self, clock: Arc<dyn Clock>) -> TokenBucket {
+        TokenBucket {
+            tokens: self.tokens,
+            last: self.last,
+            max_cost: self.max_cost,
+            reserve: self.reserve,
+            clock: Some(clock),
+            scarce_since: self.scarce_since,
+            integer_only: self.integer_only,
+            adaptive: self.adaptive,
+            penalty: self.penalty,
+            observed: self.observed,
+            overflowed: self.overflowed,
+            scheduled: self.scheduled.clone(),
+            lazy: self.lazy,
+            idle_cap: self.idle_cap,
+            denied_at: self.denied_at,
+            forfeited: self.forfeited,
+            shadow: self.shadow,
+            shadow_denials: self.shadow_denials,
+            epsilon: self.epsilon,
+            rounding: self.rounding,
+            remainder: self.remainder,
+            grace: self.grace,
+            grace_left: self.grace_left,
+            admission: self.admission.clone(),
+            #[cfg(feature = "metrics")]
+            metric_names: None,
+            ..TokenBucket::const_new(self.r, self.b)
+        }
+    }
+
+    // Returns whether no optional setting needs to observe a successful
+    // acquisition, see `acquire_plentiful`.
+    #[inline(always)]
+    fn is_plain(&self) -> bool {
+        let plain = !self.integer_only
+            && self.rounding == RoundingMode::Fractional
+            && !self.lazy
+            && self.on_replenish.is_none()
+            && self.adaptive.is_none()
+            && self.observed.is_none()
+            && self.idle_cap.is_none()
+            && self.grace_left == 0
+            && self.admission.is_none()
+            && self.scheduled.is_empty();
+        #[cfg(feature = "prometheus")]
+        let plain = plain && self.metrics.is_none();
+        #[cfg(feature = "opentelemetry")]
+        let plain = plain && self.meter.is_none();
+        #[cfg(feature = "metrics")]
+        let plain = plain && (self.metric_names.is_none() || !facade::installed());
+        plain
+    }
+
+    // Removes the configured penalty from a bucket that just denied an
+    // acquisition with `tokens` available, without taking it further
+    // than the maximum debt below empty.
+    fn penalize(&mut self, tokens: f64) {
+        if let Some((penalty, max_debt)) = self.penalty {
+            let removed = (tokens - (tokens - penalty).max(-max_debt)).max(0.0);
+            self.tokens -= removed;
+        }
+    }
+
+    // Returns the largest burst value the bucket may have, which is
+    // `max_b` if the burst value is adaptive.
+    fn max_burst(&self) -> f64 {
+        self.adaptive.map_or(self.b, |(_, max_b)| max_b)
+    }
+
+    // Grows `b` after a well-behaved acquisition or shrinks it after a
+    // denial, if the burst value is adaptive.
+    fn adapt_burst(&mut self, well_behaved: bool) {
+        let (min_b, max_b) = match self.adaptive {
+            Some(bounds) => bounds,
+            None => return,
+        };
+
+        if well_behaved {
+            self.b = max_b.min(self.b + (max_b - min_b) * 0.05);
+        } else {
+            self.b = min_b + (self.b - min_b) / 2.0;
+            self.tokens = self.tokens.min(self.b);
+        }
+    }
+
+    // Returns whether `tokens` cover an acquisition of `count`,
+    // allowing for the configured rounding tolerance.
+    #[inline(always)]
+    fn covers(&self, tokens: f64, count: f64) -> bool {
+        tokens + self.epsilon >= count
+    }
+
+    // Returns `tokens`, rounding a shortfall within the tolerance below
+    // empty up to zero, so a covered acquisition never leaves the
+    // bucket in debt.
+    #[inline(always)]
+    fn clamp_dust(&self, tokens: f64) -> f64 {
+        if tokens < 0.0 && tokens >= -self.epsilon {
+            0.0
+        } else {
+            tokens
+        }
+    }
+
+    // Returns the number of tokens out of `tokens` that the bucket may
+    // hand out, which is only whole tokens in integer-only mode.
+    fn whole(&self, tokens: f64) -> f64 {
+        if self.integer_only {
+            tokens.floor()
+        } else {
+            tokens
+        }
+    }
+
+    // Returns the current time according to the configured clock.
+    fn now(&self) -> SystemTime {
+        match &self.clock {
+            Some(clock) => clock.now(),
+            None => SystemTime::now(),
+        }
+    }
+
+    // Returns the number of tokens that would be in the bucket at
+    // `now`, accounting for the tokens accrued since `self.last` and
+    // clamped to the burst value, along with the elapsed time in
+    // nanoseconds. The bucket itself is left untouched.
+    //
+    // Nanosecond precision keeps replenishment smooth at high rates,
+    // where whole milliseconds would credit tokens in lumps. A `now`
+    // earlier than `self.last` counts as no time having passed.
+    //
+    // Scheduled deductions that are due by `now` are applied at their
+    // own time along the way, so that the clamping in between matches
+    // what would have happened had they been acquired on time.
+    fn replenished(&self, now: SystemTime) -> (f64, u128) {
+        let elapsed = now.duration_since(self.last)
+                         .unwrap_or(Duration::ZERO);
+        let end = self.accrual_end(now);
+
+        if self.scheduled.is_empty() {
+            let accruing = end.duration_since(self.last).unwrap_or(Duration::ZERO);
+            let tokens = self.b.min(self.tokens + self.accrued(accruing));
+            return (tokens, elapsed.as_nanos());
+        }
+
+        let mut tokens = self.tokens;
+        let mut at = self.last;
+        for &(when, count) in self.scheduled.iter().take_while(|&&(when, _)| when <= now) {
+            tokens = self.accrue(tokens, at.min(end), when.min(end)) - count;
+            at = at.max(when);
+        }
+        (self.accrue(tokens, at.min(end), end), elapsed.as_nanos())
+    }
+
+    // Returns the time up to which tokens accrue when replenishing up
+    // to `now`. This is `now`, unless an idle cap cut accrual short
+    // during long stretches without acquisition attempts, in which case
+    // the time that was cut is taken off.
+    #[inline(always)]
+    fn accrual_end(&self, now: SystemTime) -> SystemTime {
+        if self.idle_cap.is_none() {
+            return now;
+        }
+
+        let cut = self.forfeited_time() + self.idle_excess(now);
+        now.checked_sub(cut).unwrap_or(self.last).max(self.last)
+    }
+
+    // Ends the current idle stretch with an acquisition attempt at
+    // `now`, remembering the time cut from it.
+    fn end_idle(&mut self, now: SystemTime) {
+        let cut = self.forfeited_time() + self.idle_excess(now);
+        self.forfeited = (self.last, cut);
+        self.denied_at = now;
+    }
+
+    // Returns the time cut from earlier idle stretches since the last
+    // acquisition.
+    fn forfeited_time(&self) -> Duration {
+        match self.forfeited {
+            (last, cut) if last == self.last => cut,
+            _ => Duration::ZERO,
+        }
+    }
+
+    // Returns how far the current idle stretch, up to `now`, exceeds
+    // the idle cap.
+    fn idle_excess(&self, now: SystemTime) -> Duration {
+        let cap = self.idle_cap.unwrap_or(Duration::MAX);
+        let from = self.denied_at.max(self.last);
+        now.duration_since(from).unwrap_or(Duration::ZERO).saturating_sub(cap)
+    }
+
+    // Returns `tokens` after accruing from `from` to `to`, clamped to
+    // the burst value.
+    fn accrue(&self, tokens: f64, from: SystemTime, to: SystemTime) -> f64 {
+        if self.rounding == RoundingMode::Fractional {
+            let elapsed = to.duration_since(from).unwrap_or(Duration::ZERO);
+            return self.b.min(tokens + self.tokens_accrued(elapsed));
+        }
+
+        // Round the accrual since `self.last` as a whole, rather than
+        // every stretch between scheduled deductions on its own.
+        let since = |at: SystemTime| at.duration_since(self.last).unwrap_or(Duration::ZERO);
+        self.b.min(tokens + self.accrued(since(to)) - self.accrued(since(from)))
+    }
+
+    // Returns the tokens credited for accruing over `elapsed` since
+    // `self.last`, rounded according to the rounding mode along with
+    // the remainder carried over from the previous refill.
+    #[inline(always)]
+    fn accrued(&self, elapsed: Duration) -> f64 {
+        self.rounding.apply(self.tokens_accrued(elapsed) + self.remainder)
+    }
+
+    // Returns the part of the tokens accrued over `elapsed` since
+    // `self.last`, along with the remainder carried over, that
+    // `accrued` doesn't credit.
+    fn uncredited(&self, elapsed: Duration) -> f64 {
+        let accrued = self.tokens_accrued(elapsed) + self.remainder;
+        accrued - self.rounding.apply(accrued)
+    }
+
+    // Returns the tokens that have to accrue from `now` on before the
+    // bucket holds `tokens` more than it does, if nothing is acquired
+    // meanwhile. Rounded refills only credit whole tokens, so this
+    // takes off the part of the next one that already accrued.
+    fn accrual_needed(&self, tokens: f64, now: SystemTime) -> f64 {
+        let whole = (tokens - self.epsilon).ceil().max(0.0);
+        let elapsed = self.accrual_end(now).duration_since(self.last).unwrap_or(Duration::ZERO);
+        match self.rounding {
+            RoundingMode::Fractional => tokens,
+            RoundingMode::Floor => whole - self.uncredited(elapsed),
+            // A token is credited once half of it accrued.
+            RoundingMode::Round => whole - 0.5 - self.uncredited(elapsed),
+        }
+    }
+
+    // Drops the scheduled deductions due by `now`, once the stored
+    // tokens have been brought up to `now` and include them.
+    #[inline(always)]
+    fn forget_due(&mut self, now: SystemTime) {
+        if !self.scheduled.is_empty() {
+            self.scheduled.retain(|&(when, _)| when > now);
+        }
+    }
+}
+
+/// Builds a TokenBucket from an `(r, b)` tuple, i.e. the rate comes
+/// first and the burst value second, exactly as in
+/// [TokenBucket::new()](struct.TokenBucket.html#method.new).
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::TokenBucket;
+/// // 5 tokens per second, bursting up to 100.
+/// let mut tb: TokenBucket = (5.0, 100.0).into();
+/// assert!(tb.acquire(100.0).is_ok());
+///
+/// let tiers: Vec<TokenBucket> = [(1.0, 10.0), (10.0, 100.0)].map(TokenBucket::from).into();
+/// assert_eq!(tiers[1].status().burst, 100.0);
+/// ```
+impl From<(f64, f64)> for TokenBucket {
+    fn from((r, b): (f64, f64)) -> TokenBucket {
+        TokenBucket::new(r, b)
+    }
+}
+
+// Returns the fraction of `b` that `tokens` represents, as reported by
+// every metrics integration.
+#[cfg(any(feature = "prometheus", feature = "opentelemetry", feature = "metrics"))]
+pub(crate) fn fill_ratio(tokens: f64, b: f64) -> f64 {
+    if b > 0.0 {
+        tokens / b
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    // This module contains unit tests for the TokenBucket implementation.
+
+    /// 1. **Initial Token Acquisition**:
+    ///    - Test acquiring tokens immediately after creating a new TokenBucket.
+    ///    - The bucket should have enough tokens initially, so the acquisition should succeed.
+    #[test]
+    fn test_initial_acquire() {
+        let mut bucket = TokenBucket::new(1.0, 1.0);
+        let result = bucket.acquire(1.0);
+        assert!(result.is_ok());
+    }
+
+    /// 2. **Token Acquisition When Tokens Are Available**:
+    ///    - Test acquiring tokens after waiting for some time.
+    ///    - After waiting for a sufficient duration, the bucket should have replenished tokens, so the acquisition should succeed.
+    #[test]
+    fn test_acquire_when_tokens_available() {
+        let mut bucket = TokenBucket::new(1.0, 1.0);
+        let result = bucket.acquire(1.0);
+        assert!(result.is_ok());
+        thread::sleep(Duration::from_secs(1));
+        let result = bucket.acquire(1.0);
+        assert!(result.is_ok());
+    }
+
+    /// 3. **Token Acquisition When Tokens Are Not Available**:
+    ///    - Test acquiring more tokens than available in the bucket.
+    ///    - If the requested number of tokens exceeds the available tokens, the acquisition should fail.
+    #[test]
+    fn test_acquire_when_tokens_not_available() {
+        let mut bucket = TokenBucket::new(1.0, 1.0);
+        let result = bucket.acquire(2.0);
+        assert!(result.is_err());
+    }
+
+    /// 4. **Token Acquisition with Replenishment**:
+    ///    - Test acquiring tokens, waiting for replenishment, and then acquiring again.
+    ///    - After the initial acquisition, wait for some time to allow tokens to replenish, then attempt to acquire tokens again.
+    #[test]
+    fn test_acquire_with_replenish() {
+        let mut bucket = TokenBucket::new(1.0, 2.0);
+        let result1 = bucket.acquire(1.0);
+        assert!(result1.is_ok());
+        thread::sleep(Duration::from_secs(1));
+        let result2 = bucket.acquire(1.0);
+        assert!(result2.is_ok());
+    }
+
+    /// 5. **Rate Limiting Behavior**:
+    ///    - Test acquiring tokens too quickly in succession.
+    ///    - If tokens are requested at a higher rate than they are replenished, the acquisition should fail due to rate limiting.
+    #[test]
+    fn test_rate_limited() {
+        let mut bucket = TokenBucket::new(1.0, 1.0);
+        let result1 = bucket.acquire(1.0);
+        assert!(result1.is_ok());
+        thread::sleep(Duration::from_millis(500));
+        let result2 = bucket.acquire(1.0);
+        assert!(result2.is_err());
+    }
+
+    /// 6. **Draining the Bucket**:
+    ///    - Test draining a full bucket and then acquiring again.
+    ///    - Drain should return every available token and leave the bucket empty, so the next acquisition should fail.
+    #[test]
+    fn test_drain() {
+        let mut bucket = TokenBucket::new(1.0, 5.0);
+        assert_eq!(bucket.drain(), 5.0);
+        let result = bucket.acquire(1.0);
+        assert!(result.is_err());
+    }
+
+    /// 7. **Acquisition Exceeding the Maximum Cost**:
+    ///    - Test acquiring more tokens than the configured `max_cost` allows.
+    ///    - The acquisition should fail with `CostExceedsMax` even though the bucket holds enough tokens, and no tokens should be removed.
+    #[test]
+    fn test_acquire_exceeds_max_cost() {
+        let mut bucket = TokenBucket::builder(1.0, 10.0).max_cost(2.0).build();
+        let result = bucket.acquire(3.0);
+        assert_eq!(
+            result,
+            Err(TokenAcquisitionError::CostExceedsMax { count: 3.0, max_cost: 2.0 })
+        );
+        assert_eq!(bucket.drain(), 10.0);
+    }
+
+    /// 8. **Blocking Acquisition**:
+    ///    - Test `acquire_wait` on an empty bucket.
+    ///    - The call should block until the next token has been replenished and then succeed.
+    #[test]
+    fn test_acquire_wait() {
+        let mut bucket = TokenBucket::new(10.0, 1.0);
+        assert!(bucket.acquire(1.0).is_ok());
+        let start = Instant::now();
+        assert!(bucket.acquire_wait(1.0).is_ok());
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+
+    /// 9. **Blocking Acquisition Beyond the Burst**:
+    ///    - Test `acquire_wait` with more tokens than the bucket can ever hold.
+    ///    - The call should return `CostExceedsBurst` immediately instead of blocking forever.
+    #[test]
+    fn test_acquire_wait_exceeds_burst() {
+        let mut bucket = TokenBucket::new(1.0, 1.0);
+        let result = bucket.acquire_wait(2.0);
+        assert_eq!(
+            result,
+            Err(TokenAcquisitionError::CostExceedsBurst { count: 2.0, burst: 1.0 })
+        );
+    }
+
+    /// 10. **Throttled Execution**:
+    ///    - Test running closures through `throttle` faster than the bucket allows.
+    ///    - Each closure should run and return its value, with later calls delayed until tokens are replenished.
+    #[test]
+    fn test_throttle() {
+        let mut bucket = TokenBucket::new(10.0, 1.0);
+        let start = Instant::now();
+        let results: Vec<u32> = (0..3).map(|i| bucket.throttle(1.0, || i * 2)).collect();
+        assert_eq!(results, vec![0, 2, 4]);
+        assert!(start.elapsed() >= Duration::from_millis(180));
+    }
+
+    /// 11. **Asynchronous Acquisition**:
+    ///    - Test `acquire_async` on an empty bucket with an injected sleep function.
+    ///    - The call should await the injected sleep until the next token has been replenished and then succeed.
+    #[test]
+    fn test_acquire_async() {
+        let mut bucket = TokenBucket::new(10.0, 1.0);
+        assert!(bucket.acquire(1.0).is_ok());
+        let start = Instant::now();
+        let sleep = |duration| async move { thread::sleep(duration) };
+        assert!(block_on(bucket.acquire_async(1.0, sleep)).is_ok());
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+
+    /// 12. **Acquisition Near the Reserve Floor**:
+    ///    - Test ordinary and reserve acquisitions on a bucket with a reserve.
+    ///    - Ordinary acquisitions should fail once only the reserve is left, while `acquire_reserve` may use it until the bucket is empty.
+    #[test]
+    fn test_acquire_reserve() {
+        let mut bucket = TokenBucket::builder(0.001, 10.0).reserve(2.0).build();
+        assert!(bucket.acquire(8.0).is_ok());
+        assert!(bucket.acquire(1.0).is_err());
+        assert!(bucket.acquire_reserve(1.0).is_ok());
+        assert!(bucket.acquire_reserve(1.0).is_ok());
+        assert!(bucket.acquire_reserve(1.0).is_err());
+    }
+
+    /// 13. **Replenishment Callback**:
+    ///    - Test registering an `on_replenish` callback and acquiring tokens after a short wait.
+    ///    - The callback should not fire when nothing accrued, and should receive the accrued amount once tokens are added.
+    #[test]
+    fn test_on_replenish() {
+        let added = Arc::new(Mutex::new(Vec::new()));
+        let mut bucket = TokenBucket::new(100.0, 10.0);
+        let recorder = Arc::clone(&added);
+        bucket.on_replenish(move |amount| recorder.lock().unwrap().push(amount));
+
+        assert!(bucket.acquire(10.0).is_ok());
+        assert!(added.lock().unwrap().is_empty());
+
+        thread::sleep(Duration::from_millis(50));
+        assert!(bucket.acquire(1.0).is_ok());
+        let added = added.lock().unwrap();
+        assert_eq!(added.len(), 1);
+        assert!(added[0] >= 5.0 && added[0] <= 10.0);
+    }
+
+    /// 14. **Completion Time Estimates**:
+    ///    - Test estimating jobs that fit within the burst and jobs that exceed it.
+    ///    - Jobs that fit should complete at the target rate, while larger jobs should be bound by the sustainable rate once the burst is drained.
+    #[test]
+    fn test_estimate_completion_time() {
+        let bucket = TokenBucket::new(5.0, 100.0);
+        assert_eq!(bucket.sustainable_rate(), 5.0);
+        assert_eq!(bucket.estimate_completion_time(100.0, 50.0), Duration::from_secs(2));
+        assert_eq!(bucket.estimate_completion_time(200.0, 50.0), Duration::from_secs(20));
+        assert_eq!(bucket.estimate_completion_time(10.0, 2.0), Duration::from_secs(5));
+    }
+
+    /// 15. **Replenishment Precision at High Rates**:
+    ///    - Test draining a 10,000 tokens/second bucket over many sub-millisecond intervals.
+    ///    - The total drained should track the configured rate closely instead of losing the fraction of every interval, as millisecond timing would.
+    #[test]
+    fn test_replenish_precision_high_rate() {
+        let mut bucket = TokenBucket::new(10_000.0, 10_000.0);
+        bucket.drain();
+        let start = Instant::now();
+        let mut total = 0.0;
+        for _ in 0..50 {
+            thread::sleep(Duration::from_micros(200));
+            total += bucket.drain();
+        }
+        let expected = 10_000.0 * start.elapsed().as_secs_f64();
+        assert!(total <= expected * 1.05);
+        assert!(total >= expected * 0.95);
+    }
+
+    /// 16. **Acquisition With Remaining Tokens**:
+    ///    - Test acquiring tokens and reading the remaining count from the same call, on success and on denial.
+    ///    - The remaining count should match what the acquisition left in the bucket, and a denial should leave it unchanged.
+    #[test]
+    fn test_acquire_with_remaining() {
+        let mut bucket = TokenBucket::new(0.001, 10.0);
+        let (result, remaining) = bucket.acquire_with_remaining(3.0);
+        assert!(result.is_ok());
+        assert!((remaining - 7.0).abs() < 0.001);
+
+        let (result, after_denial) = bucket.acquire_with_remaining(8.0);
+        assert!(result.is_err());
+        assert!((after_denial - remaining).abs() < 0.001);
+        assert!((bucket.drain() - after_denial).abs() < 0.001);
+    }
+
+    /// 17. **Clock Moving Backwards**:
+    ///    - Test acquiring tokens after the injected clock is set to a time before the last acquisition.
+    ///    - The bucket should treat the gap as no time having passed, neither panicking nor crediting the skipped interval twice once the clock catches up.
+    #[test]
+    fn test_clock_moves_backwards() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let clock = ManualClock::new(start);
+        let mut bucket = TokenBucket::builder(1.0, 2.0).clock(clock.clone()).build();
+        assert!(bucket.acquire(2.0).is_ok());
+
+        clock.set(start - Duration::from_secs(10));
+        assert!(bucket.acquire(1.0).is_err());
+        assert_eq!(bucket.time_until_available(1.0), Duration::from_secs(1));
+
+        clock.set(start + Duration::from_secs(1));
+        assert!(bucket.acquire(1.0).is_ok());
+        assert_eq!(bucket.drain(), 0.0);
+    }
+
+    /// 18. **Acquisition at Supplied Timestamps**:
+    ///    - Test replaying a recorded trace of timestamps through `try_acquire_at`, including one earlier than a previous event.
+    ///    - Decisions should follow the supplied times rather than the wall clock, and the out-of-order event should count as no time having passed.
+    #[test]
+    fn test_try_acquire_at() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let mut bucket = TokenBucket::builder(2.0, 2.0).clock(ManualClock::new(start)).build();
+        let trace = [(0, true), (0, true), (250, false), (500, true), (100, false), (1_000, true)];
+        for (offset_ms, expected) in trace {
+            let at = start + Duration::from_millis(offset_ms);
+            assert_eq!(bucket.try_acquire_at(1.0, at).is_ok(), expected, "at {}ms", offset_ms);
+        }
+    }
+
+    /// 19. **Token Accrual**:
+    ///    - Test the pure accrual function over a few durations.
+    ///    - Accrual should be the rate multiplied by the elapsed seconds, without clamping to the burst value.
+    #[test]
+    fn test_tokens_accrued() {
+        let bucket = TokenBucket::new(4.0, 10.0);
+        assert_eq!(bucket.tokens_accrued(Duration::ZERO), 0.0);
+        assert_eq!(bucket.tokens_accrued(Duration::from_millis(250)), 1.0);
+        assert_eq!(bucket.tokens_accrued(Duration::from_secs(5)), 20.0);
+    }
+
+    /// 20. **Jittered Initial Tokens**:
+    ///    - Test building buckets with seeded random initial tokens.
+    ///    - The same seed should always produce the same fill within `[0, b]`, and different seeds should produce different fills.
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_jitter_initial_tokens() {
+        let fill = |seed| {
+            TokenBucket::builder(0.0, 100.0)
+                .jitter_initial_tokens_with_seed(seed)
+                .build()
+                .drain()
+        };
+        for seed in 0..10 {
+            assert!((0.0..=100.0).contains(&fill(seed)));
+            assert_eq!(fill(seed), fill(seed));
+        }
+        assert_ne!(fill(1), fill(2));
+    }
+
+    /// 21. **Snapshot Round Trip**:
+    ///    - Test snapshotting a partially drained bucket and restoring it on a clock that has moved on.
+    ///    - The restored bucket should hold the same tokens, including those accrued before the snapshot, but not those for the time spent in storage.
+    #[test]
+    fn test_snapshot_round_trip() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let clock = ManualClock::new(start);
+        let config = TokenBucket::builder(2.0, 10.0).clock(clock.clone());
+        let mut bucket = config.clone().build();
+        assert!(bucket.acquire(3.0).is_ok());
+        clock.advance(Duration::from_millis(500));
+
+        let state = bucket.snapshot();
+        assert_eq!(state, BucketState { tokens: 7.0, elapsed: Duration::from_millis(500) });
+
+        clock.advance(Duration::from_secs(60));
+        let mut restored = TokenBucket::from_snapshot(config, state);
+        assert_eq!(restored.snapshot(), state);
+        assert_eq!(restored.drain(), 8.0);
+    }
+
+    /// 22. **Denial Streak Duration**:
+    ///    - Test repeatedly denied acquisitions followed by a success and another denial.
+    ///    - `scarce_for` should grow from the first denial of the streak and start over once an acquisition succeeds.
+    #[test]
+    fn test_scarce_for() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(0.1, 1.0).clock(clock.clone()).build();
+        let scarce_for = |result| match result {
+            Err(TokenAcquisitionError::RateLimited { scarce_for, .. }) => scarce_for,
+            other => panic!("unexpected result {:?}", other),
+        };
+
+        assert!(bucket.acquire(1.0).is_ok());
+        assert_eq!(scarce_for(bucket.acquire(1.0)), Duration::ZERO);
+        clock.advance(Duration::from_secs(4));
+        assert_eq!(scarce_for(bucket.acquire(1.0)), Duration::from_secs(4));
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(scarce_for(bucket.acquire(1.0)), Duration::from_secs(9));
+
+        clock.advance(Duration::from_secs(1));
+        assert!(bucket.acquire(1.0).is_ok());
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(scarce_for(bucket.acquire(1.0)), Duration::ZERO);
+    }
+
+    /// 23. **Burst Expressed in Seconds**:
+    ///    - Test constructing buckets whose burst is a number of seconds' worth of tokens.
+    ///    - The full burst should be acquirable at once, and nothing beyond it.
+    #[test]
+    fn test_with_burst_seconds() {
+        for (r, seconds) in [(10.0, 5.0), (4.0, 0.5), (100.0, 2.0)] {
+            let mut bucket = TokenBucket::with_burst_seconds(r, seconds);
+            assert!(!bucket.peek_allow(r * seconds + 1.0));
+            assert!(bucket.acquire(r * seconds).is_ok());
+        }
+    }
+
+    /// 24. **Sustained Rate Throttling**:
+    ///    - Test whether sustaining rates around the configured rate would throttle, regardless of the burst.
+    ///    - Only rates above `r` should throttle, however large the burst.
+    #[test]
+    fn test_would_throttle_at() {
+        for b in [1.0, 1_000.0] {
+            let bucket = TokenBucket::new(10.0, b);
+            assert!(!bucket.would_throttle_at(1.0));
+            assert!(!bucket.would_throttle_at(10.0));
+            assert!(bucket.would_throttle_at(10.5));
+        }
+    }
+
+    /// 25. **Batch Acquisition Totals**:
+    ///    - Test acquiring a batch of equal-cost items larger than the available tokens.
+    ///    - Items should be admitted until the tokens run out and the rest rejected, with the totals adding up to the batch size.
+    #[test]
+    fn test_acquire_batch() {
+        let mut bucket = TokenBucket::new(0.001, 10.0);
+        assert_eq!(bucket.acquire_batch(8, 1.5), (6, 2));
+        assert_eq!(bucket.acquire_batch(3, 1.0), (1, 2));
+        assert_eq!(bucket.acquire_batch(0, 1.0), (0, 0));
+    }
+
+    /// 26. **Lazily Computed Cost**:
+    ///    - Test `acquire_with` on a bucket with tokens and on an exhausted bucket.
+    ///    - The cost function should be evaluated and its cost acquired while tokens remain, and skipped entirely once the bucket is empty, without reporting a denial of any made-up cost.
+    #[test]
+    fn test_acquire_with() {
+        let mut bucket = TokenBucket::new(0.0, 3.0);
+        let denied = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&denied);
+        bucket.on_denied(move |count, _| recorded.lock().unwrap().push(count));
+        let mut evaluated = 0;
+        let mut cost = || {
+            evaluated += 1;
+            2.0
+        };
+
+        assert!(bucket.acquire_with(&mut cost).is_ok());
+        assert!(bucket.acquire_with(&mut cost).is_err());
+        assert_eq!(bucket.drain(), 1.0);
+        assert!(matches!(
+            bucket.acquire_with(&mut cost),
+            Err(TokenAcquisitionError::RateLimited { .. })
+        ));
+        assert_eq!(evaluated, 2);
+        assert_eq!(*denied.lock().unwrap(), [2.0]);
+    }
+
+    /// 27. **Idle Time**:
+    ///    - Test the idle time of a bucket as the injected clock advances around acquisitions.
+    ///    - The idle time should grow with the clock and start over on each successful acquisition, but not on a denied one.
+    #[test]
+    fn test_idle_time() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(1.0, 1.0).clock(clock.clone()).build();
+        assert_eq!(bucket.idle_time(), Duration::ZERO);
+
+        clock.advance(Duration::from_secs(3));
+        assert_eq!(bucket.idle_time(), Duration::from_secs(3));
+        assert!(bucket.acquire(1.0).is_ok());
+        assert_eq!(bucket.idle_time(), Duration::ZERO);
+
+        clock.advance(Duration::from_millis(500));
+        assert!(bucket.acquire(1.0).is_err());
+        assert_eq!(bucket.idle_time(), Duration::from_millis(500));
+    }
+
+    /// 28. **Integer-Only Mode**:
+    ///    - Test fractional and whole acquisitions on an integer-only bucket, and draining it partway through a token.
+    ///    - Fractional requests should be rejected with `FractionalCount`, and only whole tokens handed out while the fraction keeps accruing.
+    #[test]
+    fn test_integer_only() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(1.0, 5.0)
+            .integer_only(true)
+            .clock(clock.clone())
+            .build();
+
+        assert_eq!(
+            bucket.acquire(0.5),
+            Err(TokenAcquisitionError::FractionalCount { count: 0.5 })
+        );
+        assert!(bucket.acquire(1.5).is_err());
+        assert!(bucket.acquire(5.0).is_ok());
+
+        clock.advance(Duration::from_millis(1_500));
+        assert_eq!(bucket.drain(), 1.0);
+        clock.advance(Duration::from_millis(500));
+        assert!(bucket.acquire(1.0).is_ok());
+    }
+
+    /// 29. **Construction From Throughput Terms**:
+    ///    - Test deriving buckets from peak rate, sustained rate and burst window.
+    ///    - The rate should be the sustained rate and the burst should follow the documented formula.
+    #[test]
+    fn test_from_throughput() {
+        let cases = [(50.0, 10.0, 3_000, 130.0), (10.0, 10.0, 5_000, 10.0), (100.0, 20.0, 500, 60.0)];
+        for (peak, sustained, window_ms, expected_b) in cases {
+            let bucket = TokenBucket::from_throughput(peak, sustained, Duration::from_millis(window_ms));
+            assert_eq!(bucket.sustainable_rate(), sustained);
+            assert!(bucket.peek_allow(expected_b));
+            assert!(!bucket.peek_allow(expected_b + 0.5));
+        }
+    }
+
+    /// 30. **Denial Callback**:
+    ///    - Test registering an `on_denied` callback and making allowed, denied and invalid acquisitions.
+    ///    - The callback should fire only for the denial, with the requested count and the tokens available.
+    #[test]
+    fn test_on_denied() {
+        let denials = Arc::new(Mutex::new(Vec::new()));
+        let mut bucket = TokenBucket::builder(0.0, 3.0).max_cost(10.0).build();
+        let recorder = Arc::clone(&denials);
+        bucket.on_denied(move |count, tokens| recorder.lock().unwrap().push((count, tokens)));
+
+        assert!(bucket.acquire(2.0).is_ok());
+        assert!(bucket.acquire(5.0).is_err());
+        assert!(bucket.acquire(20.0).is_err());
+        assert_eq!(*denials.lock().unwrap(), vec![(5.0, 1.0)]);
+    }
+
+    /// 31. **Set Tokens**:
+    ///    - Test setting the token level below zero, within range and above `b` on a bucket with a manual clock.
+    ///    - The level should be clamped to `[0, b]` and replenishment should restart from the time of the call.
+    #[test]
+    fn test_set_tokens() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(1.0, 10.0).clock(clock.clone()).build();
+
+        bucket.set_tokens(-5.0);
+        assert!(bucket.acquire(1.0).is_err());
+
+        clock.advance(Duration::from_secs(4));
+        bucket.set_tokens(2.0);
+        assert_eq!(bucket.idle_time(), Duration::ZERO);
+        assert!(bucket.acquire(3.0).is_err());
+        clock.advance(Duration::from_secs(1));
+        assert!(bucket.acquire(3.0).is_ok());
+
+        bucket.set_tokens(50.0);
+        assert_eq!(bucket.drain(), 10.0);
+    }
+
+    /// 32. **Next Available Time**:
+    ///    - Test `next_available_at` on a full, an emptied and a non-replenishing bucket with a manual clock.
+    ///    - It should return the current time when tokens are available, the refill time otherwise, and `None` when the tokens can never be afforded.
+    #[test]
+    fn test_next_available_at() {
+        let start = SystemTime::UNIX_EPOCH;
+        let clock = ManualClock::new(start);
+        let mut bucket = TokenBucket::builder(2.0, 4.0).clock(clock.clone()).build();
+
+        assert_eq!(bucket.next_available_at(4.0), Some(start));
+        assert!(bucket.acquire(4.0).is_ok());
+        assert_eq!(bucket.next_available_at(1.0), Some(start + Duration::from_millis(500)));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(bucket.next_available_at(4.0), Some(start + Duration::from_secs(2)));
+        assert_eq!(bucket.next_available_at(5.0), None);
+
+        let mut frozen = TokenBucket::builder(0.0, 4.0).clock(clock.clone()).build();
+        assert!(frozen.acquire(4.0).is_ok());
+        assert_eq!(frozen.next_available_at(1.0), None);
+    }
+
+    /// 33. **Remaining After Acquisition**:
+    ///    - Test `remaining_after` for affordable counts, counts above the available tokens, above `b` and into the reserve.
+    ///    - It should project the remaining tokens for affordable counts, return `None` otherwise, and never change the bucket.
+    #[test]
+    fn test_remaining_after() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(1.0, 10.0).reserve(2.0).clock(clock.clone()).build();
+
+        assert_eq!(bucket.remaining_after(0.0), Some(10.0));
+        assert_eq!(bucket.remaining_after(8.0), Some(2.0));
+        assert_eq!(bucket.remaining_after(9.0), None);
+        assert_eq!(bucket.remaining_after(11.0), None);
+        assert_eq!(bucket.remaining_after(f64::INFINITY), None);
+
+        assert!(bucket.acquire(6.0).is_ok());
+        assert_eq!(bucket.remaining_after(3.0), None);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(bucket.remaining_after(3.0), Some(2.0));
+        assert_eq!(bucket.remaining_after(3.0), Some(2.0));
+    }
+
+    /// 34. **Status Snapshot**:
+    ///    - Test `status` on a bucket with a manual clock after an acquisition and some idle time.
+    ///    - Every field should reflect the same instant, including the tokens accrued while idle.
+    #[test]
+    fn test_status() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(2.0, 10.0).clock(clock.clone()).build();
+        assert!(bucket.acquire(8.0).is_ok());
+        clock.advance(Duration::from_secs(1));
+
+        assert_eq!(
+            bucket.status(),
+            BucketStatus {
+                rate: 2.0,
+                burst: 10.0,
+                tokens: 4.0,
+                fill: 0.4,
+                idle: Duration::from_secs(1),
+            }
+        );
+    }
+
+    /// 35. **Duration Cost**:
+    ///    - Test charging a time budget with `acquire_duration` using sub-second costs and a manual clock.
+    ///    - Costs should be charged as fractional seconds and the budget should refill at `r` seconds per second.
+    #[test]
+    fn test_acquire_duration() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(0.25, 1.0).clock(clock.clone()).build();
+
+        assert!(bucket.acquire_duration(Duration::from_millis(750)).is_ok());
+        assert!(bucket.acquire_duration(Duration::from_millis(300)).is_err());
+        assert!(bucket.acquire_duration(Duration::from_millis(250)).is_ok());
+
+        clock.advance(Duration::from_secs(2));
+        assert!(bucket.acquire_duration(Duration::from_millis(500)).is_ok());
+        assert!(bucket.acquire_duration(Duration::from_millis(1)).is_err());
+    }
+
+    /// 36. **Retry Exhaustion**:
+    ///    - Test `acquire_with_retry` with a fixed backoff against a bucket that never replenishes.
+    ///    - Exactly `max_attempts` attempts should be made before the last denial is returned.
+    #[test]
+    fn test_acquire_with_retry_exhausted() {
+        let attempts = Arc::new(Mutex::new(0));
+        let mut bucket = TokenBucket::new(0.0, 1.0);
+        let counter = Arc::clone(&attempts);
+        bucket.on_denied(move |_, _| *counter.lock().unwrap() += 1);
+        assert!(bucket.acquire(1.0).is_ok());
+
+        let policy = RetryPolicy::new(3).backoff(Backoff::Fixed(Duration::from_millis(1)));
+        assert!(matches!(
+            bucket.acquire_with_retry(1.0, &policy),
+            Err(TokenAcquisitionError::RateLimited { .. })
+        ));
+        assert_eq!(*attempts.lock().unwrap(), 3);
+        assert_eq!(
+            bucket.acquire_with_retry(2.0, &policy),
+            Err(TokenAcquisitionError::CostExceedsBurst { count: 2.0, burst: 1.0 })
+        );
+        assert_eq!(*attempts.lock().unwrap(), 3);
+    }
 
-    /// 4. **Token Acquisition with Replenishment**:
-    ///    - Test acquiring tokens, waiting for replenishment, and then acquiring again.
-    ///    - After the initial acquisition, wait for some time to allow tokens to replenish, then attempt to acquire tokens again.
+    /// 37. **Successful Retry**:
+    ///    - Test `acquire_with_retry` waiting until available on an emptied bucket.
+    ///    - The retry should succeed once the bucket has replenished.
     #[test]
-    fn test_acquire_with_replenish() {
-        let mut bucket = TokenBucket::new(1.0, 2.0);
-        let result1 = bucket.acquire(1.0);
-        assert!(result1.is_ok());
-        thread::sleep(Duration::from_secs(1));
-        let result2 = bucket.acquire(1.0);
-        assert!(result2.is_ok());
+    fn test_acquire_with_retry_succeeds() {
+        let mut bucket = TokenBucket::new(20.0, 1.0);
+        assert!(bucket.acquire(1.0).is_ok());
+
+        let start = Instant::now();
+        assert!(bucket.acquire_with_retry(1.0, &RetryPolicy::new(2)).is_ok());
+        assert!(start.elapsed() >= Duration::from_millis(45));
     }
 
-    /// 5. **Rate Limiting Behavior**:
-    ///    - Test acquiring tokens too quickly in succession.
-    ///    - If tokens are requested at a higher rate than they are replenished, the acquisition should fail due to rate limiting.
+    /// 38. **Adaptive Burst**:
+    ///    - Test a bucket with an adaptive burst under light load, then under a spike, using a manual clock.
+    ///    - The burst value should grow to its maximum under light load and shrink towards its minimum on denials, and a full bucket denying a cost above its shrunk burst value should grow it back instead.
     #[test]
-    fn test_rate_limited() {
-        let mut bucket = TokenBucket::new(1.0, 1.0);
-        let result1 = bucket.acquire(1.0);
-        assert!(result1.is_ok());
-        thread::sleep(Duration::from_millis(500));
-        let result2 = bucket.acquire(1.0);
-        assert!(result2.is_err());
+    fn test_adaptive_burst() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(10.0, 5.0)
+            .adaptive_burst(10.0, 30.0)
+            .clock(clock.clone())
+            .build();
+        assert_eq!(bucket.status().burst, 10.0);
+
+        for _ in 0..30 {
+            assert!(bucket.acquire(1.0).is_ok());
+            clock.advance(Duration::from_secs(1));
+        }
+        assert_eq!(bucket.status().burst, 30.0);
+
+        while bucket.acquire(5.0).is_ok() {}
+        assert_eq!(bucket.status().burst, 20.0);
+        assert!(bucket.acquire(5.0).is_err());
+        assert_eq!(bucket.status().burst, 15.0);
+
+        // A cost above the shrunk burst value but within `max_b` is
+        // waited for rather than rejected, and recovers the burst.
+        clock.advance(Duration::from_secs(10));
+        assert!(bucket.acquire_blocking_step(25.0).is_err());
+        let mut attempts = 1;
+        while bucket.acquire(25.0).is_err() {
+            clock.advance(Duration::from_secs(10));
+            attempts += 1;
+        }
+        assert!(attempts <= 11, "{}", attempts);
+        assert_eq!(bucket.status().burst, 25.0);
+    }
+
+    /// 39. **Tick Pattern**:
+    ///    - Test iterating an empty bucket with `ticks` at three times its rate, with a manual clock.
+    ///    - Every third tick should be allowed, and the clock should have advanced by every interval.
+    #[test]
+    fn test_ticks() {
+        let start = SystemTime::UNIX_EPOCH;
+        let clock = ManualClock::new(start);
+        let mut bucket = TokenBucket::builder(1.0, 1.0).clock(clock.clone()).build();
+        assert!(bucket.acquire(1.0).is_ok());
+
+        let interval = Duration::from_micros(333_334);
+        let pattern: Vec<bool> = bucket.ticks(&clock, interval, 1.0).take(9).collect();
+        assert_eq!(pattern, [false, false, true, false, false, true, false, false, true]);
+        assert_eq!(clock.now(), start + interval * 9);
+    }
+
+    /// 40. **Overflowed Tokens**:
+    ///    - Test a long idle period on a partially drained bucket with a manual clock, followed by acquisitions.
+    ///    - Only the accrued tokens that didn't fit into the bucket should be counted as overflowed.
+    #[test]
+    fn test_overflowed() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(2.0, 10.0).clock(clock.clone()).build();
+        assert!(bucket.acquire(4.0).is_ok());
+
+        clock.advance(Duration::from_secs(1));
+        assert!(bucket.acquire(1.0).is_ok());
+        assert_eq!(bucket.overflowed(), 0.0);
+
+        clock.advance(Duration::from_secs(60));
+        assert!(bucket.acquire(1.0).is_ok());
+        assert_eq!(bucket.overflowed(), 117.0);
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(bucket.drain(), 10.0);
+        assert_eq!(bucket.overflowed(), 118.0);
+    }
+
+    /// 41. **Proportional Split**:
+    ///    - Test splitting a partially drained bucket 70/30 with a manual clock.
+    ///    - The rates, burst values and tokens of both parts should sum to those of the original.
+    #[test]
+    fn test_split() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(10.0, 100.0).clock(clock.clone()).build();
+        assert!(bucket.acquire(60.0).is_ok());
+        clock.advance(Duration::from_secs(1));
+        let original = bucket.status();
+
+        let (first, second) = bucket.split(0.7);
+        let (first, second) = (first.status(), second.status());
+        assert!((first.rate - 7.0).abs() < 1e-9 && (second.rate - 3.0).abs() < 1e-9);
+        assert!((first.rate + second.rate - original.rate).abs() < 1e-9);
+        assert!((first.burst + second.burst - original.burst).abs() < 1e-9);
+        assert!((first.tokens + second.tokens - original.tokens).abs() < 1e-9);
+        assert!((first.tokens - 35.0).abs() < 1e-9);
+    }
+
+    /// 42. **Invalid Split Ratio**:
+    ///    - Test splitting a bucket with a ratio of one.
+    ///    - The split should panic, since one part would receive no capacity.
+    #[test]
+    #[should_panic(expected = "split ratio must be in (0, 1)")]
+    fn test_split_invalid_ratio() {
+        let _ = TokenBucket::new(10.0, 100.0).split(1.0);
+    }
+
+    /// 43. **Bandwidth in Bytes**:
+    ///    - Test the rate and burst of a 1 MB/s bucket with a 0.01 MB burst, and acquiring a few kilobytes at a time with a manual clock.
+    ///    - Transfers should be allowed until the 10,000 byte burst is used up, and again once enough bytes have accrued.
+    #[test]
+    fn test_bytes_per_sec() {
+        let reference = TokenBucket::bytes_per_sec(1.0, 0.01);
+        assert_eq!((reference.r, reference.b), (1_000_000.0, 10_000.0));
+
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(reference.r, reference.b).clock(clock.clone()).build();
+        assert!(bucket.acquire(4096.0).is_ok());
+        assert!(bucket.acquire(4096.0).is_ok());
+        assert!(bucket.acquire(4096.0).is_err());
+
+        clock.advance(Duration::from_millis(3));
+        assert!(bucket.acquire(4096.0).is_ok());
+    }
+
+    /// 44. **Config Hot Reload**:
+    ///    - Test applying a new config mid-stream to a partially drained bucket with a manual clock.
+    ///    - Tokens accrued before the reload should use the old rate, the fill level should carry over, and the new limits should apply afterwards.
+    #[test]
+    fn test_apply_config() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(1.0, 10.0).clock(clock.clone()).build();
+        assert!(bucket.acquire(8.0).is_ok());
+        clock.advance(Duration::from_secs(2));
+
+        bucket.apply_config(&BucketConfig {
+            max_cost: Some(15.0),
+            ..BucketConfig::new(10.0, 40.0)
+        });
+        assert_eq!(bucket.status().tokens, 16.0);
+        assert!(bucket.acquire(20.0).is_err());
+
+        clock.advance(Duration::from_secs(1));
+        assert!(bucket.acquire(15.0).is_ok());
+        assert!(matches!(
+            bucket.acquire(16.0),
+            Err(TokenAcquisitionError::CostExceedsMax { .. })
+        ));
+    }
+
+    /// 45. **Granted Blocking Acquisition**:
+    ///    - Test `acquire_wait_granted` on a full bucket, then on an emptied bucket that refills one token every 50ms.
+    ///    - The first grant should report no wait and leave the one token it didn't take, and the second should report at least the time until the next token.
+    #[test]
+    fn test_acquire_wait_granted() {
+        let mut bucket = TokenBucket::new(20.0, 2.0);
+        let grant = bucket.acquire_wait_granted(1.0).unwrap();
+        assert_eq!(grant.waited, Duration::ZERO);
+        assert_eq!(grant.tokens, 1.0);
+
+        assert!(bucket.acquire(1.0).is_ok());
+        let grant = bucket.acquire_wait_granted(1.0).unwrap();
+        assert!(grant.waited >= Duration::from_millis(45), "{:?}", grant.waited);
+        assert!(grant.tokens >= 0.0);
+    }
+
+    /// 46. **Single Token Fast Path**:
+    ///    - Test `allow1` against `acquire(1.0)` on identical buckets with a reserve and a manual clock.
+    ///    - Both should allow and deny the same acquisitions and leave the buckets in the same state.
+    #[test]
+    fn test_allow1() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let builder = TokenBucket::builder(2.0, 3.0).reserve(1.0).clock(clock.clone());
+        let (mut fast, mut regular) = (builder.clone().build(), builder.build());
+
+        for step in 0..12 {
+            assert_eq!(fast.allow1(), regular.acquire(1.0).is_ok(), "step {}", step);
+            assert_eq!(fast.status(), regular.status());
+            clock.advance(Duration::from_millis(250));
+        }
+
+        let mut capped = TokenBucket::builder(2.0, 3.0).max_cost(0.5).build();
+        assert!(!capped.allow1());
+    }
+
+    /// 47. **Non-Monotonic Clock Fuzzing**:
+    ///    - Test a long pseudo-random sequence of operations on a bucket whose injected clock mostly moves forward but regularly jumps backwards.
+    ///    - Nothing should panic, the tokens should stay within `[0, b]`, and no more tokens should be granted than the burst plus the tokens accrued up to the latest time seen.
+    #[test]
+    fn test_non_monotonic_clock_fuzz() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let clock = ManualClock::new(start);
+        let (r, b) = (5.0, 10.0);
+        let mut bucket = TokenBucket::builder(r, b).clock(clock.clone()).build();
+
+        let mut seed = 7;
+        let mut random = move || {
+            seed += 1;
+            builder::unit_interval(seed)
+        };
+        let (mut now, mut latest) = (start, start);
+        let mut granted = 0.0;
+
+        for _ in 0..10_000 {
+            // Every fourth step or so jumps up to 1s back, the others
+            // move up to 300ms forward.
+            let back = Duration::from_secs_f64(random());
+            now = if random() < 0.25 {
+                now.checked_sub(back).unwrap_or(start).max(start)
+            } else {
+                now + Duration::from_secs_f64(random() * 0.3)
+            };
+            latest = latest.max(now);
+            clock.set(now);
+
+            let cost = (random() * 3.0).round();
+            match (random() * 6.0) as u32 {
+                0 => granted += bucket.acquire(cost).map_or(0.0, |_| cost),
+                1 => granted += if bucket.allow1() { 1.0 } else { 0.0 },
+                2 => granted += bucket.drain(),
+                3 => granted += bucket.try_acquire_at(cost, now - back).map_or(0.0, |_| cost),
+                4 => {
+                    bucket.time_until_available(cost);
+                    bucket.next_available_at(cost);
+                    bucket.idle_time();
+                },
+                _ => granted += bucket.acquire_with_remaining(cost).0.map_or(0.0, |_| cost),
+            }
+
+            let status = bucket.status();
+            assert!(status.tokens >= 0.0 && status.tokens <= b, "{:?}", status);
+        }
+
+        let accrued = r * latest.duration_since(start).unwrap().as_secs_f64();
+        assert!(granted <= b + accrued + 1e-6, "granted {} of {}", granted, b + accrued);
+    }
+
+    /// 48. **Burst Around One Token**:
+    ///    - Test single-token acquisitions against burst values just below and just above one, and against `new_no_burst`, with a manual clock.
+    ///    - A burst below one should never allow a token, while a burst of at least one should allow one token per refill interval.
+    #[test]
+    fn test_burst_around_one_token() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let build = |b| TokenBucket::builder(10.0, b).clock(clock.clone()).build();
+        let (mut below, mut above) = (build(0.999), build(1.001));
+
+        for _ in 0..5 {
+            assert!(below.acquire(1.0).is_err());
+            assert!(above.acquire(1.0).is_ok());
+            clock.advance(Duration::from_secs(1));
+        }
+        assert!(matches!(
+            below.acquire_wait(1.0),
+            Err(TokenAcquisitionError::CostExceedsBurst { .. })
+        ));
+
+        let status = TokenBucket::new_no_burst(10.0).status();
+        assert_eq!((status.rate, status.burst), (10.0, 1.0));
+        let mut no_burst = build(status.burst);
+        assert!(no_burst.acquire(1.0).is_ok());
+        assert!(no_burst.acquire(1.0).is_err());
+        clock.advance(Duration::from_millis(100));
+        assert!(no_burst.acquire(1.0).is_ok());
+        clock.advance(Duration::from_secs(10));
+        assert!(no_burst.acquire(1.0).is_ok());
+        assert!(no_burst.acquire(1.0).is_err());
+    }
+
+    /// 49. **Poll-Based Acquisition**:
+    ///    - Test `poll_acquire` through `poll_fn` on a full bucket, an emptied bucket and with a count above the burst value.
+    ///    - It should be ready immediately when possible or invalid, and otherwise become ready after being woken by the timer.
+    #[test]
+    fn test_poll_acquire() {
+        let mut bucket = TokenBucket::new(20.0, 1.0);
+        assert!(block_on(std::future::poll_fn(|cx| bucket.poll_acquire(cx, 1.0))).is_ok());
+
+        let start = Instant::now();
+        assert!(block_on(std::future::poll_fn(|cx| bucket.poll_acquire(cx, 1.0))).is_ok());
+        assert!(start.elapsed() >= Duration::from_millis(45));
+
+        assert!(matches!(
+            block_on(std::future::poll_fn(|cx| bucket.poll_acquire(cx, 2.0))),
+            Err(TokenAcquisitionError::CostExceedsBurst { .. })
+        ));
+    }
+
+    /// 50. **Cancelled Blocking Wait**:
+    ///    - Test a blocking acquisition that would wait ten seconds, with the cancellation flag set from another thread after 50ms.
+    ///    - The acquisition should return `Cancelled` once the flag is set rather than waiting for the token, without consuming any tokens.
+    #[test]
+    fn test_acquire_wait_cancellable() {
+        let mut bucket = TokenBucket::new(0.1, 1.0);
+        assert!(bucket.acquire(1.0).is_ok());
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let setter = Arc::clone(&cancel);
+        let start = Instant::now();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            setter.store(true, Ordering::Relaxed);
+        });
+
+        assert_eq!(bucket.acquire_wait_cancellable(1.0, &cancel), Err(TokenAcquisitionError::Cancelled));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        handle.join().unwrap();
+
+        cancel.store(false, Ordering::Relaxed);
+        bucket.set_tokens(1.0);
+        assert!(bucket.acquire_wait_cancellable(1.0, &cancel).is_ok());
+    }
+
+    /// 51. **Projected Token Level**:
+    ///    - Test projecting the token level of an emptied bucket into the past, the future and beyond the time it refills.
+    ///    - Past times should report the current level, future times the accrued tokens, and the projection should never exceed the burst value.
+    #[test]
+    fn test_tokens_at() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let clock = ManualClock::new(start);
+        let mut bucket = TokenBucket::builder(2.0, 10.0).clock(clock.clone()).build();
+        bucket.drain();
+        clock.advance(Duration::from_secs(1));
+
+        assert_eq!(bucket.tokens_at(start - Duration::from_secs(50)), 2.0);
+        assert_eq!(bucket.tokens_at(start + Duration::from_secs(3)), 6.0);
+        assert_eq!(bucket.tokens_at(start + Duration::from_secs(60)), 10.0);
+        assert_eq!(bucket.drain(), 2.0);
+    }
+
+    /// 52. **Denial Penalty**:
+    ///    - Test a denial storm against an emptied bucket with a denial penalty, next to an identical bucket without one.
+    ///    - The penalized bucket should take longer to recover, but never longer than its maximum debt allows.
+    #[test]
+    fn test_denial_penalty() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut plain = TokenBucket::builder(10.0, 1.0).clock(clock.clone()).build();
+        let mut penalized = TokenBucket::builder(10.0, 1.0)
+            .clock(clock.clone())
+            .denial_penalty(0.5, 5.0)
+            .build();
+        plain.drain();
+        penalized.drain();
+
+        for _ in 0..4 {
+            assert!(plain.acquire(1.0).is_err());
+            assert!(penalized.acquire(1.0).is_err());
+        }
+        assert_eq!(plain.time_until_available(1.0), Duration::from_millis(100));
+        assert_eq!(penalized.time_until_available(1.0), Duration::from_millis(300));
+
+        for _ in 0..1000 {
+            assert!(penalized.acquire(1.0).is_err());
+        }
+        assert_eq!(penalized.time_until_available(1.0), Duration::from_millis(600));
+        assert_eq!(penalized.drain(), 0.0);
+
+        clock.advance(Duration::from_millis(600));
+        assert!(penalized.acquire(1.0).is_ok());
+    }
+
+    /// 53. **Retry on the Next Tick**:
+    ///    - Test `acquire_until_next_tick` on emptied buckets with discrete refills, once for a single token on the system clock and once for two tokens on a manual clock that doesn't move while it waits.
+    ///    - The single token should be granted after at least one tick, the tick should be measured from the part of the next token that already accrued, and two tokens should still be denied after a single tick.
+    #[test]
+    fn test_acquire_until_next_tick() {
+        let mut bucket = TokenBucket::builder(20.0, 2.0).rounding(RoundingMode::Floor).build();
+        assert_eq!(bucket.drain(), 2.0);
+        let start = Instant::now();
+        assert!(bucket.acquire_until_next_tick(1.0).is_ok());
+        assert!(start.elapsed() >= Duration::from_millis(45), "{:?}", start.elapsed());
+
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(20.0, 2.0).rounding(RoundingMode::Floor).clock(clock.clone()).build();
+        bucket.drain();
+        clock.advance(Duration::from_millis(30));
+        assert!(bucket.next_tick().abs_diff(Duration::from_millis(20)) < Duration::from_micros(1));
+        assert!(matches!(
+            bucket.acquire_until_next_tick(2.0),
+            Err(TokenAcquisitionError::RateLimited { .. })
+        ));
+        clock.advance(Duration::from_millis(20));
+        assert!(bucket.acquire(1.0).is_ok());
+
+        let mut frozen = TokenBucket::new(0.0, 1.0);
+        frozen.drain();
+        assert!(frozen.acquire_until_next_tick(1.0).is_err());
+    }
+
+    /// 54. **Observed Rate Divergence**:
+    ///    - Test a bucket whose large burst lets a caller acquire 100 times its rate for a second, next to a caller staying under the rate.
+    ///    - The over-rate bucket should be flagged as diverging, the well-behaved one should not, and the flag should clear once acquisitions stop.
+    #[test]
+    fn test_rate_diverges() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let build = || {
+            TokenBucket::builder(10.0, 2000.0)
+                .clock(clock.clone())
+                .observe_rate(Duration::from_secs(1))
+                .build()
+        };
+        let mut bursty = build();
+        let mut steady = build();
+
+        for _ in 0..10 {
+            clock.advance(Duration::from_millis(100));
+            assert!(bursty.acquire(100.0).is_ok());
+            assert!(steady.acquire(0.5).is_ok());
+        }
+        assert!(bursty.observed_rate().unwrap() > 500.0);
+        assert!(bursty.rate_diverges(2.0));
+        assert!(!steady.rate_diverges(2.0));
+
+        clock.advance(Duration::from_secs(10));
+        assert!(!bursty.rate_diverges(2.0));
+        assert!(!TokenBucket::new(1.0, 1.0).rate_diverges(0.0));
+    }
+
+    /// 55. **Time-Proportional Cost**:
+    ///    - Test acquiring the cost of a 1.5 second task at 4 tokens per second, then a task the bucket can't afford, from a non-replenishing bucket.
+    ///    - The first acquisition should take exactly 6 tokens, and the second should be denied without taking any.
+    #[test]
+    fn test_acquire_for_duration() {
+        let mut bucket = TokenBucket::new(0.0, 10.0);
+        assert!(bucket.acquire_for_duration(Duration::from_millis(1500), 4.0).is_ok());
+        assert_eq!(bucket.remaining_after(0.0), Some(4.0));
+
+        assert!(bucket.acquire_for_duration(Duration::from_secs(2), 2.5).is_err());
+        assert_eq!(bucket.remaining_after(0.0), Some(4.0));
+    }
+
+    /// 56. **Invalid Token Rate**:
+    ///    - Test acquiring for a duration at a negative rate.
+    ///    - The acquisition should panic instead of computing a negative cost.
+    #[test]
+    #[should_panic(expected = "tokens_per_sec must be finite and not negative")]
+    fn test_acquire_for_duration_invalid_rate() {
+        let _ = TokenBucket::new(1.0, 10.0).acquire_for_duration(Duration::from_secs(1), -1.0);
+    }
+
+    /// 57. **Lazily Clocked Static Bucket**:
+    ///    - Test a bucket created with `const_new`, as done for a `static`, acquiring from it and letting it overflow afterwards.
+    ///    - It should start full, and only count overflow from its first acquisition, not from the UNIX epoch.
+    #[test]
+    fn test_const_new() {
+        static BUCKET: Mutex<TokenBucket> = Mutex::new(crate::token_bucket!(rate = 1000.0, burst = 10.0));
+
+        let mut bucket = BUCKET.lock().unwrap();
+        let start = Instant::now();
+        assert!(bucket.acquire(10.0).is_ok());
+        assert_eq!(bucket.overflowed(), 0.0);
+
+        thread::sleep(Duration::from_millis(50));
+        assert!(bucket.acquire(1.0).is_ok());
+        // Overflow can't exceed what accrued since the first acquisition.
+        assert!(bucket.overflowed() > 0.0);
+        assert!(bucket.overflowed() <= 1000.0 * start.elapsed().as_secs_f64());
+    }
+
+    /// 58. **Priority-Weighted Costs**:
+    ///    - Test exhausting two identical non-replenishing buckets, one with nominal high-priority acquisitions and one with low-priority acquisitions weighted by 2.5.
+    ///    - The low-priority caller should exhaust its bucket after fewer acquisitions, each deducting the scaled cost.
+    #[test]
+    fn test_acquire_weighted() {
+        let exhaust = |multiplier: f64| {
+            let mut bucket = TokenBucket::new(0.0, 10.0);
+            let admitted = (0..20).take_while(|_| bucket.acquire_weighted(1.0, multiplier).is_ok()).count();
+            (admitted, bucket.remaining_after(0.0))
+        };
+
+        assert_eq!(exhaust(1.0), (10, Some(0.0)));
+        assert_eq!(exhaust(2.5), (4, Some(0.0)));
+    }
+
+    /// 59. **Manually Driven Replenishment**:
+    ///    - Test a manual bucket driven by `tick` from a simulated loop, with an hour passing on its clock between frames.
+    ///    - Tokens should only ever come from `tick`, clamped to the burst value, never from elapsed time.
+    #[test]
+    fn test_manual_tick() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket {
+            clock: Some(Arc::new(clock.clone())),
+            ..TokenBucket::manual(5.0)
+        };
+        assert_eq!(bucket.drain(), 5.0);
+
+        let mut admitted = Vec::new();
+        for _ in 0..3 {
+            clock.advance(Duration::from_secs(3600));
+            bucket.tick(2.0);
+            admitted.push((0..5).take_while(|_| bucket.acquire(1.0).is_ok()).count());
+        }
+        assert_eq!(admitted, [2, 2, 2]);
+
+        bucket.tick(100.0);
+        assert_eq!(bucket.drain(), 5.0);
+    }
+
+    /// 60. **Error Trait Interoperability**:
+    ///    - Test propagating acquisition errors with `?` from a function returning `Box<dyn Error>`, and the messages of several errors.
+    ///    - The error should convert into the boxed error unchanged and describe the reason it occurred.
+    #[test]
+    fn test_error_trait() {
+        fn send(bucket: &mut TokenBucket, cost: f64) -> Result<f64, Box<dyn std::error::Error>> {
+            let rate = bucket.acquire(cost)?;
+            Ok(rate)
+        }
+
+        let mut bucket = TokenBucket::builder(0.0, 5.0).max_cost(4.0).build();
+        assert!(send(&mut bucket, 3.0).is_ok());
+
+        let err = send(&mut bucket, 3.0).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TokenAcquisitionError>(),
+            Some(TokenAcquisitionError::RateLimited { .. })
+        ));
+        assert!(err.to_string().starts_with("rate limited: not enough tokens available"));
+
+        let err = send(&mut bucket, 6.0).unwrap_err();
+        assert_eq!(err.to_string(), "requested 6 tokens, more than the maximum cost of 4");
+        assert_eq!(TokenAcquisitionError::Cancelled.to_string(), "acquisition cancelled");
+    }
+
+    /// 61. **Reservations Against the Future**:
+    ///    - Test reserving tokens for several future times in an emptied bucket, including reservations that would overbook it now or later, then letting the reserved times pass.
+    ///    - Overbooking reservations should be rejected, and accepted ones should be deducted once their time arrives.
+    #[test]
+    fn test_reserve_at() {
+        let start = SystemTime::UNIX_EPOCH;
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let clock = ManualClock::new(start);
+        let mut bucket = TokenBucket::builder(1.0, 10.0).clock(clock.clone()).build();
+        bucket.drain();
+
+        assert!(bucket.reserve_at(5.0, at(5)).is_ok());
+        assert_eq!(bucket.reserve_at(6.0, at(5)), Err(TokenAcquisitionError::Overbooked { count: 6.0 }));
+        assert!(bucket.reserve_at(3.0, at(8)).is_ok());
+        // Affordable at 6s on its own, but would overdraw the reservation at 8s.
+        assert_eq!(bucket.reserve_at(1.0, at(6)), Err(TokenAcquisitionError::Overbooked { count: 1.0 }));
+        assert!(matches!(bucket.reserve_at(11.0, at(60)), Err(TokenAcquisitionError::CostExceedsBurst { .. })));
+        assert_eq!(bucket.tokens_at(at(8)), 0.0);
+        assert_eq!(bucket.tokens_at(at(9)), 1.0);
+
+        clock.set(at(5));
+        assert!(bucket.acquire(1.0).is_err());
+        clock.set(at(8));
+        assert_eq!(bucket.remaining_after(0.0), Some(0.0));
+        clock.set(at(10));
+        assert!(bucket.acquire(2.0).is_ok());
+        assert!(bucket.scheduled.is_empty());
+        assert_eq!(bucket.overflowed(), 0.0);
+    }
+
+    /// 62. **Plentiful Fast Path**:
+    ///    - Test acquiring from a plain bucket, which takes the fast path, and from an identical bucket with an `on_replenish` callback, which doesn't.
+    ///    - Both should grant the same acquisitions and end up with the same tokens and overflow, whether replenishing is clamped or not.
+    #[test]
+    fn test_plentiful_fast_path() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut plain = TokenBucket::builder(10.0, 20.0).clock(clock.clone()).build();
+        let mut observed = TokenBucket::builder(10.0, 20.0).clock(clock.clone()).build();
+        observed.on_replenish(|_| {});
+
+        for (secs, count) in [(0.0, 5.0), (0.3, 12.0), (5.0, 1.0), (0.0, 19.0), (0.1, 2.5), (0.2, 0.5)] {
+            clock.advance(Duration::from_secs_f64(secs));
+            assert_eq!(plain.acquire(count).is_ok(), observed.acquire(count).is_ok());
+            assert_eq!(plain.remaining_after(0.0), observed.remaining_after(0.0));
+            assert_eq!(plain.overflowed(), observed.overflowed());
+        }
+        assert!(plain.overflowed() > 0.0);
+    }
+
+    /// 63. **Burst Fractions**:
+    ///    - Test acquiring fractions of the burst value that add up to just over 100% from a bucket that doesn't refill.
+    ///    - Every fraction but the one taking the total over 100% should be granted.
+    #[test]
+    fn test_acquire_fraction() {
+        let mut bucket = TokenBucket::new(0.0, 100.0);
+        for fraction in [0.5, 0.25, 0.2] {
+            assert!(bucket.acquire_fraction(fraction).is_ok());
+        }
+        assert!(bucket.acquire_fraction(0.06).is_err());
+        assert!(bucket.acquire_fraction(0.05).is_ok());
+        assert!(bucket.acquire_fraction(0.0).is_ok());
+    }
+
+    /// 64. **Fraction Out Of Range**:
+    ///    - Test acquiring a fraction above 1.
+    ///    - The acquisition should panic.
+    #[test]
+    #[should_panic(expected = "fraction must be between 0 and 1")]
+    fn test_acquire_fraction_out_of_range() {
+        TokenBucket::new(5.0, 100.0).acquire_fraction(1.01).ok();
+    }
+
+    /// 65. **Reconcile Overestimate**:
+    ///    - Test acquiring an estimate and reconciling it with a smaller actual cost, including one that would overfill the bucket.
+    ///    - The unused tokens should be returned, up to the burst value.
+    #[test]
+    fn test_reconcile_overestimate() {
+        let mut bucket = TokenBucket::new(0.0, 100.0);
+        assert!(bucket.acquire(50.0).is_ok());
+        bucket.reconcile(50.0, 20.0);
+        assert_eq!(bucket.remaining_after(0.0), Some(80.0));
+
+        bucket.reconcile(50.0, 0.0);
+        assert_eq!(bucket.remaining_after(0.0), Some(100.0));
+    }
+
+    /// 66. **Reconcile Underestimate**:
+    ///    - Test reconciling estimates with larger actual costs, with and without tokens accrued since the last acquisition, until the bucket can't cover the difference.
+    ///    - The missing tokens should be taken from the replenished bucket, which should stop at zero.
+    #[test]
+    fn test_reconcile_underestimate() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(10.0, 100.0).clock(clock.clone()).build();
+        assert!(bucket.acquire(90.0).is_ok());
+
+        clock.advance(Duration::from_secs(1));
+        bucket.reconcile(5.0, 20.0);
+        assert_eq!(bucket.remaining_after(0.0), Some(5.0));
+
+        bucket.reconcile(5.0, 50.0);
+        assert_eq!(bucket.remaining_after(0.0), Some(0.0));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(bucket.remaining_after(0.0), Some(10.0));
+    }
+
+    /// 67. **Idle Accrual Cap**:
+    ///    - Test a bucket with and one without an idle accrual cap after an hour of idling, then a caller retrying every few seconds for more tokens than accrue within the cap.
+    ///    - The uncapped bucket should allow its full burst while the capped one only allows the capped accrual, and retrying should see tokens accrue at the full rate.
+    #[test]
+    fn test_max_idle_accrual() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut uncapped = TokenBucket::builder(1.0, 100.0).clock(clock.clone()).build();
+        let mut capped = TokenBucket::builder(1.0, 100.0)
+            .max_idle_accrual(Duration::from_secs(10))
+            .clock(clock.clone())
+            .build();
+        uncapped.drain();
+        capped.drain();
+
+        clock.advance(Duration::from_secs(3600));
+        assert!(uncapped.acquire(100.0).is_ok());
+        assert!(capped.acquire(100.0).is_err());
+        assert!(capped.acquire(11.0).is_err());
+        assert!(capped.acquire(10.0).is_ok());
+
+        for _ in 0..5 {
+            clock.advance(Duration::from_secs(5));
+            assert!(capped.acquire(30.0).is_err());
+        }
+        clock.advance(Duration::from_secs(5));
+        assert!(capped.acquire(30.0).is_ok());
+    }
+
+    /// 68. **Detailed Acquisition**:
+    ///    - Test detailed acquisitions that are allowed after a partial refill, allowed after a clamped refill, and denied.
+    ///    - The reported levels should be the stored tokens and the replenished tokens, clamped to the burst value, and the results should match `acquire`.
+    #[test]
+    fn test_acquire_detailed() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(4.0, 10.0).clock(clock.clone()).build();
+        assert!(bucket.acquire(8.0).is_ok());
+
+        clock.advance(Duration::from_millis(500));
+        let (before, after, result) = bucket.acquire_detailed(3.0);
+        assert_eq!((before, after), (2.0, 4.0));
+        assert!(result.is_ok());
+
+        clock.advance(Duration::from_secs(5));
+        let (before, after, result) = bucket.acquire_detailed(10.0);
+        assert_eq!((before, after), (1.0, 10.0));
+        assert!(result.is_ok());
+
+        let (before, after, result) = bucket.acquire_detailed(1.0);
+        assert_eq!((before, after), (0.0, 0.0));
+        assert!(matches!(result, Err(TokenAcquisitionError::RateLimited { .. })));
+    }
+
+    /// 69. **Reference Model**:
+    ///    - Test random sequences of clock advances and costs against randomly configured buckets, once on the fast path and once on the full acquisition path, next to a straightforward reference simulation.
+    ///    - Every decision and token level should agree with the reference within float tolerance, including around the burst clamp and with zero rates and costs.
+    #[test]
+    fn test_reference_model() {
+        let mut seed = 0;
+        let mut random = || {
+            seed += 1;
+            builder::unit_interval(seed)
+        };
+
+        for _ in 0..200 {
+            let r = if random() < 0.1 { 0.0 } else { random() * 100.0 };
+            let b = 1.0 + random() * 99.0;
+            let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+            let mut fast = TokenBucket::builder(r, b).clock(clock.clone()).build();
+            let mut full = TokenBucket::builder(r, b).clock(clock.clone()).build();
+            full.on_replenish(|_| {});
+
+            // Each bucket is followed by its own reference level. It is
+            // brought in line after a decision within float tolerance of
+            // a tie, so that such a decision doesn't derail the rest.
+            let mut tokens = [b, b];
+            let tolerance = 1e-9 * b;
+            for _ in 0..200 {
+                let elapsed = Duration::from_nanos((random() * 2.0 * b / r.max(1.0) * 1e9) as u64);
+                let count = if random() < 0.1 { 0.0 } else { random() * b * 1.2 };
+                clock.advance(elapsed);
+
+                for (bucket, tokens) in IntoIterator::into_iter([&mut fast, &mut full]).zip(&mut tokens) {
+                    let available = b.min(*tokens + r * elapsed.as_secs_f64());
+                    let allowed = available >= count;
+                    *tokens = if allowed { available - count } else { available };
+
+                    let acquired = bucket.acquire(count).is_ok();
+                    let remaining = bucket.remaining_after(0.0).unwrap();
+                    if acquired != allowed {
+                        assert!((available - count).abs() <= tolerance, "{} vs {}", available, count);
+                        *tokens = remaining;
+                    }
+                    assert!((remaining - *tokens).abs() <= tolerance, "{} != {}", remaining, tokens);
+                }
+            }
+        }
+    }
+
+    /// 70. **Shadow Mode**:
+    ///    - Test acquiring well past the limit from a bucket in shadow mode, next to the same bucket enforcing the limit.
+    ///    - The shadow bucket should only fail the acquisitions the limit rejects as invalid, should count exactly those the enforcing bucket denies for lack of tokens and report them to `on_denied`, and should neither penalize nor shrink its burst for them.
+    #[test]
+    fn test_shadow_mode() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut shadow = TokenBucket::builder(1.0, 3.0).max_cost(5.0).shadow(true).clock(clock.clone()).build();
+        let mut enforcing = TokenBucket::builder(1.0, 3.0).max_cost(5.0).clock(clock.clone()).build();
+        let reported = Arc::new(Mutex::new(0));
+        let counter = Arc::clone(&reported);
+        shadow.on_denied(move |_, _| *counter.lock().unwrap() += 1);
+
+        let mut denied = 0;
+        for count in [2.0, 2.0, 1.0, 6.0, 1.0, 1.0, 1.5] {
+            clock.advance(Duration::from_millis(500));
+            match enforcing.acquire(count) {
+                Err(TokenAcquisitionError::RateLimited { .. }) => {
+                    denied += 1;
+                    assert!(shadow.acquire(count).is_ok());
+                },
+                result => assert_eq!(shadow.acquire(count), result),
+            }
+        }
+        assert_eq!(denied, 2);
+        assert_eq!(shadow.shadow_denials(), 2);
+        assert_eq!(*reported.lock().unwrap(), 2);
+        assert_eq!(shadow.remaining_after(0.0), enforcing.remaining_after(0.0));
+
+        let mut penalized = TokenBucket::builder(0.0, 4.0)
+            .shadow(true)
+            .denial_penalty(1.0, 2.0)
+            .adaptive_burst(2.0, 4.0)
+            .build();
+        assert!(penalized.acquire(3.0).is_ok());
+        assert!(penalized.acquire(3.0).is_ok());
+        assert_eq!(penalized.shadow_denials(), 1);
+        assert_eq!(penalized.remaining_after(0.0), Some(1.0));
+        assert_eq!(penalized.b, 4.0);
+    }
+
+    /// 71. **Byte Encoding**:
+    ///    - Test encoding a bucket's state as bytes, decoding it into a new bucket, and decoding malformed input.
+    ///    - The encoding should follow the documented layout, the restored bucket should keep the stored and accrued tokens, and input of the wrong length should be rejected.
+    #[test]
+    fn test_byte_encoding() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(2.0, 10.0).clock(clock.clone()).build();
+        assert!(bucket.acquire(9.5).is_ok());
+        clock.advance(Duration::from_millis(1500));
+
+        let bytes = bucket.to_bytes();
+        assert_eq!(bytes[..8], 0.5f64.to_le_bytes());
+        assert_eq!(bytes[8..], 1_500_000_000u64.to_le_bytes());
+        assert_eq!(BucketState::from_bytes(&bytes), Some(bucket.snapshot()));
+
+        let config = TokenBucket::builder(2.0, 10.0).clock(clock.clone());
+        let restored = TokenBucket::from_bytes(config.clone(), &bytes).unwrap();
+        assert_eq!(restored.remaining_after(0.0), Some(3.5));
+
+        assert!(TokenBucket::from_bytes(config.clone(), &bytes[..15]).is_none());
+        assert!(TokenBucket::from_bytes(config, &[0; 17]).is_none());
+    }
+
+    /// 72. **Deny Reasons**:
+    ///    - Test every kind of denial, from an empty bucket, a capped cost, an unreachable burst, an exhausted window, a fractional count, a cancelled wait and an overbooked reservation.
+    ///    - Each error should carry the matching reason, and the reasons should have distinct codes.
+    #[test]
+    fn test_deny_reasons() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(1.0, 2.0)
+            .clock(clock.clone())
+            .max_cost(1.5)
+            .integer_only(true)
+            .build();
+        let reason = |result: Result<_, TokenAcquisitionError>| result.unwrap_err().reason();
+
+        assert_eq!(reason(bucket.acquire(1.75)), DenyReason::ExceedsCapacity);
+        assert_eq!(reason(bucket.acquire(0.5)), DenyReason::InvalidCount);
+        assert_eq!(reason(bucket.acquire_wait(3.0)), DenyReason::ExceedsCapacity);
+
+        bucket.drain();
+        assert_eq!(reason(bucket.acquire(1.0)), DenyReason::InsufficientTokens);
+        assert_eq!(reason(bucket.acquire_wait_cancellable(1.0, &AtomicBool::new(true))), DenyReason::Cancelled);
+        assert_eq!(bucket.reserve_at(1.0, SystemTime::UNIX_EPOCH).unwrap_err().reason(), DenyReason::Overbooked);
+
+        let mut hybrid = HybridLimiter::new(TokenBucket::builder(1.0, 2.0).clock(clock).build(), 1.0, Duration::from_secs(60));
+        assert!(hybrid.acquire(1.0).is_ok());
+        assert_eq!(reason(hybrid.acquire(1.0)), DenyReason::WindowExhausted);
+
+        let reasons = [
+            DenyReason::InsufficientTokens,
+            DenyReason::ExceedsCapacity,
+            DenyReason::WindowExhausted,
+            DenyReason::InvalidCount,
+            DenyReason::Cancelled,
+            DenyReason::Overbooked,
+            DenyReason::Vetoed,
+            DenyReason::Reentrant,
+        ];
+        let codes: std::collections::HashSet<_> = reasons.iter().map(DenyReason::as_str).collect();
+        assert_eq!(codes.len(), reasons.len());
+    }
+
+    /// 73. **Rounding Tolerance**:
+    ///    - Test acquisitions, fullness and emptiness just inside and just outside the default tolerance, a wider one, and none at all, then the wait for tokens with a wider tolerance.
+    ///    - Shortfalls within the tolerance should be allowed and leave the bucket exactly empty, while larger ones should be denied, and no wait should be needed once the shortfall is within the tolerance.
+    #[test]
+    fn test_epsilon() {
+        let mut bucket = TokenBucket::new(0.0, 1.0);
+        assert!(bucket.acquire(1.0 + 2.0 * EPSILON).is_err());
+        assert!(bucket.peek_allow(1.0 + EPSILON / 2.0));
+        assert!(bucket.acquire(1.0 + EPSILON / 2.0).is_ok());
+        assert_eq!(bucket.remaining_after(0.0), Some(0.0));
+        assert!(bucket.is_empty() && !bucket.is_full());
+
+        bucket.set_tokens(1.0 - EPSILON / 2.0);
+        assert!(bucket.is_full());
+        bucket.set_tokens(1.0 - 2.0 * EPSILON);
+        assert!(!bucket.is_full());
+        bucket.set_tokens(2.0 * EPSILON);
+        assert!(!bucket.is_empty());
+
+        let mut wide = TokenBucket::builder(0.0, 1.0).epsilon(0.01).build();
+        assert!(wide.acquire(0.995).is_ok());
+        assert!(wide.is_empty());
+        assert!(wide.acquire(0.02).is_err());
+        assert!(wide.acquire(0.01).is_ok());
+        assert_eq!(wide.remaining_after(0.0), Some(0.0));
+
+        let mut exact = TokenBucket::builder(0.0, 0.3).epsilon(0.0).build();
+        assert!(exact.acquire(0.1 + 0.2).is_err());
+        assert!(exact.acquire(0.3).is_ok());
+        assert!(exact.is_empty());
+
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut waiting = TokenBucket::builder(1.0, 1.0).epsilon(0.01).clock(clock.clone()).build();
+        waiting.set_tokens(0.995);
+        assert_eq!(waiting.time_until_available(1.0), Duration::ZERO);
+        assert_eq!(waiting.next_available_at(1.005), Some(SystemTime::UNIX_EPOCH));
+        waiting.set_tokens(0.5);
+        assert_eq!(waiting.time_until_available(1.0), Duration::from_millis(500));
+        clock.advance(Duration::from_millis(490));
+        assert!(waiting.acquire(1.0).is_ok());
+    }
+
+    /// 74. **Logged Acquisition**:
+    ///    - Test allowed, rate limited and invalid acquisitions logged to a buffer.
+    ///    - Every call should write exactly one line describing its outcome, and return the same result as `acquire`.
+    #[test]
+    fn test_acquire_logged() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(2.0, 1.0).max_cost(1.0).clock(clock.clone()).build();
+        let mut log = Vec::new();
+
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(bucket.acquire_logged(1.0, &mut log), Ok(2.0));
+        clock.advance(Duration::from_millis(250));
+        assert!(bucket.acquire_logged(1.0, &mut log).is_err());
+        assert!(bucket.acquire_logged(2.0, &mut log).is_err());
+
+        assert_eq!(
+            String::from_utf8(log).unwrap(),
+            "rate/allow: 2, true\n\
+             rate/allow: 4, false\n\
+             error: requested 2 tokens, more than the maximum cost of 1\n"
+        );
+    }
+
+    /// 75. **Grace Allowance**:
+    ///    - Test a bucket with a grace allowance of three acquisitions, before and after resetting it.
+    ///    - The first three acquisitions should succeed without taking tokens, after which the bucket should limit as usual until it is reset.
+    #[test]
+    fn test_grace() {
+        let mut bucket = TokenBucket::builder(0.0, 2.0).grace(3).build();
+        for _ in 0..3 {
+            assert!(bucket.acquire(5.0).is_ok());
+        }
+        assert!(bucket.acquire(2.0).is_ok());
+        assert!(matches!(bucket.acquire(1.0), Err(TokenAcquisitionError::RateLimited { .. })));
+
+        bucket.reset();
+        for _ in 0..3 {
+            assert!(bucket.acquire(1.0).is_ok());
+        }
+        assert_eq!(bucket.remaining_after(0.0), Some(2.0));
+        assert!(bucket.acquire(3.0).is_err());
+    }
+
+    /// 76. **Burst Contribution**:
+    ///    - Test a client acquiring at the sustained rate from an empty bucket, then one dipping into tokens saved up while idle.
+    ///    - Acquisitions covered by the last second's accrual should take nothing from the burst, while the rest should report exactly the tokens taken from above the one-second line.
+    #[test]
+    fn test_acquire_with_burst() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(10.0, 100.0).clock(clock.clone()).build();
+        bucket.drain();
+
+        for _ in 0..5 {
+            clock.advance(Duration::from_secs(1));
+            assert_eq!(bucket.acquire_with_burst(10.0), (Ok(1.0), 0.0));
+        }
+
+        clock.advance(Duration::from_secs(4));
+        assert_eq!(bucket.acquire_with_burst(5.0), (Ok(0.25), 5.0));
+        assert_eq!(bucket.acquire_with_burst(30.0).1, 25.0);
+        assert_eq!(bucket.acquire_with_burst(5.0).1, 0.0);
+        assert_eq!(bucket.acquire_with_burst(10.0).1, 0.0);
+    }
+
+    /// 77. **Swapping Clocks**:
+    ///    - Test swapping a running bucket to a clock far ahead, letting that clock jump forward, and swapping to a clock behind.
+    ///    - The tokens accrued before each swap should be kept, and replenishment should follow the new clock from there, up to the burst value.
+    #[test]
+    fn test_set_clock() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(2.0, 10.0).clock(clock.clone()).build();
+        bucket.drain();
+        clock.advance(Duration::from_secs(1));
+
+        let ahead = ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(86_400));
+        bucket.set_clock(ahead.clone());
+        assert_eq!(bucket.remaining_after(0.0), Some(2.0));
+        ahead.advance(Duration::from_millis(1500));
+        assert_eq!(bucket.remaining_after(0.0), Some(5.0));
+
+        ahead.advance(Duration::from_secs(3_600));
+        assert_eq!(bucket.remaining_after(0.0), Some(10.0));
+        assert!(bucket.acquire(10.0).is_ok());
+
+        bucket.set_clock(clock.clone());
+        assert_eq!(bucket.remaining_after(0.0), Some(0.0));
+        clock.advance(Duration::from_secs(2));
+        assert!(bucket.acquire(4.0).is_ok());
+        assert!(bucket.acquire(1.0).is_err());
+    }
+
+    /// 78. **Precise Pacing**:
+    ///    - Test pacing 400 packets at 4,000 per second, sleeping for most of every wait and spinning through the rest, then waiting for more tokens than accrue within the bucket's idle accrual limit.
+    ///    - The packets should take at least as long as the rate dictates, no 40 consecutive packets should be sent faster than it, and the capped wait should still end.
+    #[test]
+    fn test_acquire_wait_precise() {
+        let mut pacer = TokenBucket::new(4_000.0, 1.0);
+        pacer.drain();
+
+        let start = Instant::now();
+        let sent: Vec<_> = (0..400)
+            .map(|_| {
+                assert!(pacer.acquire_wait_precise(1.0, Duration::from_micros(100)).is_ok());
+                Instant::now()
+            })
+            .collect();
+        assert!(start.elapsed() >= Duration::from_millis(99), "{:?}", start.elapsed());
+        for window in sent.windows(40) {
+            let spacing = window[39] - window[0];
+            assert!(spacing >= Duration::from_micros(9_700), "{:?}", spacing);
+        }
+
+        let mut capped = TokenBucket::builder(100.0, 1.0).max_idle_accrual(Duration::from_millis(2)).build();
+        capped.drain();
+        let start = Instant::now();
+        assert!(capped.acquire_wait_precise(1.0, Duration::from_millis(50)).is_ok());
+        assert!(start.elapsed() >= Duration::from_millis(9), "{:?}", start.elapsed());
+    }
+
+    /// 79. **Decisions**:
+    ///    - Test deciding acquisitions on a drained bucket before and after its tokens are due, and acquisitions that can never succeed.
+    ///    - A throttled acquisition should retry after exactly the time its tokens take to accrue, and impossible ones should never be retried.
+    #[test]
+    fn test_decide() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(10.0, 5.0).max_cost(4.0).clock(clock.clone()).build();
+        assert_eq!(bucket.decide(4.0), Decision::Allow);
+
+        assert_eq!(bucket.decide(3.0), Decision::Throttle {
+            retry_after: Duration::from_millis(200),
+        });
+        clock.advance(Duration::from_millis(100));
+        assert_eq!(bucket.decide(3.0), Decision::Throttle {
+            retry_after: Duration::from_millis(100),
+        });
+        clock.advance(Duration::from_millis(100));
+        assert_eq!(bucket.decide(3.0), Decision::Allow);
+
+        assert_eq!(bucket.decide(4.5), Decision::Throttle { retry_after: Duration::MAX });
+        let mut small = TokenBucket::new(10.0, 2.0);
+        assert_eq!(small.decide(3.0), Decision::Throttle { retry_after: Duration::MAX });
+    }
+
+    /// 80. **Invalid Tick**:
+    ///    - Test ticking a manual bucket by a negative number of tokens.
+    ///    - The tick should panic instead of draining the bucket.
+    #[test]
+    #[should_panic(expected = "ticked tokens must be finite and not negative")]
+    fn test_tick_negative() {
+        TokenBucket::manual(10.0).tick(-1.0);
     }
 }
\ No newline at end of file
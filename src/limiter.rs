@@ -0,0 +1,84 @@
+use crate::{
+    AtomicTokenBucket, HybridLimiter, LocalTokenBucket, MultiRateLimiter, QuotaLimiter, SharedTokenBucket, TieredTokenBucket, TokenAcquisitionResult, TokenBucket,
+    TokenBucketSet,
+};
+
+/// Represents a rate limiting strategy.
+///
+/// Writing code against `Limiter` rather than a concrete type allows
+/// swapping the limiting strategy, e.g. through configuration, without
+/// touching the call sites. Every limiter in this crate implements it,
+/// alongside its own concrete methods.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::{HybridLimiter, Limiter, TokenBucket};
+/// fn send_all(limiter: &mut impl Limiter, messages: &[&str]) -> usize {
+///     messages.iter().filter(|_| limiter.acquire(1.0).is_ok()).count()
+/// }
+///
+/// let messages = ["a", "b", "c"];
+/// assert_eq!(send_all(&mut TokenBucket::new(1.0, 2.0), &messages), 2);
+///
+/// let bucket = TokenBucket::new(1.0, 10.0);
+/// assert_eq!(send_all(&mut HybridLimiter::per_minute(bucket, 1.0), &messages), 1);
+/// ```
+pub trait Limiter {
+    /// Attempts to acquire `count` tokens from the limiter.
+    fn acquire(&mut self, count: f64) -> TokenAcquisitionResult;
+}
+
+impl Limiter for TokenBucket {
+    fn acquire(&mut self, count: f64) -> TokenAcquisitionResult {
+        TokenBucket::acquire(self, count)
+    }
+}
+
+impl Limiter for HybridLimiter {
+    fn acquire(&mut self, count: f64) -> TokenAcquisitionResult {
+        HybridLimiter::acquire(self, count)
+    }
+}
+
+impl Limiter for MultiRateLimiter {
+    fn acquire(&mut self, count: f64) -> TokenAcquisitionResult {
+        MultiRateLimiter::acquire(self, count)
+    }
+}
+
+impl Limiter for AtomicTokenBucket {
+    fn acquire(&mut self, count: f64) -> TokenAcquisitionResult {
+        AtomicTokenBucket::acquire(self, count)
+    }
+}
+
+impl Limiter for TokenBucketSet {
+    fn acquire(&mut self, count: f64) -> TokenAcquisitionResult {
+        TokenBucketSet::acquire(self, count)
+    }
+}
+
+impl Limiter for SharedTokenBucket {
+    fn acquire(&mut self, count: f64) -> TokenAcquisitionResult {
+        SharedTokenBucket::acquire(self, count)
+    }
+}
+
+impl Limiter for LocalTokenBucket {
+    fn acquire(&mut self, count: f64) -> TokenAcquisitionResult {
+        LocalTokenBucket::acquire(self, count)
+    }
+}
+
+impl Limiter for TieredTokenBucket {
+    fn acquire(&mut self, count: f64) -> TokenAcquisitionResult {
+        TieredTokenBucket::acquire(self, count)
+    }
+}
+
+impl Limiter for QuotaLimiter {
+    fn acquire(&mut self, count: f64) -> TokenAcquisitionResult {
+        QuotaLimiter::acquire(self, count)
+    }
+}
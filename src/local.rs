@@ -0,0 +1,157 @@
+use crate::{TokenAcquisitionError, TokenAcquisitionResult, TokenBucket};
+use std::cell::Cell;
+use std::time::Duration;
+
+/// Represents a [TokenBucket](struct.TokenBucket.html) that can be
+/// acquired from through a shared reference on a single thread.
+///
+/// Share it with `Rc<LocalTokenBucket>` in single-threaded code, e.g.
+/// on a `!Send` async executor. Unlike `Rc<RefCell<TokenBucket>>`, an
+/// acquisition can't panic because the bucket is already borrowed, and
+/// unlike a [SharedTokenBucket](struct.SharedTokenBucket.html) there is
+/// no mutex to lock.
+///
+/// A LocalTokenBucket is not thread-safe: it can be moved to another
+/// thread, but never used from several threads at once. Use a
+/// SharedTokenBucket for that.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::{LocalTokenBucket, TokenBucket};
+/// use std::rc::Rc;
+///
+/// let bucket = Rc::new(LocalTokenBucket::new(TokenBucket::new(5.0, 1.0)));
+/// let other = Rc::clone(&bucket);
+/// assert!(bucket.acquire(1.0).is_ok());
+/// assert!(other.acquire(1.0).is_err());
+/// ```
+pub struct LocalTokenBucket {
+    // Represents the wrapped bucket. It is only taken out for the
+    // duration of an acquisition, so it is `None` while one is in
+    // progress.
+    bucket: Cell<Option<TokenBucket>>,
+}
+
+impl LocalTokenBucket {
+    /// Returns a new LocalTokenBucket wrapping `bucket`.
+    pub fn new(bucket: TokenBucket) -> LocalTokenBucket {
+        LocalTokenBucket {
+            bucket: Cell::new(Some(bucket)),
+        }
+    }
+
+    /// Attempts to acquire `count` tokens from the bucket. See
+    /// [TokenBucket.acquire()](struct.TokenBucket.html#method.acquire).
+    ///
+    /// An acquisition made from within one of the bucket's own
+    /// callbacks, e.g. from
+    /// [on_denied()](struct.TokenBucket.html#method.on_denied), can't
+    /// see the bucket and returns
+    /// `Err(TokenAcquisitionError::Reentrant)`.
+    pub fn acquire(&self, count: f64) -> TokenAcquisitionResult {
+        self.with(|bucket| bucket.acquire(count))?
+    }
+
+    // Performs a single step of a blocking acquisition, see
+    // `TokenBucket::acquire_blocking_step`. A reentrant acquisition
+    // fails for good rather than being retried.
+    pub(crate) fn acquire_blocking_step(&self, count: f64) -> Result<TokenAcquisitionResult, Duration> {
+        self.with(|bucket| bucket.acquire_blocking_step(count))
+            .unwrap_or_else(|err| Ok(Err(err)))
+    }
+
+    // Calls `f` with the wrapped bucket, or returns
+    // `Err(TokenAcquisitionError::Reentrant)` if an acquisition is
+    // already in progress. The bucket is put back even if `f` panics.
+    fn with<T>(&self, f: impl FnOnce(&mut TokenBucket) -> T) -> Result<T, TokenAcquisitionError> {
+        struct Restore<'a> {
+            cell:   &'a Cell<Option<TokenBucket>>,
+            bucket: Option<TokenBucket>,
+        }
+
+        impl Drop for Restore<'_> {
+            fn drop(&mut self) {
+                self.cell.set(self.bucket.take());
+            }
+        }
+
+        let bucket = self.bucket.take().ok_or(TokenAcquisitionError::Reentrant)?;
+        let mut restore = Restore {
+            cell:   &self.bucket,
+            bucket: Some(bucket),
+        };
+        Ok(f(restore.bucket.as_mut().expect("token bucket restored early")))
+    }
+
+    /// Returns the wrapped TokenBucket.
+    pub fn into_inner(self) -> TokenBucket {
+        self.bucket
+            .into_inner()
+            .expect("token bucket taken during acquisition")
+    }
+}
+
+impl From<TokenBucket> for LocalTokenBucket {
+    fn from(bucket: TokenBucket) -> LocalTokenBucket {
+        LocalTokenBucket::new(bucket)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::rc::Rc;
+
+    // This module contains unit tests for the LocalTokenBucket implementation.
+
+    /// 1. **Shared Local Acquisition**:
+    ///    - Test acquiring through two `Rc` handles of the same bucket, then unwrapping it.
+    ///    - Both handles should draw from the same tokens, and the unwrapped bucket should keep its state.
+    #[test]
+    fn test_rc_shared_acquire() {
+        let bucket = Rc::new(LocalTokenBucket::new(TokenBucket::new(0.0, 2.0)));
+        let other = Rc::clone(&bucket);
+
+        assert!(bucket.acquire(1.0).is_ok());
+        assert!(other.acquire(1.0).is_ok());
+        assert!(bucket.acquire(1.0).is_err());
+
+        drop(other);
+        let mut inner = Rc::try_unwrap(bucket).ok().unwrap().into_inner();
+        assert_eq!(inner.drain(), 0.0);
+    }
+
+    thread_local! {
+        static BUCKET: RefCell<Option<Rc<LocalTokenBucket>>> = const { RefCell::new(None) };
+        static REENTERED: Cell<Option<TokenAcquisitionResult>> = const { Cell::new(None) };
+    }
+
+    /// 2. **Reentrant Acquisition**:
+    ///    - Test acquiring from a bucket within its own denial callback, then a callback that panics.
+    ///    - The nested acquisition should fail as reentrant right away, and the bucket should survive the panic.
+    #[test]
+    fn test_reentrant_acquire() {
+        let mut inner = TokenBucket::new(0.0, 1.0);
+        inner.on_denied(|_, _| {
+            let nested = BUCKET.with(|bucket| bucket.borrow().as_ref().unwrap().acquire(1.0));
+            REENTERED.with(|reentered| reentered.set(Some(nested)));
+        });
+        let bucket = Rc::new(LocalTokenBucket::new(inner));
+        BUCKET.with(|cell| *cell.borrow_mut() = Some(Rc::clone(&bucket)));
+
+        assert!(bucket.acquire(1.0).is_ok());
+        assert!(bucket.acquire(1.0).is_err());
+        assert_eq!(REENTERED.with(Cell::take), Some(Err(TokenAcquisitionError::Reentrant)));
+
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            bucket.with(|_| panic!("callback failed")).unwrap();
+        }));
+        assert!(panicked.is_err());
+        assert!(bucket.acquire(1.0).is_err());
+        BUCKET.with(|cell| cell.borrow_mut().take());
+        assert_eq!(Rc::try_unwrap(bucket).ok().unwrap().into_inner().drain(), 0.0);
+    }
+}
@@ -0,0 +1,345 @@
+use crate::{LocalTokenBucket, TokenAcquisitionResult};
+use std::cell::RefCell;
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+/// Represents a single timer driver shared by the waiting acquisitions
+/// of several [LocalTokenBucket](struct.LocalTokenBucket.html)s on a
+/// single-threaded async executor.
+///
+/// Buckets are registered with
+/// [register()](struct.LocalScheduler.html#method.register) and
+/// acquired from with
+/// [acquire()](struct.LocalScheduler.html#method.acquire). Instead of
+/// every waiting acquisition sleeping on its own timer, the future
+/// returned by [run()](struct.LocalScheduler.html#method.run) sleeps
+/// until the earliest point at which any of them can be afforded, then
+/// wakes exactly the waiters their buckets can serve. Waiters on the
+/// same bucket are served in arrival order.
+///
+/// This is the `!Send` counterpart of
+/// [SharedTokenBucket.acquire_notified()](struct.SharedTokenBucket.html#method.acquire_notified).
+/// Cloning a LocalScheduler is cheap and returns another handle to the
+/// same scheduler.
+///
+/// # Example
+///
+/// ```ignore
+/// # use tokenbucket::{LocalScheduler, LocalTokenBucket, TokenBucket};
+/// use std::rc::Rc;
+///
+/// let scheduler = LocalScheduler::new();
+/// let uploads = scheduler.register(Rc::new(LocalTokenBucket::new(TokenBucket::new(5.0, 10.0))));
+/// tokio::task::spawn_local({
+///     let scheduler = scheduler.clone();
+///     async move { scheduler.run(tokio::time::sleep).await }
+/// });
+/// scheduler.acquire(uploads, 10.0).await?;
+/// ```
+#[derive(Clone, Default)]
+pub struct LocalScheduler {
+    state: Rc<RefCell<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    buckets: Vec<Rc<LocalTokenBucket>>,
+    // Represents the id handed to the next waiter to arrive.
+    next_id: u64,
+    // Represents the waiters, in arrival order.
+    waiting: Vec<Waiting>,
+    // Represents the waker of the driver, once it has been polled.
+    driver:  Option<Waker>,
+    // Represents whether a waiter arrived since the driver last
+    // computed its sleep.
+    changed: bool,
+}
+
+struct Waiting {
+    id:     u64,
+    key:    usize,
+    cost:   f64,
+    // Represents the outcome of the acquisition, once the driver has
+    // made it.
+    result: Option<TokenAcquisitionResult>,
+    waker:  Option<Waker>,
+}
+
+// Represents a single caller of `acquire`. Dropping it withdraws the
+// waiter.
+struct Acquire {
+    state: Rc<RefCell<State>>,
+    id:    u64,
+}
+
+impl LocalScheduler {
+    /// Returns a new LocalScheduler without any buckets.
+    pub fn new() -> LocalScheduler {
+        LocalScheduler::default()
+    }
+
+    /// Registers `bucket` with the scheduler and returns the key to
+    /// acquire from it with.
+    pub fn register(&self, bucket: Rc<LocalTokenBucket>) -> usize {
+        let mut state = self.state.borrow_mut();
+        state.buckets.push(bucket);
+        state.buckets.len() - 1
+    }
+
+    /// Returns the bucket registered under `key`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if no bucket is registered under `key`.
+    pub fn bucket(&self, key: usize) -> Rc<LocalTokenBucket> {
+        Rc::clone(&self.state.borrow().buckets[key])
+    }
+
+    /// Acquires `count` tokens from the bucket registered under `key`,
+    /// asynchronously waiting until the driver can serve the
+    /// acquisition.
+    ///
+    /// The acquisition is attempted right away unless earlier waiters
+    /// on the same bucket are still waiting, and the future only waits
+    /// if the bucket is rate limited. An acquisition that can never
+    /// succeed, e.g. because `count` exceeds the burst value, returns
+    /// its error right away. Waiting acquisitions are only served while
+    /// [run()](struct.LocalScheduler.html#method.run) is being polled.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if no bucket is registered under `key`.
+    pub fn acquire(&self, key: usize, count: f64) -> impl Future<Output = TokenAcquisitionResult> {
+        let bucket = self.bucket(key);
+        let queued = {
+            let state = self.state.borrow();
+            state.waiting.iter().any(|waiting| waiting.key == key && waiting.result.is_none())
+        };
+        let result = if queued {
+            None
+        } else {
+            bucket.acquire_blocking_step(count).ok()
+        };
+
+        let mut state = self.state.borrow_mut();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.waiting.push(Waiting {
+            id,
+            key,
+            cost: count,
+            result,
+            waker: None,
+        });
+        if state.waiting.last().unwrap().result.is_none() {
+            state.changed = true;
+            if let Some(driver) = state.driver.take() {
+                driver.wake();
+            }
+        }
+
+        Acquire {
+            state: Rc::clone(&self.state),
+            id,
+        }
+    }
+
+    /// Returns the number of acquisitions waiting to be served.
+    pub fn waiting(&self) -> usize {
+        let state = self.state.borrow();
+        state.waiting.iter().filter(|waiting| waiting.result.is_none()).count()
+    }
+
+    /// Drives the waiting acquisitions of every registered bucket,
+    /// sleeping with `sleep` until the next of them can be served.
+    ///
+    /// The returned future never completes, so spawn it once on the
+    /// executor, e.g. with `spawn_local`, next to the tasks acquiring
+    /// through the scheduler. Only one sleep is pending at any time, and
+    /// it is restarted whenever a new waiter arrives. See
+    /// [TokenBucket.acquire_async()](struct.TokenBucket.html#method.acquire_async)
+    /// for how to provide `sleep`.
+    pub async fn run<F, Fut>(&self, mut sleep: F)
+    where
+        F: FnMut(Duration) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let mut timer: Option<Pin<Box<Fut>>> = None;
+        future::poll_fn(|cx| loop {
+            {
+                let mut state = self.state.borrow_mut();
+                state.driver = Some(cx.waker().clone());
+                if state.changed {
+                    state.changed = false;
+                    timer = None;
+                }
+            }
+
+            if timer.is_none() {
+                match self.serve() {
+                    Some(wait) => timer = Some(Box::pin(sleep(wait))),
+                    None => return Poll::Pending,
+                }
+            }
+            match timer.as_mut().unwrap().as_mut().poll(cx) {
+                Poll::Ready(()) => timer = None,
+                Poll::Pending => return Poll::Pending,
+            }
+        })
+        .await
+    }
+
+    // Serves every waiter at the head of its bucket's queue that the
+    // bucket can afford, in arrival order, and returns how long to
+    // sleep until the next one can be served, if ever.
+    fn serve(&self) -> Option<Duration> {
+        let (buckets, heads) = {
+            let state = self.state.borrow();
+            let heads: Vec<_> = state
+                .waiting
+                .iter()
+                .filter(|waiting| waiting.result.is_none())
+                .map(|waiting| (waiting.id, waiting.key, waiting.cost))
+                .collect();
+            (state.buckets.clone(), heads)
+        };
+
+        // The bucket is acquired from without the state borrowed, in
+        // case one of its callbacks acquires through the scheduler.
+        let mut blocked = Vec::new();
+        let mut wait: Option<Duration> = None;
+        for (id, key, cost) in heads {
+            if blocked.contains(&key) {
+                continue;
+            }
+            match buckets[key].acquire_blocking_step(cost) {
+                Ok(result) => {
+                    let waker = {
+                        let mut state = self.state.borrow_mut();
+                        let waiting = state.waiting.iter_mut().find(|waiting| waiting.id == id);
+                        waiting.and_then(|waiting| {
+                            waiting.result = Some(result);
+                            waiting.waker.take()
+                        })
+                    };
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                },
+                Err(until) => {
+                    blocked.push(key);
+                    if until < Duration::MAX {
+                        wait = Some(wait.map_or(until, |wait| wait.min(until)));
+                    }
+                },
+            }
+        }
+        wait
+    }
+}
+
+impl Future for Acquire {
+    type Output = TokenAcquisitionResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<TokenAcquisitionResult> {
+        let mut state = self.state.borrow_mut();
+        let index = state
+            .waiting
+            .iter()
+            .position(|waiting| waiting.id == self.id)
+            .expect("waiter polled after completion");
+
+        match state.waiting[index].result.take() {
+            Some(result) => {
+                state.waiting.remove(index);
+                Poll::Ready(result)
+            },
+            None => {
+                state.waiting[index].waker = Some(cx.waker().clone());
+                Poll::Pending
+            },
+        }
+    }
+}
+
+impl Drop for Acquire {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.state.try_borrow_mut() {
+            state.waiting.retain(|waiting| waiting.id != self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use crate::{TokenAcquisitionError, TokenBucket};
+    use std::cell::Cell;
+    use std::thread;
+    use std::time::Instant;
+
+    // This module contains unit tests for the LocalScheduler implementation.
+
+    /// 1. **Shared Local Timer**:
+    ///    - Test several local waiters on two empty buckets with different rates, driven by a single scheduler on one thread, next to an acquisition that is served right away and one that can never be.
+    ///    - Every waiter should be served in order per bucket once its tokens are available, with the driver sleeping at most once per wake-up.
+    #[test]
+    fn test_local_scheduler() {
+        let scheduler = LocalScheduler::new();
+        let fast = scheduler.register(Rc::new(LocalTokenBucket::new(TokenBucket::new(100.0, 1.0))));
+        let slow = scheduler.register(Rc::new(LocalTokenBucket::new(TokenBucket::new(50.0, 1.0))));
+        assert!(scheduler.bucket(fast).acquire(1.0).is_ok());
+        assert!(scheduler.bucket(slow).acquire(1.0).is_ok());
+
+        let full = scheduler.register(Rc::new(LocalTokenBucket::new(TokenBucket::new(0.0, 1.0))));
+        assert!(block_on(scheduler.acquire(full, 1.0)).is_ok());
+        assert!(matches!(
+            block_on(scheduler.acquire(full, 2.0)),
+            Err(TokenAcquisitionError::CostExceedsBurst { .. })
+        ));
+
+        let start = Instant::now();
+        let served = Rc::new(RefCell::new(Vec::new()));
+        let mut waiters: Vec<Pin<Box<dyn Future<Output = ()>>>> = [fast, slow, fast, slow, fast]
+            .iter()
+            .enumerate()
+            .map(|(i, &key)| {
+                let acquire = scheduler.acquire(key, 1.0);
+                let served = Rc::clone(&served);
+                Box::pin(async move {
+                    assert!(acquire.await.is_ok());
+                    served.borrow_mut().push(i);
+                }) as Pin<Box<dyn Future<Output = ()>>>
+            })
+            .collect();
+        assert_eq!(scheduler.waiting(), 5);
+
+        let sleeps = Cell::new(0);
+        let mut driver = Box::pin(scheduler.run(|duration| {
+            sleeps.set(sleeps.get() + 1);
+            async move { thread::sleep(duration) }
+        }));
+        block_on(future::poll_fn(|cx| {
+            let _ = driver.as_mut().poll(cx);
+            waiters.retain_mut(|waiter| waiter.as_mut().poll(cx).is_pending());
+            if waiters.is_empty() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        }));
+
+        let served = served.borrow();
+        assert_eq!(served.len(), 5);
+        let position = |i| served.iter().position(|&j| j == i).unwrap();
+        assert!(position(0) < position(2) && position(2) < position(4));
+        assert!(position(1) < position(3));
+        assert!(sleeps.get() <= 5);
+        assert!(start.elapsed() >= Duration::from_millis(40));
+        assert_eq!(scheduler.waiting(), 0);
+    }
+}
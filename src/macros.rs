@@ -0,0 +1,27 @@
+/// Creates a [TokenBucket](struct.TokenBucket.html) from a rate and a
+/// burst value in a constant context, e.g. in a `static`.
+///
+/// `token_bucket!(rate = r, burst = b)` expands to
+/// [TokenBucket::const_new(r, b)](struct.TokenBucket.html#method.const_new),
+/// which documents how the bucket is clocked. Naming both values makes
+/// it impossible to swap them by accident.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::Mutex;
+/// use tokenbucket::{token_bucket, TokenBucket};
+///
+/// // Allow 10 requests per second, bursting up to 100.
+/// static API_LIMIT: Mutex<TokenBucket> = Mutex::new(token_bucket!(rate = 10.0, burst = 100.0));
+///
+/// let mut limiter = API_LIMIT.lock().unwrap();
+/// assert!(limiter.acquire(100.0).is_ok());
+/// assert!(limiter.acquire(10.0).is_err());
+/// ```
+#[macro_export]
+macro_rules! token_bucket {
+    (rate = $rate:expr, burst = $burst:expr $(,)?) => {
+        $crate::TokenBucket::const_new($rate, $burst)
+    };
+}
@@ -1,20 +1,15 @@
 use tokenbucket::TokenBucket;
-use tokenbucket::TokenAcquisitionResult;
+use std::io;
 use std::{thread, time};
 
 // Will acquire tokens at the specified rate for the specified duration.
-// After each acquisition, the AcquisitionResult will be printed.
+// After each acquisition, the result will be printed.
 fn run(bucket: &mut TokenBucket, rate: u32, duration: u32) {
+    let mut stdout = io::stdout();
     for _ in 0..=(rate * duration) {
-        // Acquire 1 token from the bucket.
-        let acquisition: TokenAcquisitionResult = bucket.acquire(1.0);
+        // Acquire 1 token from the bucket and print the result.
+        let _ = bucket.acquire_logged(1.0, &mut stdout);
 
-        // Determine the acquisition result.
-        match acquisition {
-            Ok(rate)  => println!("rate/allow: {}, true", rate),
-            Err(rate) => println!("rate/allow: {}, false", rate),
-        }
-        
         // Sleep for enough time to match the desired rate/second.
         thread::sleep(time::Duration::from_micros(
             (1000000.0 * (1.0 / rate as f64)) as u64,
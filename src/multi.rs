@@ -0,0 +1,161 @@
+use crate::{TokenAcquisitionResult, TokenBucket};
+use std::ops::BitAnd;
+
+/// Represents several [TokenBucket](struct.TokenBucket.html)s that must
+/// all allow an acquisition, e.g. a per-second and a per-minute limit
+/// on the same upstream.
+///
+/// An acquisition only consumes from the buckets if every one of them
+/// can afford it, so a denial by one limit never uses up the tokens of
+/// another. Combine buckets with the `&` operator, or with
+/// [new()](#method.new).
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::TokenBucket;
+/// // Allow bursts of 10 per second, but no more than 600 per minute.
+/// let per_second = TokenBucket::new(10.0, 10.0);
+/// let per_minute = TokenBucket::new(10.0, 600.0);
+/// let mut limiter = per_second & per_minute;
+///
+/// for _ in 0..10 {
+///     assert!(limiter.acquire(1.0).is_ok());
+/// }
+/// assert!(limiter.acquire(1.0).is_err());
+/// assert!(limiter.buckets()[1].peek_allow(590.0));
+/// ```
+pub struct MultiRateLimiter {
+    buckets: Vec<TokenBucket>,
+}
+
+impl MultiRateLimiter {
+    /// Returns a new MultiRateLimiter requiring every bucket in
+    /// `buckets` to allow an acquisition. With no buckets every
+    /// acquisition succeeds.
+    pub fn new(buckets: Vec<TokenBucket>) -> MultiRateLimiter {
+        MultiRateLimiter { buckets }
+    }
+
+    /// Attempts to acquire `count` tokens from every bucket.
+    ///
+    /// Returns the error of the first bucket that would deny `count`,
+    /// for lack of tokens or any other reason such as its admission
+    /// policy, leaving every bucket untouched. Otherwise acquires from
+    /// all of them and returns `Ok(rate)`, where `rate` is the lowest
+    /// rate of token acquisition reported by the buckets.
+    ///
+    /// Every bucket is checked and acquired from at a single reading of
+    /// its clock, so its admission policy is consulted twice per
+    /// acquisition and should give the same verdict both times. The
+    /// bucket that denies an acquisition records the denial, e.g. with
+    /// its `on_denied` callback, while the others don't see it.
+    pub fn acquire(&mut self, count: f64) -> TokenAcquisitionResult {
+        let nows: Vec<_> = self.buckets.iter().map(|bucket| bucket.now()).collect();
+        let denied = self
+            .buckets
+            .iter()
+            .zip(&nows)
+            .position(|(bucket, &now)| bucket.check_at(count, bucket.reserve, now).is_err());
+
+        // The denying bucket is acquired from at the time it was checked
+        // at, so it reaches the same denial and records it as usual.
+        if let Some(index) = denied {
+            let bucket = &mut self.buckets[index];
+            let reserve = bucket.reserve;
+            return bucket.acquire_above(count, reserve, nows[index]).0;
+        }
+
+        let mut rate = f64::INFINITY;
+        for (bucket, &now) in self.buckets.iter_mut().zip(&nows) {
+            let reserve = bucket.reserve;
+            rate = rate.min(bucket.acquire_above(count, reserve, now).0?);
+        }
+        Ok(rate)
+    }
+
+    /// Returns the combined buckets, in the order they were added.
+    pub fn buckets(&self) -> &[TokenBucket] {
+        &self.buckets
+    }
+}
+
+impl BitAnd for TokenBucket {
+    type Output = MultiRateLimiter;
+
+    fn bitand(self, rhs: TokenBucket) -> MultiRateLimiter {
+        MultiRateLimiter::new(vec![self, rhs])
+    }
+}
+
+impl BitAnd<TokenBucket> for MultiRateLimiter {
+    type Output = MultiRateLimiter;
+
+    fn bitand(mut self, rhs: TokenBucket) -> MultiRateLimiter {
+        self.buckets.push(rhs);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Admission, AdmissionPolicy, TokenAcquisitionError};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // This module contains unit tests for the MultiRateLimiter implementation.
+
+    /// 1. **All Limits Apply**:
+    ///    - Test chaining three buckets with `&` and acquiring until the tightest one denies.
+    ///    - The denial should come from that bucket, and the other buckets should keep the tokens it denied.
+    #[test]
+    fn test_and_combinator() {
+        let mut limiter = TokenBucket::new(0.0, 5.0) & TokenBucket::new(0.0, 3.0) & TokenBucket::new(0.0, 4.0);
+        assert_eq!(limiter.buckets().len(), 3);
+
+        assert!(limiter.acquire(2.0).is_ok());
+        assert!(matches!(limiter.acquire(2.0), Err(TokenAcquisitionError::RateLimited { .. })));
+
+        let remaining: Vec<_> = limiter.buckets().iter().map(|b| b.remaining_after(0.0)).collect();
+        assert_eq!(remaining, [Some(3.0), Some(1.0), Some(2.0)]);
+        assert!(MultiRateLimiter::new(Vec::new()).acquire(1.0).is_ok());
+    }
+
+    /// 2. **Denials Beyond Tokens**:
+    ///    - Test a limiter whose second bucket has enough tokens but an admission policy vetoing every acquisition, and one whose second bucket only takes whole tokens.
+    ///    - Both should deny with the second bucket's error without taking any tokens from the first bucket or counting a denial on it, while a denial by the first bucket should be counted.
+    #[test]
+    fn test_all_or_nothing() {
+        struct Veto;
+
+        impl AdmissionPolicy for Veto {
+            fn admit(&self, _count: f64, _covered: bool) -> Admission {
+                Admission::Veto
+            }
+        }
+
+        let denied = Arc::new(AtomicUsize::new(0));
+        let first = || {
+            let denied = Arc::clone(&denied);
+            let mut bucket = TokenBucket::new(0.0, 5.0);
+            bucket.on_denied(move |_, _| {
+                denied.fetch_add(1, Ordering::Relaxed);
+            });
+            bucket
+        };
+
+        let mut vetoed = first() & TokenBucket::builder(0.0, 5.0).admission_policy(Arc::new(Veto)).build();
+        assert_eq!(vetoed.acquire(2.0), Err(TokenAcquisitionError::Vetoed));
+        assert_eq!(vetoed.buckets()[0].remaining_after(0.0), Some(5.0));
+
+        let mut whole = first() & TokenBucket::builder(0.0, 5.0).integer_only(true).build();
+        assert!(matches!(whole.acquire(0.5), Err(TokenAcquisitionError::FractionalCount { .. })));
+        assert_eq!(whole.buckets()[0].remaining_after(0.0), Some(5.0));
+        assert_eq!(denied.load(Ordering::Relaxed), 0);
+
+        assert!(whole.acquire(5.0).is_ok());
+        assert!(matches!(whole.acquire(1.0), Err(TokenAcquisitionError::RateLimited { .. })));
+        assert_eq!(denied.load(Ordering::Relaxed), 1);
+    }
+}
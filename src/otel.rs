@@ -0,0 +1,101 @@
+/// Receives the metrics of a [TokenBucket](struct.TokenBucket.html)
+/// built with
+/// [TokenBucketBuilder.with_meter()](struct.TokenBucketBuilder.html#method.with_meter).
+///
+/// This is the bridge to an OpenTelemetry `Meter`: implement it by
+/// forwarding to a `Counter<u64>` and a `Gauge<f64>` created from the
+/// meter once, keyed by `name`. Every acquisition that is allowed or
+/// denied for lack of tokens reports:
+///
+/// * `tokenbucket.allowed` - A counter of allowed acquisitions.
+/// * `tokenbucket.denied` - A counter of acquisitions denied for lack
+///   of tokens.
+/// * `tokenbucket.fill_ratio` - A gauge holding the fraction of the
+///   burst value in the bucket after the acquisition.
+///
+/// See the [crate documentation](index.html#metrics) for which
+/// acquisitions are reported. Only available with the `opentelemetry`
+/// feature.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::{Meter, TokenBucket};
+/// use std::sync::atomic::{AtomicU64, Ordering};
+/// use std::sync::Arc;
+///
+/// #[derive(Default)]
+/// struct Allowed(AtomicU64);
+///
+/// impl Meter for Allowed {
+///     fn add_counter(&self, name: &'static str, value: u64) {
+///         if name == "tokenbucket.allowed" {
+///             self.0.fetch_add(value, Ordering::Relaxed);
+///         }
+///     }
+///
+///     fn record_gauge(&self, _name: &'static str, _value: f64) {}
+/// }
+///
+/// let meter = Arc::new(Allowed::default());
+/// let mut tb = TokenBucket::builder(5.0, 100.0).with_meter(meter.clone()).build();
+/// tb.acquire(1.0).unwrap();
+/// assert_eq!(meter.0.load(Ordering::Relaxed), 1);
+/// ```
+pub trait Meter: Send + Sync {
+    /// Adds `value` to the counter `name`.
+    fn add_counter(&self, name: &'static str, value: u64);
+
+    /// Records `value` as the current value of the gauge `name`.
+    fn record_gauge(&self, name: &'static str, value: f64);
+}
+
+// Reports the outcome of an acquisition that left `tokens` in a bucket
+// with burst value `b` to `meter`.
+pub(crate) fn record(meter: &dyn Meter, allowed: bool, tokens: f64, b: f64) {
+    let counter = if allowed { "tokenbucket.allowed" } else { "tokenbucket.denied" };
+    meter.add_counter(counter, 1);
+    meter.record_gauge("tokenbucket.fill_ratio", crate::fill_ratio(tokens, b));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TokenBucket;
+    use std::sync::{Arc, Mutex};
+
+    // This module contains unit tests for the OpenTelemetry instrumentation.
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<(&'static str, f64)>>);
+
+    impl Meter for Recorder {
+        fn add_counter(&self, name: &'static str, value: u64) {
+            self.0.lock().unwrap().push((name, value as f64));
+        }
+
+        fn record_gauge(&self, name: &'static str, value: f64) {
+            self.0.lock().unwrap().push((name, value));
+        }
+    }
+
+    /// 1. **Reported Metrics**:
+    ///    - Test making allowed, denied and invalid acquisitions from a bucket with a meter.
+    ///    - Allowed and denied acquisitions should each report their counter and the fill ratio, and invalid ones nothing.
+    #[test]
+    fn test_with_meter() {
+        let meter = Arc::new(Recorder::default());
+        let mut tb = TokenBucket::builder(0.0, 4.0).max_cost(10.0).with_meter(meter.clone()).build();
+
+        assert!(tb.acquire(3.0).is_ok());
+        assert!(tb.acquire(2.0).is_err());
+        assert!(tb.acquire(20.0).is_err());
+
+        assert_eq!(*meter.0.lock().unwrap(), [
+            ("tokenbucket.allowed", 1.0),
+            ("tokenbucket.fill_ratio", 0.25),
+            ("tokenbucket.denied", 1.0),
+            ("tokenbucket.fill_ratio", 0.25),
+        ]);
+    }
+}
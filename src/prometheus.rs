@@ -0,0 +1,192 @@
+use crate::{fill_ratio, TokenBucket};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Collects the metrics of registered
+/// [TokenBucket](struct.TokenBucket.html)s and renders them in the
+/// Prometheus text exposition format.
+///
+/// Register buckets with
+/// [TokenBucket.register()](struct.TokenBucket.html#method.register)
+/// and serve the output of [gather()](#method.gather) from the
+/// endpoint Prometheus scrapes. Every registered bucket exports:
+///
+/// * `tokenbucket_fill_ratio` - A gauge holding the fraction of the
+///   burst value in the bucket after the latest acquisition.
+/// * `tokenbucket_allowed_total` - A counter of allowed acquisitions.
+/// * `tokenbucket_denied_total` - A counter of acquisitions denied for
+///   lack of tokens.
+///
+/// See the [crate documentation](index.html#metrics) for which
+/// acquisitions are reported. Only available with the `prometheus`
+/// feature.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::{Registry, TokenBucket};
+/// let registry = Registry::new();
+/// let mut tb = TokenBucket::new(5.0, 100.0);
+/// tb.register(&registry, &[("bucket", "api")]);
+///
+/// tb.acquire(50.0).unwrap();
+/// assert!(registry.gather().contains("tokenbucket_fill_ratio{bucket=\"api\"} 0.5"));
+/// ```
+#[derive(Default)]
+pub struct Registry {
+    series: Mutex<Vec<Arc<Series>>>,
+}
+
+// Represents the metrics of a single registered bucket. The fill
+// ratio is stored as the bits of an `f64`.
+pub(crate) struct Series {
+    labels:  String,
+    fill:    AtomicU64,
+    allowed: AtomicU64,
+    denied:  AtomicU64,
+}
+
+impl Registry {
+    /// Returns a new, empty Registry.
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    /// Returns the current metrics of every registered bucket in the
+    /// Prometheus text exposition format.
+    pub fn gather(&self) -> String {
+        let series = self.series.lock().expect("registry poisoned");
+        let mut out = String::new();
+
+        let fill = |s: &Series| f64::from_bits(s.fill.load(Ordering::Relaxed)).to_string();
+        let allowed = |s: &Series| s.allowed.load(Ordering::Relaxed).to_string();
+        let denied = |s: &Series| s.denied.load(Ordering::Relaxed).to_string();
+
+        write_family(&mut out, "tokenbucket_fill_ratio", "gauge",
+            "Fraction of the burst value in the bucket.", &series, fill);
+        write_family(&mut out, "tokenbucket_allowed_total", "counter",
+            "Number of allowed acquisitions.", &series, allowed);
+        write_family(&mut out, "tokenbucket_denied_total", "counter",
+            "Number of acquisitions denied for lack of tokens.", &series, denied);
+
+        out
+    }
+}
+
+impl Series {
+    // Records the outcome of an acquisition that left `tokens` in a
+    // bucket with burst value `b`.
+    pub(crate) fn record(&self, allowed: bool, tokens: f64, b: f64) {
+        let counter = if allowed { &self.allowed } else { &self.denied };
+        counter.fetch_add(1, Ordering::Relaxed);
+        self.fill.store(fill_ratio(tokens, b).to_bits(), Ordering::Relaxed);
+    }
+}
+
+impl TokenBucket {
+    /// Registers the bucket's metrics with `registry`, identified by
+    /// `labels`.
+    ///
+    /// From then on every call to
+    /// [acquire()](struct.TokenBucket.html#method.acquire), and every
+    /// other acquisition method, updates the fill ratio gauge and
+    /// either the allowed or the denied counter. Acquisitions rejected
+    /// as invalid, e.g. for exceeding `max_cost`, are not counted. See
+    /// [Registry](struct.Registry.html) for the exported metrics.
+    ///
+    /// Registering again reports to the new registry and labels from
+    /// then on, and the previously registered series stop updating.
+    /// Only available with the `prometheus` feature.
+    pub fn register(&mut self, registry: &Registry, labels: &[(&str, &str)]) {
+        let status = self.status();
+        let series = Arc::new(Series {
+            labels:  render_labels(labels),
+            fill:    AtomicU64::new(fill_ratio(status.tokens, status.burst).to_bits()),
+            allowed: AtomicU64::new(0),
+            denied:  AtomicU64::new(0),
+        });
+
+        registry
+            .series
+            .lock()
+            .expect("registry poisoned")
+            .push(Arc::clone(&series));
+        self.metrics = Some(series);
+    }
+}
+
+// Writes the metric family `name` to `out`, with one sample per
+// series rendered by `value`.
+fn write_family<F>(
+    out: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    series: &[Arc<Series>],
+    value: F,
+) where
+    F: Fn(&Series) -> String,
+{
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for s in series {
+        let _ = writeln!(out, "{}{} {}", name, s.labels, value(s));
+    }
+}
+
+// Renders `labels` as a Prometheus label set, e.g. `{bucket="api"}`,
+// escaping the values as the text format requires.
+fn render_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // This module contains unit tests for the Prometheus exporter implementation.
+
+    /// 1. **Exported Metrics**:
+    ///    - Test registering two buckets with different labels and making allowed, denied and invalid acquisitions.
+    ///    - The gathered output should hold each bucket's fill ratio and counters, without counting invalid acquisitions.
+    #[test]
+    fn test_gather() {
+        let registry = Registry::new();
+        let mut api = TokenBucket::builder(0.0, 4.0).max_cost(10.0).build();
+        let mut jobs = TokenBucket::new(0.0, 2.0);
+        api.register(&registry, &[("bucket", "api"), ("path", "a\"b")]);
+        jobs.register(&registry, &[("bucket", "jobs")]);
+
+        assert!(api.acquire(3.0).is_ok());
+        assert!(api.acquire(2.0).is_err());
+        assert!(api.acquire(20.0).is_err());
+
+        let output = registry.gather();
+        for line in [
+            "# TYPE tokenbucket_fill_ratio gauge",
+            "tokenbucket_fill_ratio{bucket=\"api\",path=\"a\\\"b\"} 0.25",
+            "tokenbucket_fill_ratio{bucket=\"jobs\"} 1",
+            "# TYPE tokenbucket_allowed_total counter",
+            "tokenbucket_allowed_total{bucket=\"api\",path=\"a\\\"b\"} 1",
+            "tokenbucket_denied_total{bucket=\"api\",path=\"a\\\"b\"} 1",
+            "tokenbucket_denied_total{bucket=\"jobs\"} 0",
+        ] {
+            assert!(output.lines().any(|l| l == line), "missing `{}` in:\n{}", line, output);
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use crate::{Clock, TokenAcquisitionError, TokenAcquisitionResult};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// Represents a quota of tokens per fixed window, where part of the
+/// allowance left unused at the end of a window can roll over into the
+/// next one, e.g. for monthly plans with rollover.
+///
+/// Unlike a [TokenBucket](struct.TokenBucket.html), nothing accrues
+/// during a window: each window starts with the full `allowance` plus
+/// whatever rolled over, and acquisitions draw it down until the window
+/// ends. Windows are aligned to the wall clock like those of a
+/// [HybridLimiter](struct.HybridLimiter.html).
+///
+/// Without [rollover()](#method.rollover) the quota is simply reset at
+/// every window boundary. With it, the tokens left above `threshold`
+/// carry over, up to `cap`. If whole windows pass without any
+/// acquisition, each of them rolls over in turn.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::{ManualClock, QuotaLimiter};
+/// use std::time::{Duration, SystemTime};
+///
+/// // 100 tokens a day, carrying over what is left beyond 20, up to 50.
+/// let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+/// let mut quota = QuotaLimiter::new(100.0, Duration::from_secs(86_400))
+///     .rollover(20.0, 50.0)
+///     .clock(clock.clone());
+/// assert!(quota.acquire(40.0).is_ok());
+///
+/// // 60 tokens were left, of which 40 roll over.
+/// clock.advance(Duration::from_secs(86_400));
+/// assert_eq!(quota.remaining(), 140.0);
+/// ```
+pub struct QuotaLimiter {
+    allowance: f64,
+    window:    Duration,
+    // Represents the number of unused tokens kept back from rolling
+    // over, and the most that roll over. A `cap` of zero disables
+    // rollover.
+    threshold: f64,
+    cap:       f64,
+    clock:     Option<Arc<dyn Clock>>,
+    // Represents the index, counted from the UNIX epoch, of the window
+    // `remaining` refers to. `None` until the quota is first used.
+    index:     Option<u128>,
+    // Represents the number of tokens left in the current window.
+    remaining: f64,
+    // Represents the number of tokens the current window started with
+    // on top of the allowance.
+    carried:   f64,
+}
+
+impl QuotaLimiter {
+    /// Returns a new QuotaLimiter allowing `allowance` tokens per
+    /// wall-clock aligned `window`, without rollover.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is zero.
+    pub fn new(allowance: f64, window: Duration) -> QuotaLimiter {
+        assert!(!window.is_zero(), "a QuotaLimiter window must not be zero");
+
+        QuotaLimiter {
+            allowance,
+            window,
+            threshold: 0.0,
+            cap: 0.0,
+            clock: None,
+            index: None,
+            remaining: allowance,
+            carried: 0.0,
+        }
+    }
+
+    /// Sets the quota to carry the tokens left above `threshold` at the
+    /// end of a window over into the next one, up to `cap` tokens.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threshold` or `cap` is negative or not finite.
+    pub fn rollover(mut self, threshold: f64, cap: f64) -> QuotaLimiter {
+        assert!(threshold.is_finite() && threshold >= 0.0, "the rollover threshold must be finite and non-negative");
+        assert!(cap.is_finite() && cap >= 0.0, "the rollover cap must be finite and non-negative");
+        self.threshold = threshold;
+        self.cap = cap;
+        self
+    }
+
+    /// Sets the clock the windows are read from. Defaults to the
+    /// system clock.
+    pub fn clock(mut self, clock: impl Clock + 'static) -> QuotaLimiter {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Attempts to acquire `count` tokens from the current window.
+    ///
+    /// Returns `Ok(rate)` with the allowance per second, or
+    /// `Err(TokenAcquisitionError::WindowExhausted { .. })` with the
+    /// tokens the window started with if what is left of them can't
+    /// afford `count`. Denied acquisitions take nothing.
+    pub fn acquire(&mut self, count: f64) -> TokenAcquisitionResult {
+        let since_epoch = self.since_epoch();
+        self.roll(since_epoch);
+
+        if count > self.remaining {
+            let window_ns = self.window.as_nanos();
+            let window_end = (since_epoch.as_nanos() / window_ns + 1) * window_ns;
+            return Err(TokenAcquisitionError::WindowExhausted {
+                limit:     self.allowance + self.carried,
+                resets_in: Duration::from_nanos((window_end - since_epoch.as_nanos()) as u64),
+            });
+        }
+
+        self.remaining -= count;
+        Ok(self.allowance / self.window.as_secs_f64())
+    }
+
+    /// Returns the number of tokens left in the current window.
+    pub fn remaining(&mut self) -> f64 {
+        let since_epoch = self.since_epoch();
+        self.roll(since_epoch);
+        self.remaining
+    }
+
+    /// Returns the number of tokens the current window started with on
+    /// top of the allowance.
+    pub fn carried(&mut self) -> f64 {
+        let since_epoch = self.since_epoch();
+        self.roll(since_epoch);
+        self.carried
+    }
+
+    fn since_epoch(&self) -> Duration {
+        let now = match &self.clock {
+            Some(clock) => clock.now(),
+            None => SystemTime::now(),
+        };
+        now.duration_since(SystemTime::UNIX_EPOCH).unwrap_or(Duration::ZERO)
+    }
+
+    // Moves the quota to the window containing `since_epoch`, rolling
+    // over every window boundary crossed.
+    fn roll(&mut self, since_epoch: Duration) {
+        let index = since_epoch.as_nanos() / self.window.as_nanos();
+        let current = match self.index {
+            // Windows only move forward, so a clock that jumps
+            // backwards can't reopen an exhausted window.
+            Some(current) if index <= current => return,
+            Some(current) => current,
+            None => {
+                self.index = Some(index);
+                return;
+            },
+        };
+
+        // Every idle window that follows carries at least as much as
+        // the one before, so stop once the carry no longer changes.
+        let mut boundaries = index - current;
+        while boundaries > 0 {
+            let carried = (self.remaining - self.threshold).max(0.0).min(self.cap);
+            let unchanged = boundaries < index - current && carried == self.carried;
+            self.carried = carried;
+            self.remaining = self.allowance + carried;
+            if unchanged {
+                break;
+            }
+            boundaries -= 1;
+        }
+        self.index = Some(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ManualClock;
+
+    // This module contains unit tests for the QuotaLimiter implementation.
+
+    const DAY: Duration = Duration::from_secs(86_400);
+
+    /// 1. **Reset Without Rollover**:
+    ///    - Test exhausting a daily quota without rollover, then crossing the boundary into the next day with tokens left over.
+    ///    - The exhausted quota should report when it resets, and each new day should start with exactly the allowance.
+    #[test]
+    fn test_quota_resets() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH + DAY - Duration::from_secs(10));
+        let mut quota = QuotaLimiter::new(10.0, DAY).clock(clock.clone());
+
+        assert!(quota.acquire(10.0).is_ok());
+        assert_eq!(
+            quota.acquire(1.0),
+            Err(TokenAcquisitionError::WindowExhausted {
+                limit:     10.0,
+                resets_in: Duration::from_secs(10),
+            })
+        );
+
+        clock.advance(Duration::from_secs(10));
+        assert!(quota.acquire(4.0).is_ok());
+        clock.advance(DAY);
+        assert_eq!(quota.remaining(), 10.0);
+        assert_eq!(quota.carried(), 0.0);
+    }
+
+    /// 2. **Rollover Below and At the Cap**:
+    ///    - Test leaving tokens unused across window boundaries with a rollover threshold and cap, then skipping several idle windows.
+    ///    - Only the tokens above the threshold should roll over, capped at the cap, and idle windows should keep rolling over up to the cap.
+    #[test]
+    fn test_quota_rollover() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut quota = QuotaLimiter::new(100.0, DAY).rollover(20.0, 50.0).clock(clock.clone());
+
+        // 30 left, 10 of them above the threshold.
+        assert!(quota.acquire(70.0).is_ok());
+        clock.advance(DAY);
+        assert_eq!(quota.carried(), 10.0);
+        assert_eq!(quota.remaining(), 110.0);
+
+        // 90 left, capped at 50.
+        assert!(quota.acquire(20.0).is_ok());
+        clock.advance(DAY);
+        assert_eq!(quota.carried(), 50.0);
+        assert!(quota.acquire(150.0).is_ok());
+        assert!(matches!(
+            quota.acquire(1.0),
+            Err(TokenAcquisitionError::WindowExhausted { limit, .. }) if limit == 150.0
+        ));
+
+        // An exhausted day followed by idle ones: nothing rolls over
+        // at first, then the cap is reached again.
+        clock.advance(DAY);
+        assert_eq!(quota.carried(), 0.0);
+        assert!(quota.acquire(90.0).is_ok());
+        clock.advance(DAY * 3);
+        assert_eq!(quota.carried(), 50.0);
+        assert_eq!(quota.remaining(), 150.0);
+    }
+}
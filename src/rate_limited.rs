@@ -0,0 +1,90 @@
+use crate::TokenBucket;
+
+/// Represents a resource whose use is gated by a
+/// [TokenBucket](struct.TokenBucket.html), e.g. a client for an
+/// upstream with a rate limit.
+///
+/// The wrapped value is only reachable through
+/// [try_with()](#method.try_with), which acquires the cost of a use
+/// before handing it out, so it can't be used past the limit by
+/// accident.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::{RateLimited, TokenBucket};
+/// struct Client {
+///     sent: Vec<String>,
+/// }
+///
+/// impl Client {
+///     fn send(&mut self, message: &str) -> usize {
+///         self.sent.push(message.to_string());
+///         self.sent.len()
+///     }
+/// }
+///
+/// let client = Client { sent: Vec::new() };
+/// let mut client = RateLimited::new(client, TokenBucket::new(1.0, 2.0));
+///
+/// assert_eq!(client.try_with(1.0, |c| c.send("a")), Some(1));
+/// assert_eq!(client.try_with(1.0, |c| c.send("b")), Some(2));
+/// assert_eq!(client.try_with(1.0, |c| c.send("c")), None);
+/// assert_eq!(client.into_inner().sent, ["a", "b"]);
+/// ```
+pub struct RateLimited<T> {
+    inner:  T,
+    bucket: TokenBucket,
+}
+
+impl<T> RateLimited<T> {
+    /// Returns a new RateLimited gating every use of `inner` by
+    /// `bucket`.
+    pub fn new(inner: T, bucket: TokenBucket) -> RateLimited<T> {
+        RateLimited { inner, bucket }
+    }
+
+    /// Attempts to acquire `cost` tokens and, if they were acquired,
+    /// runs `f` with the wrapped value and returns its result.
+    ///
+    /// Returns `None` without running `f` if the acquisition fails,
+    /// e.g. because the bucket is rate limited.
+    pub fn try_with<R, F>(&mut self, cost: f64, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        self.bucket.acquire(cost).ok()?;
+        Some(f(&mut self.inner))
+    }
+
+    /// Returns the bucket gating the wrapped value.
+    pub fn bucket(&mut self) -> &mut TokenBucket {
+        &mut self.bucket
+    }
+
+    /// Returns the wrapped value, dropping the bucket.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // This module contains unit tests for the RateLimited implementation.
+
+    /// 1. **Gated Use**:
+    ///    - Test using a wrapped counter until its bucket runs out, then topping the bucket up.
+    ///    - The closure should only run while tokens are acquired, and the wrapped value should reflect exactly those runs.
+    #[test]
+    fn test_try_with() {
+        let mut counter = RateLimited::new(0, TokenBucket::manual(3.0));
+        assert_eq!(counter.try_with(2.0, |n| { *n += 1; *n }), Some(1));
+        assert_eq!(counter.try_with(2.0, |n| { *n += 1; *n }), None);
+
+        counter.bucket().tick(1.0);
+        assert_eq!(counter.try_with(2.0, |n| { *n += 1; *n }), Some(2));
+        assert_eq!(counter.into_inner(), 2);
+    }
+}
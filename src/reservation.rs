@@ -0,0 +1,157 @@
+use crate::SharedTokenBucket;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Represents tokens taken from a
+/// [SharedTokenBucket](struct.SharedTokenBucket.html) for work that
+/// hasn't happened yet, as returned by
+/// [SharedTokenBucket.reserve()](struct.SharedTokenBucket.html#method.reserve).
+///
+/// The tokens are removed from the bucket as soon as the reservation
+/// is made. [commit()](#method.commit) keeps them spent, while dropping
+/// an uncommitted reservation, e.g. because the work failed, returns
+/// them to the bucket.
+#[must_use = "dropping a reservation returns its tokens to the bucket"]
+pub struct Reservation {
+    bucket:    SharedTokenBucket,
+    count:     f64,
+    committed: bool,
+}
+
+// Represents the bookkeeping of a bucket's outstanding reservations.
+pub(crate) struct Reservations {
+    outstanding: AtomicUsize,
+    max:         AtomicUsize,
+}
+
+impl Reservations {
+    pub(crate) fn new() -> Reservations {
+        Reservations {
+            outstanding: AtomicUsize::new(0),
+            max:         AtomicUsize::new(usize::MAX),
+        }
+    }
+
+    // Claims a slot for a new reservation, returning whether one was
+    // free.
+    fn claim(&self) -> bool {
+        let max = self.max.load(Ordering::Relaxed);
+        self.outstanding
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| if n < max { Some(n + 1) } else { None })
+            .is_ok()
+    }
+
+    fn release(&self) {
+        self.outstanding.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl Reservation {
+    /// Returns the number of tokens reserved.
+    pub fn count(&self) -> f64 {
+        self.count
+    }
+
+    /// Keeps the reserved tokens spent, e.g. once the work they were
+    /// reserved for has happened.
+    pub fn commit(mut self) {
+        self.committed = true;
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.bucket.lock().tick(self.count);
+        }
+        self.bucket.reservations().release();
+    }
+}
+
+impl SharedTokenBucket {
+    /// Attempts to acquire `count` tokens from the bucket for work
+    /// that may not happen, returning a
+    /// [Reservation](struct.Reservation.html) holding them.
+    ///
+    /// Returns `None` if the tokens can't be acquired right now, or if
+    /// the maximum number of uncommitted reservations set with
+    /// [set_max_reservations()](#method.set_max_reservations) is
+    /// already outstanding.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let bucket = TokenBucket::builder(0.0, 10.0).build_shared();
+    /// let reservation = bucket.reserve(10.0).unwrap();
+    /// assert!(bucket.acquire(1.0).is_err());
+    ///
+    /// // The work failed, so hand the tokens back.
+    /// drop(reservation);
+    /// assert!(bucket.acquire(10.0).is_ok());
+    /// ```
+    pub fn reserve(&self, count: f64) -> Option<Reservation> {
+        if !self.reservations().claim() {
+            return None;
+        }
+
+        if self.acquire(count).is_err() {
+            self.reservations().release();
+            return None;
+        }
+
+        Some(Reservation {
+            bucket:    self.clone(),
+            count,
+            committed: false,
+        })
+    }
+
+    /// Sets the largest number of reservations that may be outstanding
+    /// at once, counting every reservation not yet committed or
+    /// dropped.
+    ///
+    /// This bounds the tokens a bug that keeps reservations around,
+    /// e.g. by leaking them, can tie up. Lowering the limit doesn't
+    /// affect reservations that are already outstanding. Defaults to
+    /// no limit.
+    pub fn set_max_reservations(&self, max: usize) {
+        self.reservations().max.store(max, Ordering::Relaxed);
+    }
+
+    /// Returns the number of reservations currently outstanding.
+    pub fn outstanding_reservations(&self) -> usize {
+        self.reservations().outstanding.load(Ordering::Acquire)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SharedTokenBucket, TokenBucket};
+    use std::mem;
+
+    // This module contains unit tests for the Reservation implementation.
+
+    /// 1. **Reservation Limit**:
+    ///    - Test exhausting a limit of two reservations, then committing, dropping and leaking reservations.
+    ///    - Reserving past the limit should fail even with tokens left, free slots should be reusable, and dropped reservations should refund their tokens.
+    #[test]
+    fn test_max_reservations() {
+        let bucket = SharedTokenBucket::new(TokenBucket::new(0.0, 10.0));
+        bucket.set_max_reservations(2);
+
+        let first = bucket.reserve(2.0).unwrap();
+        let second = bucket.reserve(3.0).unwrap();
+        assert!(bucket.reserve(1.0).is_none());
+        assert_eq!(bucket.outstanding_reservations(), 2);
+
+        first.commit();
+        drop(second);
+        assert_eq!(bucket.outstanding_reservations(), 0);
+        assert_eq!(bucket.lock().remaining_after(0.0), Some(8.0));
+
+        mem::forget(bucket.reserve(1.0).unwrap());
+        mem::forget(bucket.reserve(1.0).unwrap());
+        assert!(bucket.reserve(1.0).is_none());
+        assert!(bucket.acquire(6.0).is_ok());
+    }
+}
@@ -0,0 +1,140 @@
+use crate::builder::unit_interval;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// Represents how a [TokenBucket](struct.TokenBucket.html) retries a
+/// rate limited acquisition, as used by
+/// [TokenBucket.acquire_with_retry()](struct.TokenBucket.html#method.acquire_with_retry).
+///
+/// A policy is configured once and can be reused across any number of
+/// acquisitions and buckets. It defaults to waiting until the tokens
+/// are available between attempts, without jitter.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::{Backoff, RetryPolicy};
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy::new(5)
+///     .backoff(Backoff::Exponential {
+///         initial: Duration::from_millis(10),
+///         max: Duration::from_secs(1),
+///     })
+///     .jitter(true);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    backoff:      Backoff,
+    jitter:       bool,
+}
+
+/// Represents how long a [RetryPolicy](struct.RetryPolicy.html) waits
+/// before the next attempt.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backoff {
+    /// Wait until the bucket is expected to hold the tokens, as
+    /// reported by
+    /// [TokenBucket.time_until_available()](struct.TokenBucket.html#method.time_until_available).
+    UntilAvailable,
+    /// Wait the same duration before every retry.
+    Fixed(Duration),
+    /// Wait `initial` before the first retry and double the wait before
+    /// every further retry, up to `max`.
+    Exponential { initial: Duration, max: Duration },
+}
+
+impl RetryPolicy {
+    /// Returns a new RetryPolicy making at most `max_attempts`
+    /// acquisition attempts in total, including the first one.
+    ///
+    /// At least one attempt is always made, even if `max_attempts` is
+    /// zero.
+    pub fn new(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            backoff: Backoff::UntilAvailable,
+            jitter: false,
+        }
+    }
+
+    /// Sets how long to wait between attempts. Defaults to
+    /// `Backoff::UntilAvailable`.
+    pub fn backoff(mut self, backoff: Backoff) -> RetryPolicy {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Sets whether every wait is extended by a random amount of up to
+    /// half its length.
+    ///
+    /// This spreads out the retries of callers that were rate limited
+    /// at the same time, so that they don't all retry at once.
+    /// Defaults to `false`.
+    pub fn jitter(mut self, jitter: bool) -> RetryPolicy {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Returns the largest number of attempts the policy makes.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts.max(1)
+    }
+
+    // Returns how long to wait before retry number `retry`, counted
+    // from zero, given that the tokens are expected to be available
+    // in `available_in`.
+    pub(crate) fn delay(&self, retry: u32, available_in: Duration) -> Duration {
+        let delay = match self.backoff {
+            Backoff::UntilAvailable => available_in,
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { initial, max } => initial
+                .saturating_mul(2u32.saturating_pow(retry))
+                .min(max),
+        };
+
+        if self.jitter {
+            let seed = RandomState::new().build_hasher().finish();
+            delay.saturating_add(delay.mul_f64(0.5 * unit_interval(seed)))
+        } else {
+            delay
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // This module contains unit tests for the RetryPolicy implementation.
+
+    /// 1. **Backoff Delays**:
+    ///    - Test the delay of each backoff strategy over several retries, with and without jitter.
+    ///    - Exponential delays should double up to their maximum, and jitter should extend a delay by at most half.
+    #[test]
+    fn test_backoff_delays() {
+        let available_in = Duration::from_millis(30);
+        let until = RetryPolicy::new(3);
+        assert_eq!(until.delay(0, available_in), available_in);
+
+        let fixed = RetryPolicy::new(3).backoff(Backoff::Fixed(Duration::from_millis(5)));
+        assert_eq!(fixed.delay(4, available_in), Duration::from_millis(5));
+
+        let exponential = RetryPolicy::new(3).backoff(Backoff::Exponential {
+            initial: Duration::from_millis(10),
+            max: Duration::from_millis(50),
+        });
+        let delays: Vec<_> = (0..4).map(|retry| exponential.delay(retry, available_in)).collect();
+        assert_eq!(delays, [10, 20, 40, 50].map(Duration::from_millis));
+        assert_eq!(exponential.delay(u32::MAX, available_in), Duration::from_millis(50));
+
+        let jittered = fixed.jitter(true);
+        for retry in 0..10 {
+            let delay = jittered.delay(retry, available_in);
+            assert!(delay >= Duration::from_millis(5) && delay <= Duration::from_micros(7500));
+        }
+        assert_eq!(RetryPolicy::new(0).max_attempts(), 1);
+    }
+}
@@ -0,0 +1,141 @@
+/// Represents how a [TokenBucket](struct.TokenBucket.html) rounds the
+/// tokens accrued on every refill, set with
+/// [TokenBucketBuilder.rounding()](struct.TokenBucketBuilder.html#method.rounding).
+///
+/// A bucket refills whenever it is acquired from, crediting the tokens
+/// accrued since the last successful acquisition. Rounding applies to
+/// that amount before it is added to the bucket, e.g. so a bucket
+/// handing out whole slots only ever holds whole tokens.
+///
+/// # Rates and refill intervals
+///
+/// What a refill doesn't credit, or credits ahead of time, is carried
+/// over to the next one, so rounding never changes the long-run rate
+/// `r`, however frequent the refills:
+///
+/// * `Floor` credits a token once it has fully accrued.
+/// * `Round` credits a token once half of it has accrued, i.e. up to
+///   half a token early.
+///
+/// Only a bucket that fills up drops the carried part, along with the
+/// rest of the accrual that overflows. Waits, such as those reported by
+/// [TokenBucket.time_until_available()](struct.TokenBucket.html#method.time_until_available),
+/// end when the refill would credit the missing tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum RoundingMode {
+    /// Credit the accrued tokens as they are. This is the default,
+    /// smooth behavior.
+    #[default]
+    Fractional,
+    /// Credit only the whole tokens accrued.
+    Floor,
+    /// Credit the accrued tokens rounded to the nearest whole token,
+    /// with halves rounded up.
+    Round,
+}
+
+impl RoundingMode {
+    // Returns `accrued` rounded according to the mode.
+    #[inline(always)]
+    pub(crate) fn apply(self, accrued: f64) -> f64 {
+        match self {
+            RoundingMode::Fractional => accrued,
+            RoundingMode::Floor => accrued.floor(),
+            // Unlike `f64::round`, this rounds the halves of negative
+            // values up too, as a carried remainder may be.
+            RoundingMode::Round => (accrued + 0.5).floor(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Clock, Decision, ManualClock, TokenBucket};
+    use std::time::{Duration, SystemTime};
+
+    // This module contains unit tests for the RoundingMode implementation.
+
+    /// 1. **Rounded Refills**:
+    ///    - Test a drained bucket in every rounding mode over three refill intervals of one and a half tokens, acquiring a token after each.
+    ///    - Fractional accrual should be credited as is, while floored and rounded accrual should credit whole tokens, carrying the half token of every refill over to the next one.
+    #[test]
+    fn test_rounding_modes() {
+        let cases = [
+            (RoundingMode::Fractional, [1.5, 2.0, 2.5]),
+            (RoundingMode::Floor, [1.0, 2.0, 2.0]),
+            (RoundingMode::Round, [2.0, 2.0, 3.0]),
+        ];
+        for &(mode, levels) in &cases {
+            let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+            let mut bucket = TokenBucket::builder(1.0, 10.0).rounding(mode).clock(clock.clone()).build();
+            bucket.drain();
+
+            for &level in &levels {
+                clock.advance(Duration::from_millis(1500));
+                assert_eq!(bucket.remaining_after(0.0), Some(level), "{:?}", mode);
+                assert!(bucket.acquire(1.0).is_ok());
+            }
+        }
+
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut floored = TokenBucket::builder(1.0, 10.0).rounding(RoundingMode::Floor).clock(clock.clone()).build();
+        floored.drain();
+        clock.advance(Duration::from_millis(999));
+        assert!(floored.acquire(1.0).is_err());
+        clock.advance(Duration::from_millis(1));
+        assert!(floored.acquire(1.0).is_ok());
+    }
+
+    /// 2. **Long-Run Rate**:
+    ///    - Test acquiring a token every half token interval for ten seconds from a drained bucket in every rounding mode.
+    ///    - Every mode should admit the ten tokens that accrue, give or take the half token rounding may credit early.
+    #[test]
+    fn test_rounding_rate() {
+        for &mode in &[RoundingMode::Fractional, RoundingMode::Floor, RoundingMode::Round] {
+            let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+            let mut bucket = TokenBucket::builder(1.0, 1.0).rounding(mode).clock(clock.clone()).build();
+            bucket.drain();
+
+            let mut admitted = 0;
+            for _ in 0..20 {
+                clock.advance(Duration::from_millis(500));
+                if bucket.acquire(1.0).is_ok() {
+                    admitted += 1;
+                }
+            }
+            assert_eq!(admitted, 10, "{:?}", mode);
+        }
+    }
+
+    /// 3. **Rounded Retries**:
+    ///    - Test deciding an acquisition of two tokens from a drained bucket in every rounding mode after 1.4 token intervals, then again just before and once the suggested retry is due.
+    ///    - The retry should be due exactly when the refill would credit the missing token, which rounded accrual does once half of it accrued.
+    #[test]
+    fn test_rounding_retry_after() {
+        let cases = [
+            (RoundingMode::Fractional, 600),
+            (RoundingMode::Floor, 600),
+            (RoundingMode::Round, 100),
+        ];
+        for &(mode, millis) in &cases {
+            let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+            let mut bucket = TokenBucket::builder(1.0, 10.0).rounding(mode).clock(clock.clone()).build();
+            bucket.drain();
+            clock.advance(Duration::from_millis(1400));
+
+            let retry_after = match bucket.decide(2.0) {
+                Decision::Throttle { retry_after } => retry_after,
+                Decision::Allow => panic!("{:?} allowed", mode),
+            };
+            let expected = Duration::from_millis(millis);
+            assert!(retry_after.abs_diff(expected) < Duration::from_micros(1), "{:?}: {:?}", mode, retry_after);
+            assert_eq!(bucket.next_available_at(2.0), clock.now().checked_add(retry_after));
+
+            clock.advance(retry_after - Duration::from_micros(1));
+            assert!(bucket.decide(2.0) != Decision::Allow, "{:?}", mode);
+            clock.advance(Duration::from_micros(1));
+            assert_eq!(bucket.decide(2.0), Decision::Allow, "{:?}", mode);
+        }
+    }
+}
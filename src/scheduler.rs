@@ -0,0 +1,133 @@
+use crate::TokenBucket;
+
+/// Represents a weighted round-robin scheduler over several named
+/// [TokenBucket](struct.TokenBucket.html) objects, e.g. one per tenant.
+///
+/// Each call to [next()](#method.next) picks, among the tenants whose
+/// bucket can currently afford the work, the one that is furthest
+/// behind its weighted share, and acquires the cost from its bucket.
+/// Over time each tenant is served in proportion to its weight, for as
+/// long as its bucket allows.
+///
+/// The interleaving follows smooth weighted round-robin, so a tenant
+/// with weight 3 next to one with weight 1 is served `A A B A`, not
+/// `A A A B`.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::{TokenBucket, WeightedScheduler};
+/// let mut scheduler = WeightedScheduler::new();
+/// scheduler.add("gold", TokenBucket::new(5.0, 100.0), 3);
+/// scheduler.add("bronze", TokenBucket::new(5.0, 100.0), 1);
+///
+/// while let Some(tenant) = scheduler.next(1.0) {
+///     println!("serving {}", tenant);
+///     # break;
+/// }
+/// ```
+pub struct WeightedScheduler<K> {
+    tenants: Vec<Tenant<K>>,
+}
+
+struct Tenant<K> {
+    name:    K,
+    bucket:  TokenBucket,
+    weight:  u32,
+    // Represents how far ahead (negative) or behind (positive) of its
+    // weighted share the tenant currently is.
+    current: i64,
+}
+
+impl<K> WeightedScheduler<K> {
+    /// Returns a new WeightedScheduler without any tenants.
+    pub fn new() -> WeightedScheduler<K> {
+        WeightedScheduler {
+            tenants: Vec::new(),
+        }
+    }
+
+    /// Adds a tenant named `name`, limited by `bucket` and served in
+    /// proportion to `weight` relative to the other tenants.
+    pub fn add(&mut self, name: K, bucket: TokenBucket, weight: u32) {
+        self.tenants.push(Tenant {
+            name,
+            bucket,
+            weight,
+            current: 0,
+        });
+    }
+
+    /// Returns the next tenant to serve and acquires `cost` tokens from
+    /// its bucket.
+    ///
+    /// Only tenants whose bucket can currently afford `cost`, as
+    /// reported by
+    /// [TokenBucket.peek_allow()](struct.TokenBucket.html#method.peek_allow),
+    /// are considered. Returns `None` if every tenant is throttled.
+    pub fn next(&mut self, cost: f64) -> Option<&K> {
+        let mut total: i64 = 0;
+        let mut best: Option<usize> = None;
+        for i in 0..self.tenants.len() {
+            let tenant = &mut self.tenants[i];
+            if tenant.weight == 0 || !tenant.bucket.peek_allow(cost) {
+                continue;
+            }
+
+            tenant.current += i64::from(tenant.weight);
+            total += i64::from(tenant.weight);
+
+            // Ties go to the tenant that was added first.
+            let current = tenant.current;
+            if best.is_none_or(|b| current > self.tenants[b].current) {
+                best = Some(i);
+            }
+        }
+
+        let tenant = &mut self.tenants[best?];
+        tenant.current -= total;
+        tenant.bucket.acquire(cost).ok()?;
+        Some(&tenant.name)
+    }
+}
+
+impl<K> Default for WeightedScheduler<K> {
+    fn default() -> WeightedScheduler<K> {
+        WeightedScheduler::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // This module contains unit tests for the WeightedScheduler implementation.
+
+    /// 1. **Weighted Interleaving**:
+    ///    - Test scheduling two tenants with weights 3 and 1 whose buckets are plentiful.
+    ///    - The heavier tenant should be served three times as often, interleaved smoothly.
+    #[test]
+    fn test_weighted_interleaving() {
+        let mut scheduler = WeightedScheduler::new();
+        scheduler.add("a", TokenBucket::new(0.001, 100.0), 3);
+        scheduler.add("b", TokenBucket::new(0.001, 100.0), 1);
+
+        let order: Vec<&str> = (0..8).map(|_| *scheduler.next(1.0).unwrap()).collect();
+        assert_eq!(order, vec!["a", "a", "b", "a", "a", "a", "b", "a"]);
+    }
+
+    /// 2. **Throttled Tenants Are Skipped**:
+    ///    - Test scheduling two tenants where the heavier one runs out of tokens.
+    ///    - Once a tenant's bucket is empty the other should be served, and `None` should be returned once both are throttled.
+    #[test]
+    fn test_throttled_tenants_skipped() {
+        let mut scheduler = WeightedScheduler::new();
+        scheduler.add("a", TokenBucket::new(0.001, 1.0), 3);
+        scheduler.add("b", TokenBucket::new(0.001, 2.0), 1);
+
+        assert_eq!(scheduler.next(1.0), Some(&"a"));
+        assert_eq!(scheduler.next(1.0), Some(&"b"));
+        assert_eq!(scheduler.next(1.0), Some(&"b"));
+        assert_eq!(scheduler.next(1.0), None);
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{Clock, TokenAcquisitionResult, TokenBucket};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Represents one logical rate limit sharded across several
+/// [TokenBucket](struct.TokenBucket.html) objects.
+///
+/// Each shard is guarded by its own mutex and receives `r / n` and
+/// `b / n` of the configured rate and burst. Acquisitions are spread
+/// across the shards round-robin, so concurrent callers rarely contend
+/// for the same lock while the set as a whole still approximates the
+/// configured limit.
+///
+/// A single acquisition is served by a single shard, so it can never
+/// acquire more than `b / n` tokens at once.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::TokenBucketSet;
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let set = Arc::new(TokenBucketSet::new(100.0, 40.0, 4));
+/// let handles: Vec<_> = (0..4).map(|_| {
+///     let set = Arc::clone(&set);
+///     thread::spawn(move || set.acquire(1.0).is_ok())
+/// }).collect();
+/// for handle in handles {
+///     assert!(handle.join().unwrap());
+/// }
+/// ```
+pub struct TokenBucketSet {
+    shards: Vec<Mutex<TokenBucket>>,
+    // Represents the index of the shard the next acquisition starts
+    // with. It only ever increases and is taken modulo the number of
+    // shards.
+    next:   AtomicUsize,
+}
+
+impl TokenBucketSet {
+    /// Returns a new TokenBucketSet splitting a rate of `r` tokens per
+    /// second and a burst value of `b` evenly across `shards` buckets.
+    ///
+    /// See [TokenBucket::new()](struct.TokenBucket.html#method.new) for
+    /// the meaning of `r` and `b`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is zero.
+    pub fn new(r: f64, b: f64, shards: usize) -> TokenBucketSet {
+        assert!(shards > 0, "a TokenBucketSet needs at least one shard");
+
+        let n = shards as f64;
+        TokenBucketSet {
+            shards: (0..shards)
+                .map(|_| Mutex::new(TokenBucket::new(r / n, b / n)))
+                .collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns a new TokenBucketSet like
+    /// [TokenBucketSet::new()](#method.new), with every shard reading
+    /// the time from `clock`, e.g. a shared
+    /// [ManualClock](struct.ManualClock.html) in tests.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is zero.
+    pub fn with_clock<C: Clock + Clone + 'static>(r: f64, b: f64, shards: usize, clock: C) -> TokenBucketSet {
+        assert!(shards > 0, "a TokenBucketSet needs at least one shard");
+
+        let n = shards as f64;
+        TokenBucketSet {
+            shards: (0..shards)
+                .map(|_| Mutex::new(TokenBucket::builder(r / n, b / n).clock(clock.clone()).build()))
+                .collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of shards in the set.
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Attempts to acquire `count` tokens from one of the shards.
+    ///
+    /// The next shard in round-robin order is tried first. If it can't
+    /// satisfy the acquisition, the remaining shards are tried in turn
+    /// and the first success is returned. If no shard can satisfy it,
+    /// the result of the last shard tried is returned.
+    pub fn acquire(&self, count: f64) -> TokenAcquisitionResult {
+        let n = self.shards.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+
+        let mut result = None;
+        for i in 0..n {
+            let mut shard = self.shards[(start + i) % n]
+                .lock()
+                .expect("token bucket shard poisoned");
+            match shard.acquire(count) {
+                Ok(rate) => return Ok(rate),
+                Err(err) => result = Some(Err(err)),
+            }
+        }
+
+        result.expect("a TokenBucketSet always has at least one shard")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ManualClock;
+    use std::thread;
+    use std::time::{Duration, SystemTime};
+
+    // This module contains unit tests for the TokenBucketSet implementation.
+
+    /// 1. **Fallback Across Shards**:
+    ///    - Test acquiring every token in the set from a single thread.
+    ///    - Once a shard is empty the acquisition should fall back to the others, so the whole burst should be acquirable before limiting kicks in.
+    #[test]
+    fn test_fallback_across_shards() {
+        let set = TokenBucketSet::new(0.001, 4.0, 4);
+        for _ in 0..4 {
+            assert!(set.acquire(1.0).is_ok());
+        }
+        assert!(set.acquire(1.0).is_err());
+    }
+
+    /// 2. **Aggregate Throughput**:
+    ///    - Test hammering a sharded set from several threads at a time, over five refill periods of a shared manual clock.
+    ///    - Exactly the configured burst plus the configured rate over the period should be admitted, however the threads interleave.
+    #[test]
+    fn test_aggregate_throughput() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let set = TokenBucketSet::with_clock(200.0, 20.0, 4, clock.clone());
+        let step = Duration::from_millis(100);
+
+        let mut admitted = 0;
+        for i in 0..=5 {
+            if i > 0 {
+                clock.advance(step);
+            }
+            admitted += thread::scope(|scope| {
+                let handles: Vec<_> = (0..4)
+                    .map(|_| scope.spawn(|| (0..10).filter(|_| set.acquire(1.0).is_ok()).count()))
+                    .collect();
+                handles.into_iter().map(|h| h.join().unwrap()).sum::<usize>()
+            });
+        }
+
+        // The clock doesn't move while the threads run, so a denial
+        // means every shard was empty and stays so until the next step.
+        assert_eq!(admitted, 20 + 200 * 5 / 10);
+    }
+}
@@ -0,0 +1,681 @@
+use crate::reservation::Reservations;
+use crate::{TokenAcquisitionResult, TokenBucket};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+
+/// Represents a [TokenBucket](struct.TokenBucket.html) that can be
+/// shared between threads.
+///
+/// Cloning a SharedTokenBucket is cheap and returns another handle to
+/// the same underlying bucket.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::{SharedTokenBucket, TokenBucket};
+/// use std::thread;
+///
+/// let bucket = SharedTokenBucket::new(TokenBucket::new(5.0, 100.0));
+/// let handles: Vec<_> = (0..4).map(|_| {
+///     let bucket = bucket.clone();
+///     thread::spawn(move || bucket.acquire(1.0).is_ok())
+/// }).collect();
+/// for handle in handles {
+///     assert!(handle.join().unwrap());
+/// }
+/// ```
+#[derive(Clone)]
+pub struct SharedTokenBucket {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    bucket: Mutex<TokenBucket>,
+    // Represents the FIFO queue of fair waiters as a pair of ticket
+    // counters. `turn` is signalled whenever the head of the queue
+    // moves.
+    queue:  Mutex<Queue>,
+    turn:   Condvar,
+    // Represents the waiters of `acquire_notified`.
+    waiters: Mutex<Waiters>,
+    reservations: Reservations,
+}
+
+struct Queue {
+    // Represents the ticket handed to the next fair waiter to arrive.
+    next:    u64,
+    // Represents the ticket of the fair waiter currently being served.
+    serving: u64,
+}
+
+struct Waiters {
+    // Represents the id handed to the next waiter to arrive.
+    next_id: u64,
+    // Represents whether a waiter is sleeping on behalf of all others.
+    timer:   bool,
+    // Represents the waiters waiting to be woken, in arrival order.
+    parked:  Vec<Parked>,
+}
+
+struct Parked {
+    id:    u64,
+    cost:  f64,
+    waker: Waker,
+}
+
+// Represents a single caller of `acquire_notified`. Dropping it, even
+// when the acquisition is cancelled, leaves the waiters consistent.
+struct Waiter {
+    inner: Arc<Inner>,
+    id:    u64,
+    cost:  f64,
+    state: WaiterState,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum WaiterState {
+    Running,
+    Parked,
+    Timer,
+}
+
+// Represents the turn of the fair waiter currently being served.
+// Dropping it, even when the acquisition panics, serves the next one.
+struct Serving<'a> {
+    inner: &'a Inner,
+}
+
+// Resolves once the waiter is either the timer or has been woken.
+struct Turn<'a> {
+    waiter: &'a mut Waiter,
+}
+
+impl SharedTokenBucket {
+    /// Returns a new SharedTokenBucket wrapping `bucket`.
+    pub fn new(bucket: TokenBucket) -> SharedTokenBucket {
+        SharedTokenBucket {
+            inner: Arc::new(Inner {
+                bucket: Mutex::new(bucket),
+                queue: Mutex::new(Queue { next: 0, serving: 0 }),
+                turn: Condvar::new(),
+                waiters: Mutex::new(Waiters {
+                    next_id: 0,
+                    timer: false,
+                    parked: Vec::new(),
+                }),
+                reservations: Reservations::new(),
+            }),
+        }
+    }
+
+    /// Locks the underlying bucket, giving access to its full API.
+    ///
+    /// Other handles block on any acquisition until the returned guard
+    /// is dropped.
+    pub fn lock(&self) -> MutexGuard<'_, TokenBucket> {
+        self.inner.bucket.lock().expect("token bucket poisoned")
+    }
+
+    // Returns the bookkeeping of the bucket's outstanding reservations.
+    pub(crate) fn reservations(&self) -> &Reservations {
+        &self.inner.reservations
+    }
+
+    /// Attempts to acquire `count` tokens from the bucket. See
+    /// [TokenBucket.acquire()](struct.TokenBucket.html#method.acquire).
+    pub fn acquire(&self, count: f64) -> TokenAcquisitionResult {
+        self.lock().acquire(count)
+    }
+
+    /// Acquires `count` tokens from the bucket, blocking the current
+    /// thread until enough tokens are available. See
+    /// [TokenBucket.acquire_wait()](struct.TokenBucket.html#method.acquire_wait).
+    ///
+    /// The bucket is only locked while attempting an acquisition, not
+    /// while sleeping. Concurrent waiters race for replenished tokens,
+    /// so the order in which they are served is undefined. Use
+    /// [acquire_wait_fair()](#method.acquire_wait_fair) when ordering
+    /// matters.
+    pub fn acquire_wait(&self, count: f64) -> TokenAcquisitionResult {
+        loop {
+            let wait = {
+                let mut bucket = self.lock();
+                match bucket.acquire_blocking_step(count) {
+                    Ok(result) => return result,
+                    Err(wait) => wait,
+                }
+            };
+            thread::sleep(wait);
+        }
+    }
+
+    /// Attempts to acquire `count` tokens from the bucket from within an
+    /// async context, returning the result immediately.
+    ///
+    /// Unlike [acquire_async()](#method.acquire_async), this never
+    /// waits for tokens to become available: a rate limited acquisition
+    /// returns `Err(TokenAcquisitionError::RateLimited { .. })` right
+    /// away, leaving any backoff to the caller. The bucket's mutex is
+    /// only held for the duration of the acquisition itself.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// # use tokenbucket::TokenBucket;
+    /// let bucket = TokenBucket::builder(5.0, 100.0).build_shared();
+    /// if bucket.try_acquire_async(1.0).await.is_err() {
+    ///     // shed the request
+    /// }
+    /// ```
+    pub async fn try_acquire_async(&self, count: f64) -> TokenAcquisitionResult {
+        self.acquire(count)
+    }
+
+    /// Acquires `count` tokens from the bucket, asynchronously waiting
+    /// until enough tokens are available.
+    ///
+    /// This waits by awaiting the future returned by `sleep`, just like
+    /// [TokenBucket.acquire_async()](struct.TokenBucket.html#method.acquire_async),
+    /// which documents the injection point. The bucket is not locked
+    /// while waiting. Use
+    /// [try_acquire_async()](#method.try_acquire_async) to get the
+    /// result without waiting.
+    pub async fn acquire_async<F, Fut>(&self, count: f64, sleep: F) -> TokenAcquisitionResult
+    where
+        F: Fn(Duration) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        loop {
+            let wait = match self.lock().acquire_blocking_step(count) {
+                Ok(result) => return result,
+                Err(wait) => wait,
+            };
+            sleep(wait).await;
+        }
+    }
+
+    /// Acquires `count` tokens from the bucket right away if possible,
+    /// otherwise returns a future that waits until the tokens are
+    /// available and then acquires them.
+    ///
+    /// The first attempt is made when this method is called, not when
+    /// the returned future is first polled, so tasks submitted in a
+    /// batch are considered in submission order. A queued future
+    /// sleeps until the time reported by
+    /// [TokenBucket.next_available_at()](struct.TokenBucket.html#method.next_available_at)
+    /// at submission, measured against the bucket's clock, and then
+    /// acquires like [acquire_async()](#method.acquire_async), waiting
+    /// further if other callers took the tokens first. This lets a
+    /// scheduler submit many tasks at once, e.g. with `join_all`, and
+    /// have the bucket pace their start times.
+    ///
+    /// The returned future holds its own handle to the bucket. See
+    /// [TokenBucket.acquire_async()](struct.TokenBucket.html#method.acquire_async)
+    /// for how to provide `sleep`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// # use tokenbucket::TokenBucket;
+    /// let bucket = TokenBucket::builder(10.0, 1.0).build_shared();
+    /// let tasks = (0..5).map(|i| {
+    ///     let start = bucket.acquire_or_queue(1.0, tokio::time::sleep);
+    ///     async move {
+    ///         start.await?;
+    ///         run_job(i).await
+    ///     }
+    /// });
+    /// futures::future::join_all(tasks).await;
+    /// ```
+    pub fn acquire_or_queue<F, Fut>(
+        &self,
+        count: f64,
+        sleep: F,
+    ) -> impl Future<Output = TokenAcquisitionResult>
+    where
+        F: Fn(Duration) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let bucket = self.clone();
+        let attempt = {
+            let mut locked = bucket.lock();
+            match locked.acquire_blocking_step(count) {
+                Ok(result) => Ok(result),
+                Err(_) => Err(locked.next_available_at(count)),
+            }
+        };
+
+        async move {
+            let at = match attempt {
+                Ok(result) => return result,
+                Err(at) => at,
+            };
+            if let Some(at) = at {
+                let now = bucket.lock().now();
+                sleep(at.duration_since(now).unwrap_or(Duration::ZERO)).await;
+            }
+            bucket.acquire_async(count, sleep).await
+        }
+    }
+
+    /// Acquires `count` tokens from the bucket, asynchronously waiting
+    /// until enough tokens are available, without every waiter
+    /// sleeping on its own.
+    ///
+    /// Concurrent callers of this method share a single sleep: one of
+    /// them sleeps until the earliest point at which any of their
+    /// acquisitions can be afforded, then wakes exactly the waiters
+    /// the bucket can serve, in arrival order. All other waiters stay
+    /// parked without polling or holding a timer, which keeps many
+    /// concurrent waiters cheap. See
+    /// [TokenBucket.acquire_async()](struct.TokenBucket.html#method.acquire_async)
+    /// for how to provide `sleep`.
+    ///
+    /// Tokens taken through any other method are not coordinated with
+    /// the waiters, but are accounted for when they are woken.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// # use tokenbucket::TokenBucket;
+    /// let bucket = TokenBucket::builder(10.0, 5.0).build_shared();
+    /// let batch = (1..=5).map(|cost| bucket.acquire_notified(cost as f64, tokio::time::sleep));
+    /// futures::future::join_all(batch).await;
+    /// ```
+    pub async fn acquire_notified<F, Fut>(&self, count: f64, sleep: F) -> TokenAcquisitionResult
+    where
+        F: Fn(Duration) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let mut waiter = Waiter::new(Arc::clone(&self.inner), count);
+        loop {
+            if let Ok(result) = self.lock().acquire_blocking_step(count) {
+                return result;
+            }
+
+            Turn { waiter: &mut waiter }.await;
+            if waiter.state == WaiterState::Timer {
+                sleep(waiter.timer_wait()).await;
+                waiter.finish_timer();
+            }
+        }
+    }
+
+    /// Acquires `count` tokens from the bucket, blocking the current
+    /// thread until enough tokens are available and every earlier
+    /// caller of this method has been served.
+    ///
+    /// Callers are served in the order they arrived, so no waiter can
+    /// starve behind a stream of later ones. Only callers of this
+    /// method take part in the queue: tokens taken through
+    /// [acquire()](#method.acquire) or
+    /// [acquire_wait()](#method.acquire_wait) on any handle are not
+    /// held back for it.
+    pub fn acquire_wait_fair(&self, count: f64) -> TokenAcquisitionResult {
+        self.acquire_wait_fair_if(count, |_| true)
+            .expect("an admitted fair waiter is always served")
+    }
+
+    /// Acquires `count` tokens from the bucket like
+    /// [acquire_wait_fair()](#method.acquire_wait_fair), but only after
+    /// `wait` has agreed to queue up behind the callers already in
+    /// line.
+    ///
+    /// `wait` is called with the caller's position in line, i.e. the
+    /// number of callers of the fair methods ahead of it, and returns
+    /// whether to join the queue. This lets an overloaded service shed
+    /// a request up front instead of waiting behind a long queue.
+    /// Returns `None` if `wait` declined, without having queued or
+    /// acquired anything.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// let bucket = TokenBucket::builder(50.0, 1.0).build_shared();
+    /// // Shed the request if more than 10 callers are already waiting.
+    /// match bucket.acquire_wait_fair_if(1.0, |position| position <= 10) {
+    ///     Some(result) => assert!(result.is_ok()),
+    ///     None => println!("shed"),
+    /// }
+    /// ```
+    pub fn acquire_wait_fair_if<F>(&self, count: f64, wait: F) -> Option<TokenAcquisitionResult>
+    where
+        F: FnOnce(usize) -> bool,
+    {
+        let mut queue = self.inner.queue.lock().expect("token bucket queue poisoned");
+        if !wait((queue.next - queue.serving) as usize) {
+            return None;
+        }
+        let ticket = queue.next;
+        queue.next += 1;
+        while queue.serving != ticket {
+            queue = self.inner.turn.wait(queue).expect("token bucket queue poisoned");
+        }
+        drop(queue);
+
+        let _serving = Serving { inner: &self.inner };
+        Some(self.acquire_wait(count))
+    }
+
+    /// Returns the number of callers of the fair methods, e.g.
+    /// [acquire_wait_fair()](#method.acquire_wait_fair), that are
+    /// currently in line, including the one being served.
+    pub fn fair_queue_len(&self) -> usize {
+        let queue = self.inner.queue.lock().expect("token bucket queue poisoned");
+        (queue.next - queue.serving) as usize
+    }
+}
+
+impl Waiter {
+    fn new(inner: Arc<Inner>, cost: f64) -> Waiter {
+        let id = {
+            let mut waiters = inner.waiters.lock().expect("token bucket waiters poisoned");
+            waiters.next_id += 1;
+            waiters.next_id
+        };
+
+        Waiter {
+            inner,
+            id,
+            cost,
+            state: WaiterState::Running,
+        }
+    }
+
+    // Returns how long the timer sleeps: until the cheapest pending
+    // acquisition can be afforded.
+    fn timer_wait(&self) -> Duration {
+        let cost = {
+            let waiters = self.inner.waiters.lock().expect("token bucket waiters poisoned");
+            waiters.parked.iter().map(|p| p.cost).fold(self.cost, f64::min)
+        };
+
+        self.inner
+            .bucket
+            .lock()
+            .expect("token bucket poisoned")
+            .time_until_available(cost)
+    }
+
+    // Gives up the timer after sleeping, waking every parked waiter
+    // that the bucket can now serve after this waiter is served.
+    fn finish_timer(&mut self) {
+        let mut waiters = self.inner.waiters.lock().expect("token bucket waiters poisoned");
+        waiters.timer = false;
+        self.state = WaiterState::Running;
+
+        let mut available = {
+            let bucket = self.inner.bucket.lock().expect("token bucket poisoned");
+            let (tokens, _) = bucket.replenished(bucket.now());
+            tokens - bucket.reserve
+        };
+        if self.cost <= available {
+            available -= self.cost;
+        }
+
+        waiters.parked.retain(|parked| {
+            if parked.cost > available {
+                return true;
+            }
+            available -= parked.cost;
+            parked.waker.wake_by_ref();
+            false
+        });
+    }
+}
+
+impl Drop for Serving<'_> {
+    fn drop(&mut self) {
+        let mut queue = self.inner.queue.lock().unwrap_or_else(PoisonError::into_inner);
+        queue.serving += 1;
+        self.inner.turn.notify_all();
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        let mut waiters = match self.inner.waiters.lock() {
+            Ok(waiters) => waiters,
+            Err(_) => return,
+        };
+        match self.state {
+            WaiterState::Parked => waiters.parked.retain(|parked| parked.id != self.id),
+            WaiterState::Timer => waiters.timer = false,
+            WaiterState::Running => {},
+        }
+
+        // Hand the timer over, so that the remaining waiters don't
+        // stay parked forever.
+        if !waiters.timer && !waiters.parked.is_empty() {
+            waiters.parked.remove(0).waker.wake();
+        }
+    }
+}
+
+impl Future for Turn<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let waiter = &mut *self.waiter;
+        let mut waiters = waiter.inner.waiters.lock().expect("token bucket waiters poisoned");
+
+        if waiter.state == WaiterState::Parked {
+            return match waiters.parked.iter_mut().find(|parked| parked.id == waiter.id) {
+                Some(parked) => {
+                    parked.waker.clone_from(cx.waker());
+                    Poll::Pending
+                },
+                None => {
+                    waiter.state = WaiterState::Running;
+                    Poll::Ready(())
+                },
+            };
+        }
+
+        if !waiters.timer {
+            waiters.timer = true;
+            waiter.state = WaiterState::Timer;
+            return Poll::Ready(());
+        }
+
+        waiters.parked.push(Parked {
+            id: waiter.id,
+            cost: waiter.cost,
+            waker: cx.waker().clone(),
+        });
+        waiter.state = WaiterState::Parked;
+        Poll::Pending
+    }
+}
+
+impl From<TokenBucket> for SharedTokenBucket {
+    fn from(bucket: TokenBucket) -> SharedTokenBucket {
+        SharedTokenBucket::new(bucket)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use crate::TokenAcquisitionError;
+    use std::time::Instant;
+
+    // This module contains unit tests for the SharedTokenBucket implementation.
+
+    /// 1. **Shared Blocking Acquisition**:
+    ///    - Test `acquire_wait` through two handles of the same bucket.
+    ///    - Both handles should draw from the same tokens, so the second acquisition should block until replenishment.
+    #[test]
+    fn test_shared_acquire_wait() {
+        let bucket = SharedTokenBucket::new(TokenBucket::new(10.0, 1.0));
+        let other = bucket.clone();
+        assert!(bucket.acquire(1.0).is_ok());
+        assert!(other.acquire(1.0).is_err());
+        assert!(other.acquire_wait(1.0).is_ok());
+    }
+
+    /// 2. **Fair Waiter Ordering**:
+    ///    - Test several threads arriving one after another at `acquire_wait_fair` on an empty bucket.
+    ///    - The threads should be served in the order they arrived.
+    #[test]
+    fn test_acquire_wait_fair_order() {
+        let bucket = SharedTokenBucket::new(TokenBucket::new(20.0, 1.0));
+        assert!(bucket.acquire(1.0).is_ok());
+        let served = Arc::new(Mutex::new(Vec::new()));
+
+        let handles: Vec<_> = (0..5)
+            .map(|i| {
+                let bucket = bucket.clone();
+                let served = Arc::clone(&served);
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(10 * i));
+                    assert!(bucket.acquire_wait_fair(1.0).is_ok());
+                    served.lock().unwrap().push(i);
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(*served.lock().unwrap(), vec![0, 1, 2, 3, 4]);
+    }
+
+    /// 3. **Asynchronous Acquisition Without Waiting**:
+    ///    - Test `try_acquire_async` and `acquire_async` on an empty shared bucket.
+    ///    - `try_acquire_async` should return the denial rather than waiting, while `acquire_async` should wait until the next token is available.
+    #[test]
+    fn test_try_acquire_async() {
+        let bucket = SharedTokenBucket::new(TokenBucket::new(10.0, 1.0));
+        assert!(block_on(bucket.try_acquire_async(1.0)).is_ok());
+
+        let start = Instant::now();
+        assert!(matches!(
+            block_on(bucket.try_acquire_async(1.0)),
+            Err(TokenAcquisitionError::RateLimited { .. })
+        ));
+
+        let sleep = |duration| async move { thread::sleep(duration) };
+        assert!(block_on(bucket.acquire_async(1.0, sleep)).is_ok());
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+
+    /// 4. **Acquire or Queue**:
+    ///    - Test `acquire_or_queue` for a batch of tasks on a bucket holding a single token, without polling the futures at first.
+    ///    - The first task should acquire at submission, and the others should be paced by the replenishment rate.
+    #[test]
+    fn test_acquire_or_queue() {
+        let bucket = SharedTokenBucket::new(TokenBucket::new(20.0, 1.0));
+        let sleep = |duration| async move { thread::sleep(duration) };
+
+        let start = Instant::now();
+        let tasks: Vec<_> = (0..3).map(|_| bucket.acquire_or_queue(1.0, sleep)).collect();
+        assert!(bucket.acquire(1.0).is_err());
+
+        for task in tasks {
+            assert!(block_on(task).is_ok());
+        }
+        assert!(start.elapsed() >= Duration::from_millis(90));
+        assert!(bucket.acquire(1.0).is_err());
+    }
+
+    /// 5. **Notified Waiters**:
+    ///    - Test several threads waiting in `acquire_notified` with different costs on an empty bucket.
+    ///    - Every waiter should be woken once its tokens are available, so all acquisitions complete at the replenishment rate.
+    #[test]
+    fn test_acquire_notified() {
+        let bucket = SharedTokenBucket::new(TokenBucket::new(50.0, 3.0));
+        assert!(bucket.acquire(3.0).is_ok());
+
+        let start = Instant::now();
+        let handles: Vec<_> = [3.0, 1.0, 2.0, 1.0]
+            .iter()
+            .map(|&cost| {
+                let bucket = bucket.clone();
+                thread::spawn(move || {
+                    let sleep = |duration| async move { thread::sleep(duration) };
+                    block_on(bucket.acquire_notified(cost, sleep))
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert!(handle.join().unwrap().is_ok());
+        }
+
+        assert!(start.elapsed() >= Duration::from_millis(130));
+        assert!(bucket.acquire(1.0).is_err());
+        let waiters = bucket.inner.waiters.lock().unwrap();
+        assert!(!waiters.timer && waiters.parked.is_empty());
+    }
+
+    /// 6. **Queue Positions**:
+    ///    - Test three fair waiters queueing on an empty bucket one after another, then a fourth caller that only waits behind fewer than three.
+    ///    - Each waiter should be told the number of callers ahead of it, and the fourth caller should be shed without queueing.
+    #[test]
+    fn test_fair_queue_positions() {
+        let bucket = SharedTokenBucket::new(TokenBucket::new(5.0, 1.0));
+        assert!(bucket.acquire(1.0).is_ok());
+
+        let handles: Vec<_> = (0..3)
+            .map(|i| {
+                let handle = {
+                    let bucket = bucket.clone();
+                    thread::spawn(move || {
+                        let mut reported = None;
+                        let result = bucket.acquire_wait_fair_if(1.0, |position| {
+                            reported = Some(position);
+                            true
+                        });
+                        (reported, result)
+                    })
+                };
+                while bucket.fair_queue_len() <= i {
+                    thread::yield_now();
+                }
+                handle
+            })
+            .collect();
+
+        let mut shed_at = None;
+        assert_eq!(
+            bucket.acquire_wait_fair_if(1.0, |position| {
+                shed_at = Some(position);
+                position < 3
+            }),
+            None
+        );
+        assert_eq!(shed_at, Some(3));
+
+        for (i, handle) in handles.into_iter().enumerate() {
+            let (reported, result) = handle.join().unwrap();
+            assert_eq!(reported, Some(i));
+            assert!(matches!(result, Some(Ok(_))));
+        }
+        assert_eq!(bucket.fair_queue_len(), 0);
+    }
+
+    /// 7. **Panicking Fair Waiter**:
+    ///    - Test a fair waiter whose acquisition panics in the bucket's `on_denied` callback.
+    ///    - The panicking waiter should leave the queue, so the next fair waiter is served first in line instead of waiting forever.
+    #[test]
+    fn test_fair_waiter_panics() {
+        let mut inner = TokenBucket::new(5.0, 1.0);
+        inner.on_denied(|_, _| panic!("denied"));
+        let bucket = SharedTokenBucket::new(inner);
+        assert!(bucket.acquire(1.0).is_ok());
+
+        let panicked = std::panic::catch_unwind(|| bucket.acquire_wait_fair(1.0));
+        assert!(panicked.is_err());
+        assert_eq!(bucket.fair_queue_len(), 0);
+
+        bucket.inner.bucket.clear_poison();
+        bucket.lock().on_denied(|_, _| {});
+        assert!(matches!(bucket.acquire_wait_fair_if(1.0, |position| position == 0), Some(Ok(_))));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,114 @@
+use crate::{ManualClock, TokenBucket};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// Represents the outcome of feeding a load profile through a
+/// [TokenBucket](struct.TokenBucket.html), as returned by
+/// [TokenBucket.simulate()](struct.TokenBucket.html#method.simulate).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationReport {
+    /// Whether each event was allowed, in the order of the events.
+    pub decisions: Vec<bool>,
+    /// The number of events that were allowed.
+    pub admitted:  usize,
+    /// The number of events that were denied.
+    pub denied:    usize,
+    /// The longest time a denied event would have had to wait for its
+    /// tokens, or `Duration::MAX` if an event exceeded the burst value.
+    /// Zero if no event was denied.
+    pub max_wait:  Duration,
+}
+
+impl TokenBucket {
+    /// Runs the bucket's algorithm against `events`, pairs of the time
+    /// an acquisition happens and its cost, and reports which of them
+    /// would be allowed.
+    ///
+    /// The simulation operates on a copy of the bucket's current state
+    /// and configuration driven by the event times alone, so it is
+    /// fully deterministic and neither the bucket nor its clock are
+    /// touched. Callbacks and metrics are not part of the copy. Events
+    /// should be in chronological order; an event earlier than the one
+    /// before it, or than the bucket's last acquisition, is treated as
+    /// happening at that time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tokenbucket::TokenBucket;
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let token_bucket = TokenBucket::new(10.0, 2.0);
+    /// let start = SystemTime::now();
+    /// let at = |ms| start + Duration::from_millis(ms);
+    ///
+    /// let report = token_bucket.simulate(&[(at(0), 1.0), (at(0), 1.0), (at(0), 1.0), (at(100), 1.0)]);
+    /// assert_eq!(report.decisions, [true, true, false, true]);
+    /// assert_eq!((report.admitted, report.denied), (3, 1));
+    /// ```
+    pub fn simulate(&self, events: &[(SystemTime, f64)]) -> SimulationReport {
+        let clock = ManualClock::new(self.last);
+        let mut bucket = self.detached(Arc::new(clock.clone()));
+
+        let mut report = SimulationReport {
+            decisions: Vec::with_capacity(events.len()),
+            admitted:  0,
+            denied:    0,
+            max_wait:  Duration::ZERO,
+        };
+        for &(when, count) in events {
+            clock.set(when.max(bucket.last));
+            let allowed = bucket.acquire(count).is_ok();
+            if allowed {
+                report.admitted += 1;
+            } else {
+                report.denied += 1;
+                let wait = match bucket.next_available_at(count) {
+                    Some(at) => at.duration_since(bucket.now()).unwrap_or(Duration::ZERO),
+                    None => Duration::MAX,
+                };
+                report.max_wait = report.max_wait.max(wait);
+            }
+            report.decisions.push(allowed);
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // This module contains unit tests for the simulation implementation.
+
+    /// 1. **Load Profile**:
+    ///    - Test simulating a burst followed by a steady trickle against a partially drained bucket, then a single oversized request, then the same profile against a bucket in shadow mode.
+    ///    - The decisions, counts and longest wait should follow the algorithm exactly, the oversized request should never become available, the bucket itself should be untouched, and the shadow bucket should allow every event.
+    #[test]
+    fn test_simulate() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut bucket = TokenBucket::builder(2.0, 4.0).clock(clock.clone()).build();
+        bucket.acquire(1.0).unwrap();
+
+        let at = |ms| SystemTime::UNIX_EPOCH + Duration::from_millis(ms);
+        let events = [
+            (at(0), 3.0),
+            (at(0), 1.0),
+            (at(250), 1.0),
+            (at(500), 1.0),
+            (at(1500), 2.0),
+            (at(1500), 1.0),
+        ];
+        let report = bucket.simulate(&events);
+        assert_eq!(report.decisions, [true, false, false, true, true, false]);
+        assert_eq!((report.admitted, report.denied), (3, 3));
+        assert_eq!(report.max_wait, Duration::from_millis(500));
+
+        let report = bucket.simulate(&[(at(0), 5.0)]);
+        assert_eq!(report.max_wait, Duration::MAX);
+        assert_eq!(bucket.remaining_after(0.0), Some(3.0));
+
+        let shadow = TokenBucket::builder(2.0, 4.0).shadow(true).clock(clock.clone()).build();
+        assert_eq!(shadow.simulate(&events).decisions, [true; 6]);
+    }
+}
@@ -0,0 +1,41 @@
+use crate::{ManualClock, TokenBucket};
+use std::time::Duration;
+
+/// An iterator that drives a [TokenBucket](struct.TokenBucket.html) at
+/// a fixed cadence on a [ManualClock](struct.ManualClock.html),
+/// yielding whether each acquisition was allowed.
+///
+/// Returned by
+/// [TokenBucket.ticks()](struct.TokenBucket.html#method.ticks). The
+/// iterator never ends, so limit it with e.g. `take(n)`.
+pub struct Ticks<'a> {
+    bucket:   &'a mut TokenBucket,
+    clock:    ManualClock,
+    interval: Duration,
+    cost:     f64,
+}
+
+impl<'a> Ticks<'a> {
+    pub(crate) fn new(
+        bucket: &'a mut TokenBucket,
+        clock: &ManualClock,
+        interval: Duration,
+        cost: f64,
+    ) -> Ticks<'a> {
+        Ticks {
+            bucket,
+            clock: clock.clone(),
+            interval,
+            cost,
+        }
+    }
+}
+
+impl Iterator for Ticks<'_> {
+    type Item = bool;
+
+    fn next(&mut self) -> Option<bool> {
+        self.clock.advance(self.interval);
+        Some(self.bucket.acquire(self.cost).is_ok())
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{TokenAcquisitionError, TokenAcquisitionResult, TokenBucket};
+use std::time::SystemTime;
+
+/// Represents an allowance split into two compartments that refill at
+/// different rates: a steady compartment for sustained use and a burst
+/// reserve drawn on once the steady compartment runs out.
+///
+/// Each compartment is a [TokenBucket](struct.TokenBucket.html) of its
+/// own, so its rate and capacity are set independently, e.g. a steady
+/// allowance refilled quickly up to a small capacity next to a large
+/// burst reserve that only recovers slowly. Acquisitions draw from the
+/// steady compartment first and take whatever it lacks from the burst
+/// reserve.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::{TieredTokenBucket, TokenBucket};
+/// // 10 per second sustained, plus a reserve of 100 that refills at 1
+/// // per second.
+/// let mut tiered = TieredTokenBucket::new(TokenBucket::new(10.0, 10.0), TokenBucket::new(1.0, 100.0));
+/// assert!(tiered.acquire(60.0).is_ok());
+/// assert!(tiered.acquire(60.0).is_err());
+/// ```
+pub struct TieredTokenBucket {
+    steady: TokenBucket,
+    burst:  TokenBucket,
+}
+
+impl TieredTokenBucket {
+    /// Returns a new TieredTokenBucket drawing from `steady` first and
+    /// from `burst` for the rest.
+    pub fn new(steady: TokenBucket, burst: TokenBucket) -> TieredTokenBucket {
+        TieredTokenBucket { steady, burst }
+    }
+
+    /// Attempts to acquire `count` tokens, as many as possible from
+    /// the steady compartment and the rest from the burst reserve.
+    ///
+    /// Returns the result of acquiring from the steady compartment, or
+    /// from the burst reserve if the steady compartment is empty. Both
+    /// compartments are checked before either is acquired from, so if
+    /// one of them denies its part, for lack of tokens or any other
+    /// reason such as its `max_cost`, its error is returned and nothing
+    /// is taken from the other.
+    pub fn acquire(&mut self, count: f64) -> TokenAcquisitionResult {
+        let steady_now = self.steady.now();
+        let burst_now = self.burst.now();
+        let from_steady = count.min(available_at(&self.steady, steady_now));
+        let from_burst = count - from_steady;
+
+        let use_steady = from_steady > 0.0 || from_burst <= 0.0;
+        if use_steady {
+            check(&mut self.steady, from_steady, steady_now)?;
+        }
+        if from_burst > 0.0 {
+            check(&mut self.burst, from_burst, burst_now)?;
+            let rate = charge(&mut self.burst, from_burst, burst_now)?;
+            if !use_steady {
+                return Ok(rate);
+            }
+        }
+        charge(&mut self.steady, from_steady, steady_now)
+    }
+
+    /// Returns a reference to the steady compartment.
+    pub fn steady(&self) -> &TokenBucket {
+        &self.steady
+    }
+
+    /// Returns a reference to the burst reserve.
+    pub fn burst(&self) -> &TokenBucket {
+        &self.burst
+    }
+}
+
+// Returns the error `bucket` denies `count` tokens at `now` with, if
+// any. A denial is reached again by acquiring from `bucket` at the same
+// time, so it records the denial as usual.
+fn check(bucket: &mut TokenBucket, count: f64, now: SystemTime) -> Result<(), TokenAcquisitionError> {
+    let reserve = bucket.reserve;
+    match bucket.check_at(count, reserve, now) {
+        Ok(()) => Ok(()),
+        Err(err) => bucket.acquire_above(count, reserve, now).0.and(Err(err)),
+    }
+}
+
+// Acquires `count` tokens from `bucket` at `now`.
+fn charge(bucket: &mut TokenBucket, count: f64, now: SystemTime) -> TokenAcquisitionResult {
+    let reserve = bucket.reserve;
+    bucket.acquire_above(count, reserve, now).0
+}
+
+// Returns the number of tokens an ordinary acquisition could take from
+// `bucket` at `now`.
+fn available_at(bucket: &TokenBucket, now: SystemTime) -> f64 {
+    let (tokens, _) = bucket.replenished(now.max(bucket.last));
+    bucket.whole((tokens - bucket.reserve).max(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Admission, AdmissionPolicy, ManualClock};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    // This module contains unit tests for the TieredTokenBucket implementation.
+
+    // Returns the number of tokens an ordinary acquisition could take
+    // from `bucket` right now.
+    fn available(bucket: &TokenBucket) -> f64 {
+        bucket.available().into()
+    }
+
+    /// 1. **Drawing Across Compartments**:
+    ///    - Test acquisitions covered by the steady compartment, spanning both compartments, and exceeding both, with the compartments refilling at different rates.
+    ///    - The steady compartment should be drawn on first, the burst reserve should only cover the rest, and a denied acquisition should leave both untouched.
+    #[test]
+    fn test_tiered_acquire() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let steady = TokenBucket::builder(10.0, 10.0).clock(clock.clone()).build();
+        let burst = TokenBucket::builder(1.0, 50.0).clock(clock.clone()).build();
+        let mut tiered = TieredTokenBucket::new(steady, burst);
+        let levels = |t: &TieredTokenBucket| (available(t.steady()), available(t.burst()));
+
+        assert!(tiered.acquire(4.0).is_ok());
+        assert_eq!(levels(&tiered), (6.0, 50.0));
+        assert!(tiered.acquire(26.0).is_ok());
+        assert_eq!(levels(&tiered), (0.0, 30.0));
+        assert!(tiered.acquire(5.0).is_ok());
+        assert_eq!(levels(&tiered), (0.0, 25.0));
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(levels(&tiered), (10.0, 26.0));
+        assert!(tiered.acquire(15.0).is_ok());
+        assert_eq!(levels(&tiered), (0.0, 21.0));
+
+        assert!(matches!(tiered.acquire(40.0), Err(TokenAcquisitionError::RateLimited { .. })));
+        assert_eq!(levels(&tiered), (0.0, 21.0));
+    }
+
+    /// 2. **Steady Compartment Denials**:
+    ///    - Test acquisitions spanning both compartments that the steady compartment denies for exceeding its `max_cost` and through a vetoing admission policy.
+    ///    - The steady compartment's error should be returned without taking anything from the burst reserve.
+    #[test]
+    fn test_tiered_steady_denies() {
+        struct Veto;
+
+        impl AdmissionPolicy for Veto {
+            fn admit(&self, _count: f64, _covered: bool) -> Admission {
+                Admission::Veto
+            }
+        }
+
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let burst = || TokenBucket::builder(1.0, 50.0).clock(clock.clone()).build();
+
+        let steady = TokenBucket::builder(10.0, 10.0).max_cost(5.0).clock(clock.clone()).build();
+        let mut capped = TieredTokenBucket::new(steady, burst());
+        assert!(matches!(capped.acquire(12.0), Err(TokenAcquisitionError::CostExceedsMax { .. })));
+        assert_eq!(available(capped.burst()), 50.0);
+
+        let steady = TokenBucket::builder(10.0, 10.0).admission_policy(Arc::new(Veto)).clock(clock.clone()).build();
+        let mut vetoed = TieredTokenBucket::new(steady, burst());
+        assert_eq!(vetoed.acquire(12.0), Err(TokenAcquisitionError::Vetoed));
+        assert_eq!(available(vetoed.burst()), 50.0);
+    }
+}
@@ -0,0 +1,49 @@
+use std::sync::{Arc, Mutex};
+use std::task::Waker;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+// Represents a background thread that wakes a task once, at a deadline
+// measured on the bucket's clock. Used by `TokenBucket::poll_acquire`.
+pub(crate) struct Timer {
+    deadline: SystemTime,
+    // Represents the task to wake. `None` once the timer has fired.
+    waker:    Arc<Mutex<Option<Waker>>>,
+}
+
+impl Timer {
+    // Starts a timer that wakes `waker` after `wait`, which is how far
+    // `deadline` lies in the future.
+    pub(crate) fn start(deadline: SystemTime, wait: Duration, waker: &Waker) -> Timer {
+        let shared = Arc::new(Mutex::new(Some(waker.clone())));
+        let fired = Arc::clone(&shared);
+        thread::spawn(move || {
+            thread::sleep(wait);
+            let waker = fired.lock().expect("timer poisoned").take();
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        });
+
+        Timer {
+            deadline,
+            waker: shared,
+        }
+    }
+
+    // Makes the timer wake `waker` instead, if it hasn't fired yet and
+    // fires no later than `deadline`. Returns whether it did.
+    pub(crate) fn rearm(&self, deadline: SystemTime, waker: &Waker) -> bool {
+        if self.deadline > deadline {
+            return false;
+        }
+
+        match self.waker.lock().expect("timer poisoned").as_mut() {
+            Some(registered) => {
+                registered.clone_from(waker);
+                true
+            },
+            None => false,
+        }
+    }
+}
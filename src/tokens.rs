@@ -0,0 +1,120 @@
+use std::fmt;
+use std::ops::{Add, AddAssign, Mul, Sub, SubAssign};
+
+/// Represents a number of tokens, as opposed to a rate or any other
+/// quantity kept as an `f64`.
+///
+/// [TokenBucket.acquire()](struct.TokenBucket.html#method.acquire)
+/// takes anything that converts into Tokens, so existing call sites
+/// passing an `f64` keep working, and
+/// [TokenBucket.available()](struct.TokenBucket.html#method.available)
+/// returns Tokens. Convert back with `f64::from()`, `.into()` or the
+/// public field.
+///
+/// Arithmetic on Tokens never goes below zero: subtracting more tokens
+/// than there are, or scaling by a negative factor, leaves zero.
+/// Conversions keep the value as is, so an invalid count such as a NaN
+/// still reaches the bucket and is denied there.
+///
+/// # Example
+///
+/// ```
+/// # use tokenbucket::{TokenBucket, Tokens};
+/// let mut token_bucket = TokenBucket::new(0.0, 100.0);
+/// let cost = Tokens(30.0) + Tokens(10.0);
+/// assert!(token_bucket.acquire(cost).is_ok());
+/// assert_eq!(token_bucket.available(), Tokens(100.0) - cost);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+pub struct Tokens(pub f64);
+
+impl Tokens {
+    /// No tokens.
+    pub const ZERO: Tokens = Tokens(0.0);
+}
+
+impl From<f64> for Tokens {
+    fn from(tokens: f64) -> Tokens {
+        Tokens(tokens)
+    }
+}
+
+impl From<Tokens> for f64 {
+    fn from(tokens: Tokens) -> f64 {
+        tokens.0
+    }
+}
+
+impl Add for Tokens {
+    type Output = Tokens;
+
+    fn add(self, rhs: Tokens) -> Tokens {
+        Tokens((self.0 + rhs.0).max(0.0))
+    }
+}
+
+impl Sub for Tokens {
+    type Output = Tokens;
+
+    fn sub(self, rhs: Tokens) -> Tokens {
+        Tokens((self.0 - rhs.0).max(0.0))
+    }
+}
+
+impl Mul<f64> for Tokens {
+    type Output = Tokens;
+
+    fn mul(self, rhs: f64) -> Tokens {
+        Tokens((self.0 * rhs).max(0.0))
+    }
+}
+
+impl AddAssign for Tokens {
+    fn add_assign(&mut self, rhs: Tokens) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for Tokens {
+    fn sub_assign(&mut self, rhs: Tokens) {
+        *self = *self - rhs;
+    }
+}
+
+impl fmt::Display for Tokens {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} tokens", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // This module contains unit tests for the Tokens implementation.
+
+    /// 1. **Clamped Arithmetic**:
+    ///    - Test adding, subtracting, scaling and converting token counts, including results below zero.
+    ///    - Results should follow plain arithmetic while non-negative and stop at zero otherwise, while conversions should keep the value as is.
+    #[test]
+    fn test_tokens_arithmetic() {
+        assert_eq!(Tokens(2.0) + Tokens(3.5), Tokens(5.5));
+        assert_eq!(Tokens(5.0) - Tokens(3.0), Tokens(2.0));
+        assert_eq!(Tokens(3.0) - Tokens(5.0), Tokens::ZERO);
+        assert_eq!(Tokens(4.0) * 0.5, Tokens(2.0));
+        assert_eq!(Tokens(4.0) * -1.0, Tokens::ZERO);
+
+        let mut tokens = Tokens(1.0);
+        tokens += Tokens(2.0);
+        tokens -= Tokens(10.0);
+        assert_eq!(tokens, Tokens::ZERO);
+
+        assert_eq!(Tokens(-3.0) + Tokens(1.0), Tokens::ZERO);
+        assert_eq!(Tokens::from(-3.0).0, -3.0);
+        assert!(Tokens::from(f64::NAN).0.is_nan());
+
+        let level: f64 = Tokens(7.0).into();
+        assert_eq!(level, 7.0);
+        assert_eq!(Tokens(1.5).to_string(), "1.5 tokens");
+    }
+}
@@ -0,0 +1,48 @@
+//! Verifies that the hot acquisition path never touches the heap.
+//!
+//! This lives in its own test binary because it installs a counting
+//! global allocator.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use tokenbucket::TokenBucket;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+/// 1. **Allocation-Free Acquisition**:
+///    - Test many allowed and denied acquisitions, including with a replenish callback registered.
+///    - Neither path, nor the structured error, should allocate.
+#[test]
+fn test_acquire_does_not_allocate() {
+    let mut bucket = TokenBucket::builder(1_000.0, 10.0).max_cost(5.0).build();
+    bucket.on_replenish(|_| {});
+
+    let before = allocations();
+    for _ in 0..1_000 {
+        let _ = bucket.acquire(1.0);
+        let _ = bucket.acquire(6.0);
+    }
+    assert_eq!(allocations(), before);
+}